@@ -0,0 +1,82 @@
+//! Compares tokenizing an ASCII document through the ASCII fast path of `CharIterator` with
+//! tokenizing the same document one character at a time, the way string tokens were read before
+//! the fast path. Run with `cargo bench`.
+
+use mdrs::bytes::{Bytes, CharIterator, CharSource, Checkpoint, Encoding};
+use mdrs::tokenizer::Tokenizer;
+use std::time::{Duration, Instant};
+
+const SAMPLE: &str = "# Heading\n\nSome *emphasis*, **strong** text and `code` with a [link](http://x.com).\n\n- item one\n- item two\n\n> quote\n\n```rust\nfn main() {}\n```\n\n";
+
+/// A `CharIterator` without its fast path: runs of characters are read one at a time through
+/// the default `CharSource::read_while`
+struct CharPath<'a>(&'a mut CharIterator);
+
+impl CharSource for CharPath<'_> {
+    fn read(&mut self) -> Bytes {
+        self.0.read()
+    }
+
+    fn look_ahead(&self, offset: usize) -> Bytes {
+        self.0.look_ahead(offset)
+    }
+
+    fn tell(&self) -> usize {
+        self.0.tell()
+    }
+
+    fn slice(&self, start: usize, end: usize) -> String {
+        self.0.slice(start, end)
+    }
+
+    fn mark(&self) -> Checkpoint {
+        self.0.mark()
+    }
+
+    fn reset_to(&mut self, checkpoint: Checkpoint) {
+        self.0.reset_to(checkpoint)
+    }
+}
+
+fn tokenize<S: CharSource>(chars: &mut S) -> usize {
+    let mut tokenizer = Tokenizer::new(chars);
+
+    let mut count = 0;
+    while !tokenizer.consume().is_eof() {
+        count += 1;
+    }
+
+    count
+}
+
+/// Tokenizes the whole of `chars` `runs` times, with or without the fast path
+fn time(runs: u32, chars: &mut CharIterator, fast: bool) -> Duration {
+    let start = chars.mark();
+    let time = Instant::now();
+    for _ in 0..runs {
+        chars.reset_to(start);
+        let count = match fast {
+            true => tokenize(chars),
+            false => tokenize(&mut CharPath(chars)),
+        };
+        std::hint::black_box(count);
+    }
+    chars.reset_to(start);
+
+    time.elapsed() / runs
+}
+
+fn main() {
+    let mut chars = CharIterator::new();
+    chars.read_from_str(&SAMPLE.repeat(500), Some(Encoding::UTF8));
+
+    // the paths take turns and keep their best round, which leaves out warm up and noise
+    let (mut fast, mut slow) = (Duration::MAX, Duration::MAX);
+    for _ in 0..20 {
+        fast = fast.min(time(20, &mut chars, true));
+        slow = slow.min(time(20, &mut chars, false));
+    }
+    println!("fast path: {:?} per run", fast);
+    println!("char path: {:?} per run", slow);
+    println!("speedup:   {:.2}x", slow.as_secs_f64() / fast.as_secs_f64());
+}
@@ -0,0 +1,234 @@
+use crate::parser::InlineToken;
+
+/// Turns the bare URLs in text into links, following the GFM autolink extension: addresses
+/// starting with `http://`, `https://` or `www.` and email addresses. Links, images and code
+/// spans are left alone.
+pub fn autolink(tokens: Vec<InlineToken>) -> Vec<InlineToken> {
+    let mut linked = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        match token {
+            InlineToken::Text(text) => linked.extend(autolink_text(&text)),
+            InlineToken::Bold(tokens) => linked.push(InlineToken::Bold(autolink(tokens))),
+            InlineToken::Italic(tokens) => linked.push(InlineToken::Italic(autolink(tokens))),
+            token => linked.push(token),
+        }
+    }
+
+    linked
+}
+
+/// Splits text into text and the links found in it
+fn autolink_text(text: &str) -> Vec<InlineToken> {
+    let mut tokens = Vec::new();
+    let mut rest = 0;
+    let mut i = 0;
+    while i < text.len() {
+        let previous = text[..i].chars().next_back();
+        let found = match starts_url(previous) {
+            true => url_at(text, i),
+            false => None,
+        }
+        .or_else(|| email_at(text, i, previous));
+
+        match found {
+            Some((length, href)) => {
+                if rest < i {
+                    tokens.push(InlineToken::new_text(&text[rest..i]));
+                }
+                let url = &text[i..i + length];
+                tokens.push(InlineToken::new_link(
+                    vec![InlineToken::new_text(url)],
+                    &href,
+                ));
+                i += length;
+                rest = i;
+            }
+            None => i += text[i..].chars().next().map_or(1, char::len_utf8),
+        }
+    }
+    if rest < text.len() {
+        tokens.push(InlineToken::new_text(&text[rest..]));
+    }
+
+    tokens
+}
+
+/// URLs start at the start of the text, after whitespace or after an emphasis delimiter or an
+/// opening parenthesis
+fn starts_url(previous: Option<char>) -> bool {
+    match previous {
+        None => true,
+        Some(char) => char.is_whitespace() || matches!(char, '*' | '_' | '~' | '('),
+    }
+}
+
+/// Returns the length and the href of the URL at `start`
+fn url_at(text: &str, start: usize) -> Option<(usize, String)> {
+    let rest = &text[start..];
+    let (scheme, prefix) = ["http://", "https://", "www."]
+        .into_iter()
+        .find(|scheme| rest.starts_with(scheme))
+        .map(|scheme| match scheme {
+            "www." => (scheme, "http://"),
+            _ => (scheme, ""),
+        })?;
+
+    let end = rest
+        .find(|c: char| c.is_whitespace() || c == '<')
+        .unwrap_or(rest.len());
+    let url = trim_url(&rest[..end]);
+
+    let domain_start = match scheme {
+        "www." => 0,
+        _ => scheme.len(),
+    };
+    let domain = url[domain_start..]
+        .split(['/', '?', '#'])
+        .next()
+        .unwrap_or("");
+    if !is_valid_domain(domain) {
+        return None;
+    }
+
+    Some((url.len(), format!("{}{}", prefix, url)))
+}
+
+/// Removes the trailing punctuation that ends the sentence around a URL rather than the URL itself:
+/// `?!.,:*_~'"`, a `)` without a matching `(` and an entity reference like `&amp;`
+fn trim_url(url: &str) -> &str {
+    let mut url = url;
+    loop {
+        let trimmed = match url.chars().next_back() {
+            Some('?' | '!' | '.' | ',' | ':' | '*' | '_' | '~' | '\'' | '"') => {
+                &url[..url.len() - 1]
+            }
+            Some(')') if url.matches(')').count() > url.matches('(').count() => {
+                &url[..url.len() - 1]
+            }
+            Some(';') => match url[..url.len() - 1].rfind('&') {
+                Some(amp)
+                    if amp + 1 < url.len() - 1
+                        && url[amp + 1..url.len() - 1]
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric()) =>
+                {
+                    &url[..amp]
+                }
+                _ => return url,
+            },
+            _ => return url,
+        };
+        url = trimmed;
+    }
+}
+
+/// A domain is made of at least two segments of alphanumerics, `-` and `_` separated by `.`. The
+/// last two segments can't hold an `_`.
+fn is_valid_domain(domain: &str) -> bool {
+    let segments = domain.split('.').collect::<Vec<_>>();
+    segments.len() >= 2
+        && segments.iter().all(|segment| {
+            !segment.is_empty()
+                && segment
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        })
+        && segments[segments.len() - 2..]
+            .iter()
+            .all(|segment| !segment.contains('_'))
+}
+
+/// Returns the length and the `mailto:` href of the email address at `start`: a local part of
+/// alphanumerics and `.+-_`, an `@` and a domain whose last character is alphanumeric
+fn email_at(text: &str, start: usize, previous: Option<char>) -> Option<(usize, String)> {
+    let is_local = |c: char| c.is_ascii_alphanumeric() || matches!(c, '.' | '+' | '-' | '_');
+    if previous.is_some_and(is_local) {
+        return None;
+    }
+
+    let rest = &text[start..];
+    let local = rest.find(|c: char| !is_local(c)).unwrap_or(rest.len());
+    if local == 0 || !rest[local..].starts_with('@') {
+        return None;
+    }
+
+    let domain = &rest[local + 1..];
+    let end = domain
+        .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_')))
+        .unwrap_or(domain.len());
+    let domain = domain[..end].trim_end_matches('.');
+    if !domain.contains('.') || !domain.ends_with(|c: char| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+
+    let length = local + 1 + domain.len();
+    Some((length, format!("mailto:{}", &rest[..length])))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn link(text: &str, href: &str) -> InlineToken {
+        InlineToken::new_link(vec![InlineToken::new_text(text)], href)
+    }
+
+    #[test]
+    fn autolink_urls() {
+        let tests = vec![
+            ("www.x.com", vec![link("www.x.com", "http://www.x.com")]),
+            (
+                "see https://x.com/a?b=c.",
+                vec![
+                    InlineToken::new_text("see "),
+                    link("https://x.com/a?b=c", "https://x.com/a?b=c"),
+                    InlineToken::new_text("."),
+                ],
+            ),
+            (
+                "(www.x.com)",
+                vec![
+                    InlineToken::new_text("("),
+                    link("www.x.com", "http://www.x.com"),
+                    InlineToken::new_text(")"),
+                ],
+            ),
+            (
+                "www.x.com/a_(b)",
+                vec![link("www.x.com/a_(b)", "http://www.x.com/a_(b)")],
+            ),
+            (
+                "www.x.com&amp;",
+                vec![
+                    link("www.x.com", "http://www.x.com"),
+                    InlineToken::new_text("&amp;"),
+                ],
+            ),
+            ("www.a_b.c_d", vec![InlineToken::new_text("www.a_b.c_d")]),
+            ("awww.x.com", vec![InlineToken::new_text("awww.x.com")]),
+        ];
+        for (text, tokens) in tests {
+            assert_eq!(autolink_text(text), tokens, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn autolink_emails() {
+        let tests = vec![
+            ("a@b.com", vec![link("a@b.com", "mailto:a@b.com")]),
+            (
+                "mail a.b+c@d.e.com.",
+                vec![
+                    InlineToken::new_text("mail "),
+                    link("a.b+c@d.e.com", "mailto:a.b+c@d.e.com"),
+                    InlineToken::new_text("."),
+                ],
+            ),
+            ("a@b", vec![InlineToken::new_text("a@b")]),
+            ("a@b.c-", vec![InlineToken::new_text("a@b.c-")]),
+        ];
+        for (text, tokens) in tests {
+            assert_eq!(autolink_text(text), tokens, "{:?}", text);
+        }
+    }
+}
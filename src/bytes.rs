@@ -7,14 +7,83 @@ pub const CHAR_LF: char = '\u{000A}';
 pub const CHAR_CR: char = '\u{000D}';
 
 /// Encoding defines the way the buffer stream is read, as what defines a "character".
-#[derive(PartialEq)]
+#[derive(PartialEq, Debug, Copy, Clone)]
 pub enum Encoding {
     /// Stream is of UTF8 characters
     UTF8,
     /// Stream consists of 8-bit ASCII characters
     ASCII,
+    /// Stream is of little endian UTF16 code units
+    UTF16LE,
+    /// Stream is of big endian UTF16 code units
+    UTF16BE,
+}
+
+const BOM_UTF8: &[u8] = &[0xEF, 0xBB, 0xBF];
+const BOM_UTF16LE: &[u8] = &[0xFF, 0xFE];
+const BOM_UTF16BE: &[u8] = &[0xFE, 0xFF];
+
+impl Encoding {
+    /// Guesses the encoding of a byte stream. A byte order mark decides between UTF8 and UTF16,
+    /// without one the stream is ASCII when all bytes are 7-bit and UTF8 otherwise. Invalid UTF8
+    /// is still read as UTF8, so that only the invalid bytes are replaced, by U+FFFD.
+    pub fn detect(bytes: &[u8]) -> Encoding {
+        if bytes.starts_with(BOM_UTF8) {
+            return Encoding::UTF8;
+        }
+        if bytes.starts_with(BOM_UTF16LE) {
+            return Encoding::UTF16LE;
+        }
+        if bytes.starts_with(BOM_UTF16BE) {
+            return Encoding::UTF16BE;
+        }
+
+        match bytes.is_ascii() {
+            true => Encoding::ASCII,
+            false => Encoding::UTF8,
+        }
+    }
+}
+
+/// Encodes a string in the given encoding, the reverse of reading it with `read_from_bytes`.
+/// UTF16 output starts with a byte order mark so that its encoding can be detected again, UTF8
+/// output doesn't need one. Characters outside of ASCII are written as '?' in ASCII.
+pub fn encode(s: &str, encoding: Encoding) -> Vec<u8> {
+    match encoding {
+        Encoding::UTF8 => s.as_bytes().to_vec(),
+        Encoding::ASCII => s
+            .chars()
+            .map(|c| if c.is_ascii() { c as u8 } else { b'?' })
+            .collect(),
+        Encoding::UTF16LE => BOM_UTF16LE
+            .iter()
+            .copied()
+            .chain(s.encode_utf16().flat_map(u16::to_le_bytes))
+            .collect(),
+        Encoding::UTF16BE => BOM_UTF16BE
+            .iter()
+            .copied()
+            .chain(s.encode_utf16().flat_map(u16::to_be_bytes))
+            .collect(),
+    }
+}
+
+/// Bytes that aren't valid in the encoding they are read with
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct DecodeError {
+    pub encoding: Encoding,
+    /// Offset of the first invalid byte
+    pub offset: usize,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid {:?} at byte {}", self.encoding, self.offset)
+    }
 }
 
+impl std::error::Error for DecodeError {}
+
 /// The confidence decides how confident we are that the input stream is of this encoding
 #[derive(PartialEq)]
 pub enum Confidence {
@@ -49,6 +118,29 @@ impl fmt::Display for Position {
     }
 }
 
+/// A position in a `CharIterator` to return to, see `CharIterator::mark`
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Checkpoint {
+    position: Position,
+    has_read_eof: bool,
+}
+
+impl Checkpoint {
+    /// Returns the offset (in chars) of the position
+    pub fn offset(&self) -> usize {
+        self.position.offset
+    }
+
+    /// Returns a checkpoint at the given offset, for sources that don't keep track of lines and
+    /// columns
+    pub fn at_offset(offset: usize) -> Checkpoint {
+        Checkpoint {
+            position: Position::new(offset, 1, 1),
+            has_read_eof: false,
+        }
+    }
+}
+
 /// Defines a single character/element in the stream. This is either a UTF8 character, or
 /// a surrogate characters since these cannot be stored in a single char.
 /// Eof is denoted as a separate element.
@@ -100,6 +192,70 @@ impl Bytes {
     }
 }
 
+/// A stream of characters the tokenizer reads from
+pub trait CharSource {
+    /// Consumes the character at the current position, or returns EOF at the end of the stream
+    fn read(&mut self) -> Bytes;
+
+    /// Returns the character `offset` characters past the current position without consuming it
+    fn look_ahead(&self, offset: usize) -> Bytes;
+
+    /// Returns the offset (in chars) of the current position
+    fn tell(&self) -> usize;
+
+    /// Returns the characters between the start and end offsets, consumed or not. Offsets past the
+    /// end of the stream are clamped to it.
+    fn slice(&self, start: usize, end: usize) -> String;
+
+    /// Returns a checkpoint of the current position, to go back to it after reading ahead
+    fn mark(&self) -> Checkpoint;
+
+    /// Moves the source back (or forward) to a position returned by `mark`
+    fn reset_to(&mut self, checkpoint: Checkpoint);
+
+    /// Returns the character at the current position without consuming it
+    fn current(&self) -> Bytes {
+        self.look_ahead(0)
+    }
+
+    /// Consumes `count` characters
+    fn skip(&mut self, count: usize) {
+        for _ in 0..count {
+            self.read();
+        }
+    }
+
+    /// Returns the characters from the current position to the end of the stream
+    fn remaining(&self) -> String {
+        self.slice(self.tell(), usize::MAX)
+    }
+
+    /// Returns true when the current position is the first column of a line
+    fn at_line_start(&self) -> bool {
+        let offset = self.tell();
+        offset == 0 || self.slice(offset - 1, offset) == "\n"
+    }
+
+    /// Consumes the characters from the current position up to the end of the line or the first
+    /// one `f` returns false for
+    fn read_while(&mut self, f: fn(char) -> bool) -> String {
+        read_chars_while(self, f)
+    }
+}
+
+fn read_chars_while<S: CharSource + ?Sized>(source: &mut S, f: fn(char) -> bool) -> String {
+    let mut string = String::new();
+    while let Ch(char) = source.current() {
+        if char == '\n' || !f(char) {
+            break;
+        }
+        string.push(char);
+        source.read();
+    }
+
+    string
+}
+
 /// Buffered UTF-8 iterator
 /// TODO: Implement `Peekable` and `Iterator<Item = char>`
 pub struct CharIterator {
@@ -117,6 +273,9 @@ pub struct CharIterator {
     buffer: Vec<Bytes>,
     /// Reference to the actual buffer stream in u8 bytes
     u8_buffer: Vec<u8>,
+    /// The buffer as bytes when it holds nothing but ASCII, which is then decoded, sliced and read
+    /// byte by byte. Empty otherwise.
+    ascii_buffer: Vec<u8>,
     /// If all things are ok, both buffer and u8_buffer should refer to the same memory location (?)
     pub has_read_eof: bool, // True when we just read an EOF
 }
@@ -142,6 +301,7 @@ impl CharIterator {
             line_columns: HashMap::new(),
             buffer: Vec::new(),
             u8_buffer: Vec::new(),
+            ascii_buffer: Vec::new(),
             has_read_eof: false,
         }
     }
@@ -151,9 +311,9 @@ impl CharIterator {
         self.confidence == Confidence::Certain
     }
 
-    /// Detect the given encoding from stream analysis
-    pub fn detect_encoding(&self) {
-        todo!()
+    /// Guesses the encoding of the stream from its bytes, see `Encoding::detect`
+    pub fn detect_encoding(&self) -> Encoding {
+        Encoding::detect(&self.u8_buffer)
     }
 
     /// Returns true when the stream pointer is at the end of the stream
@@ -168,6 +328,20 @@ impl CharIterator {
         self.position.col = 1;
     }
 
+    /// Returns a checkpoint of the current position, to go back to it after reading ahead
+    pub fn mark(&self) -> Checkpoint {
+        Checkpoint {
+            position: self.position,
+            has_read_eof: self.has_read_eof,
+        }
+    }
+
+    /// Moves the stream back (or forward) to a position returned by `mark`
+    pub fn reset_to(&mut self, checkpoint: Checkpoint) {
+        self.position = checkpoint.position;
+        self.has_read_eof = checkpoint.has_read_eof;
+    }
+
     /// Skip offset characters in the stream (based on chars)
     pub fn skip(&mut self, offset: usize) {
         let mut skip_len = offset;
@@ -218,13 +392,23 @@ impl CharIterator {
     /// correct encoding.
     pub fn force_set_encoding(&mut self, e: Encoding) {
         match e {
-            Encoding::UTF8 => {
-                let str_buf;
-                unsafe {
-                    str_buf = std::str::from_utf8_unchecked(&self.u8_buffer)
-                        .replace("\u{000D}\u{000A}", "\u{000A}")
-                        .replace('\u{000D}', "\u{000A}");
-                }
+            // ASCII is valid UTF8 holding neither a BOM nor multi-byte characters, so the bytes
+            // map to characters one to one
+            Encoding::UTF8 if self.u8_buffer.is_ascii() => {
+                self.buffer = self.normalize_newlines_and_ascii(&self.u8_buffer);
+                self.length = self.buffer.len();
+            }
+            Encoding::UTF8 | Encoding::UTF16LE | Encoding::UTF16BE => {
+                let decoded = match e {
+                    Encoding::UTF16LE => decode_utf16(&self.u8_buffer, u16::from_le_bytes),
+                    Encoding::UTF16BE => decode_utf16(&self.u8_buffer, u16::from_be_bytes),
+                    _ => String::from_utf8_lossy(&self.u8_buffer).into_owned(),
+                };
+                let str_buf = decoded
+                    .strip_prefix('\u{FEFF}')
+                    .unwrap_or(&decoded)
+                    .replace("\u{000D}\u{000A}", "\u{000A}")
+                    .replace('\u{000D}', "\u{000A}");
 
                 // Convert the utf8 string into characters so we can use easy indexing
                 self.buffer = vec![];
@@ -255,11 +439,20 @@ impl CharIterator {
             }
         }
 
+        self.ascii_buffer = match self
+            .buffer
+            .iter()
+            .all(|c| matches!(c, Ch(c) if c.is_ascii()))
+        {
+            true => self.buffer.iter().map(|c| c.char() as u8).collect(),
+            false => Vec::new(),
+        };
+
         self.encoding = e;
     }
 
     /// Normalizes newlines (CRLF/CR => LF) and converts high ascii to '?'
-    fn normalize_newlines_and_ascii(&self, buffer: &Vec<u8>) -> Vec<Bytes> {
+    fn normalize_newlines_and_ascii(&self, buffer: &[u8]) -> Vec<Bytes> {
         let mut result = Vec::with_capacity(buffer.len());
 
         for i in 0..buffer.len() {
@@ -297,6 +490,62 @@ impl CharIterator {
         self.reset();
     }
 
+    /// Populates the current buffer with the given bytes. Without an encoding it is detected from
+    /// the bytes, see `Encoding::detect`.
+    pub fn read_from_bytes(&mut self, bytes: &[u8], e: Option<Encoding>) {
+        self.u8_buffer = Vec::from(bytes);
+        self.force_set_encoding(e.unwrap_or_else(|| Encoding::detect(bytes)));
+        self.reset();
+    }
+
+    /// Populates the current buffer with the given bytes like `read_from_bytes`, but fails on bytes
+    /// that aren't valid in the encoding instead of replacing them. The buffer is left untouched
+    /// on failure.
+    pub fn read_from_bytes_strict(
+        &mut self,
+        bytes: &[u8],
+        e: Option<Encoding>,
+    ) -> Result<(), DecodeError> {
+        let encoding = e.unwrap_or_else(|| Encoding::detect(bytes));
+        let error = |offset| DecodeError { encoding, offset };
+
+        match encoding {
+            Encoding::UTF8 => {
+                std::str::from_utf8(bytes).map_err(|err| error(err.valid_up_to()))?;
+            }
+            Encoding::ASCII => {
+                if let Some(offset) = bytes.iter().position(|byte| !byte.is_ascii()) {
+                    return Err(error(offset));
+                }
+            }
+            Encoding::UTF16LE | Encoding::UTF16BE => {
+                if bytes.len() % 2 == 1 {
+                    return Err(error(bytes.len() - 1));
+                }
+                let units = bytes.chunks(2).map(|pair| match encoding {
+                    Encoding::UTF16LE => u16::from_le_bytes([pair[0], pair[1]]),
+                    _ => u16::from_be_bytes([pair[0], pair[1]]),
+                });
+
+                let mut offset = 0;
+                for char in char::decode_utf16(units) {
+                    match char {
+                        Ok(char) => offset += char.len_utf16() * 2,
+                        Err(_) => return Err(error(offset)),
+                    }
+                }
+            }
+        }
+
+        self.read_from_bytes(bytes, Some(encoding));
+        Ok(())
+    }
+
+    /// Returns true when the buffer holds nothing but ASCII
+    pub fn is_ascii(&self) -> bool {
+        self.ascii_buffer.len() == self.length
+    }
+
     /// Returns the number of characters left in the buffer
     #[cfg(test)]
     fn chars_left(&self) -> usize {
@@ -343,6 +592,17 @@ impl CharIterator {
         self.look_ahead(1)
     }
 
+    /// Returns the character after the current one without consuming anything
+    pub fn peek(&self) -> Bytes {
+        self.peek_nth(0)
+    }
+
+    /// Returns the character `n + 1` characters past the current one without consuming anything,
+    /// so that `peek_nth(0)` is `peek()`
+    pub fn peek_nth(&self, n: usize) -> Bytes {
+        self.look_ahead(n + 1)
+    }
+
     pub(crate) fn unread(&mut self) {
         // We already read eof, so "unread" the eof by unsetting the flag
         if self.has_read_eof {
@@ -364,6 +624,21 @@ impl CharIterator {
         }
     }
 
+    /// Returns the characters between the start and end offsets
+    pub(crate) fn slice(&self, start: usize, end: usize) -> String {
+        let end = std::cmp::min(self.length, end);
+        let start = std::cmp::min(start, end);
+
+        if self.is_ascii() {
+            return String::from_utf8_lossy(&self.ascii_buffer[start..end]).into_owned();
+        }
+
+        self.buffer[start..end]
+            .iter()
+            .map(|e| e.to_string())
+            .collect()
+    }
+
     /// Looks ahead in the stream and returns len characters
     pub(crate) fn look_ahead_slice(&self, len: usize) -> String {
         let end_pos = std::cmp::min(self.length, self.position.offset + len);
@@ -384,6 +659,71 @@ impl CharIterator {
     }
 }
 
+impl CharSource for CharIterator {
+    fn read(&mut self) -> Bytes {
+        CharIterator::read(self)
+    }
+
+    fn look_ahead(&self, offset: usize) -> Bytes {
+        CharIterator::look_ahead(self, offset)
+    }
+
+    fn tell(&self) -> usize {
+        CharIterator::tell(self)
+    }
+
+    fn slice(&self, start: usize, end: usize) -> String {
+        CharIterator::slice(self, start, end)
+    }
+
+    fn mark(&self) -> Checkpoint {
+        CharIterator::mark(self)
+    }
+
+    fn reset_to(&mut self, checkpoint: Checkpoint) {
+        CharIterator::reset_to(self, checkpoint)
+    }
+
+    fn at_line_start(&self) -> bool {
+        let offset = self.tell();
+        offset == 0 || self.buffer.get(offset - 1) == Some(&Ch('\n'))
+    }
+
+    fn read_while(&mut self, f: fn(char) -> bool) -> String {
+        if !self.is_ascii() {
+            return read_chars_while(self, f);
+        }
+
+        // a run within a line only moves the column, so it can be taken at once
+        let start = self.position.offset;
+        let length = self.ascii_buffer[start..]
+            .iter()
+            .take_while(|byte| **byte != b'\n' && f(**byte as char))
+            .count();
+        self.position.offset += length;
+        self.position.col += length;
+
+        String::from_utf8_lossy(&self.ascii_buffer[start..start + length]).into_owned()
+    }
+
+    fn skip(&mut self, count: usize) {
+        CharIterator::skip(self, count)
+    }
+}
+
+/// Decodes pairs of bytes into UTF16 code units, replacing unpaired surrogates and a trailing odd
+/// byte with U+FFFD
+fn decode_utf16(bytes: &[u8], unit: fn([u8; 2]) -> u16) -> String {
+    let units = bytes.chunks(2).map(|pair| match pair {
+        [first, second] => unit([*first, *second]),
+        _ => 0xFFFD,
+    });
+
+    char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -463,6 +803,46 @@ mod test {
         assert!(!chars.is_certain_encoding());
     }
 
+    #[test]
+    fn test_peek() {
+        let mut chars = CharIterator::new();
+        chars.read_from_str("abc", Some(Encoding::UTF8));
+
+        assert_eq!(chars.peek(), Ch('b'));
+        assert_eq!(chars.peek_nth(1), Ch('c'));
+        assert_eq!(chars.peek_nth(2), Eof);
+        assert_eq!(chars.current(), Ch('a'));
+
+        assert_eq!(chars.read(), Ch('a'));
+        assert_eq!(chars.peek(), Ch('c'));
+        assert_eq!(chars.current(), Ch('b'));
+
+        assert_eq!(chars.read(), Ch('b'));
+        assert_eq!(chars.peek(), Eof);
+        assert_eq!(chars.current(), Ch('c'));
+        assert_eq!(chars.read(), Ch('c'));
+        assert_eq!(chars.current(), Eof);
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let mut chars = CharIterator::new();
+        chars.read_from_str("a\nbc", Some(Encoding::UTF8));
+        chars.read();
+
+        let checkpoint = chars.mark();
+        assert_eq!(chars.read(), Ch('\n'));
+        assert_eq!(chars.read(), Ch('b'));
+        assert_eq!(chars.read(), Ch('c'));
+        assert!(chars.eof());
+
+        chars.reset_to(checkpoint);
+        assert_eq!(chars.current(), Ch('\n'));
+        assert_eq!(chars.position, Position::new(1, 1, 2));
+        assert!(!chars.eof());
+        assert_eq!(chars.read(), Ch('\n'));
+    }
+
     #[test]
     fn test_eof() {
         let mut chars = CharIterator::new();
@@ -511,4 +891,88 @@ mod test {
         chars.unread();
         assert!(matches!(chars.read(), Eof));
     }
+
+    #[test]
+    fn test_detect_encoding() {
+        assert_eq!(Encoding::detect(b"plain # ascii"), Encoding::ASCII);
+        assert_eq!(Encoding::detect(b""), Encoding::ASCII);
+        assert_eq!(Encoding::detect("f👽f".as_bytes()), Encoding::UTF8);
+        assert_eq!(Encoding::detect(b"\xEF\xBB\xBFa"), Encoding::UTF8);
+        assert_eq!(Encoding::detect(b"\xFF\xFEa\x00"), Encoding::UTF16LE);
+        assert_eq!(Encoding::detect(b"\xFE\xFF\x00a"), Encoding::UTF16BE);
+        assert_eq!(Encoding::detect(b"caf\xE9"), Encoding::UTF8);
+
+        let mut chars = CharIterator::new();
+        chars.read_from_bytes("# 👽".as_bytes(), Some(Encoding::ASCII));
+        assert_eq!(chars.detect_encoding(), Encoding::UTF8);
+    }
+
+    #[test]
+    fn test_read_from_bytes() {
+        let mut chars = CharIterator::new();
+
+        chars.read_from_bytes(b"\xFF\xFE#\x00 \x00=\xD8\x7D\xDC\r\x00\n\x00", None);
+        assert_eq!(chars.encoding, Encoding::UTF16LE);
+        assert_eq!(chars.slice(0, chars.length), "# 👽\n");
+
+        chars.read_from_bytes(b"\xEF\xBB\xBFab", None);
+        assert_eq!(chars.encoding, Encoding::UTF8);
+        assert_eq!(chars.slice(0, chars.length), "ab");
+
+        chars.read_from_bytes(b"caf\xE9 \xC3\xA9", None);
+        assert_eq!(chars.slice(0, chars.length), "caf\u{FFFD} \u{E9}");
+
+        chars.read_from_bytes(b"caf\xE9", Some(Encoding::UTF8));
+        assert_eq!(chars.slice(0, chars.length), "caf\u{FFFD}");
+    }
+
+    #[test]
+    fn test_read_from_bytes_strict() {
+        let mut chars = CharIterator::new();
+
+        chars.read_from_bytes(b"a\xFFb", Some(Encoding::UTF8));
+        assert_eq!(chars.slice(0, chars.length), "a\u{FFFD}b");
+        assert_eq!(
+            chars.read_from_bytes_strict(b"x\xFFy", Some(Encoding::UTF8)),
+            Err(DecodeError {
+                encoding: Encoding::UTF8,
+                offset: 1
+            })
+        );
+        // the previous content is kept
+        assert_eq!(chars.slice(0, chars.length), "a\u{FFFD}b");
+
+        let tests = vec![
+            (&b"caf\xE9"[..], Encoding::ASCII, 3),
+            (&b"\xFF\xFEa\x00b"[..], Encoding::UTF16LE, 4),
+            (&b"\x00a\xDC\x00"[..], Encoding::UTF16BE, 2),
+        ];
+        for (bytes, encoding, offset) in tests {
+            assert_eq!(
+                chars.read_from_bytes_strict(bytes, Some(encoding)),
+                Err(DecodeError { encoding, offset }),
+                "{:?}",
+                bytes
+            );
+        }
+
+        assert_eq!(chars.read_from_bytes_strict("é".as_bytes(), None), Ok(()));
+        assert_eq!(chars.slice(0, chars.length), "é");
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode("é", Encoding::UTF16LE), b"\xFF\xFE\xE9\x00");
+        assert_eq!(encode("é", Encoding::UTF16BE), b"\xFE\xFF\x00\xE9");
+        assert_eq!(encode("é", Encoding::UTF8), b"\xC3\xA9");
+        assert_eq!(encode("café", Encoding::ASCII), b"caf?");
+
+        let mut chars = CharIterator::new();
+        for encoding in [Encoding::UTF16LE, Encoding::UTF16BE, Encoding::UTF8] {
+            let bytes = encode("é 👽", encoding);
+            assert_eq!(chars.read_from_bytes_strict(&bytes, None), Ok(()));
+            assert_eq!(chars.encoding, encoding);
+            assert_eq!(chars.slice(0, chars.length), "é 👽");
+        }
+    }
 }
@@ -0,0 +1,168 @@
+use crate::html::render_html;
+use crate::parse;
+use crate::text::render_text;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::{fmt, io};
+
+pub const USAGE: &str = "usage: mdrs [--from FILE|-] [--to html|ast|json|text]";
+
+/// The format the parsed document is written in
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Format {
+    Html,
+    Ast,
+    Json,
+    Text,
+}
+
+#[derive(Debug)]
+pub enum CliError {
+    /// The arguments can't be understood, holds a description of the problem
+    Usage(String),
+    Io(io::Error),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Usage(message) => write!(f, "{}\n{}", message, USAGE),
+            CliError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(err: io::Error) -> Self {
+        CliError::Io(err)
+    }
+}
+
+/// Parsed command line arguments
+#[derive(Debug, PartialEq, Eq)]
+pub struct Args {
+    /// File to read the Markdown from, stdin when `None`
+    pub from: Option<String>,
+    pub to: Format,
+}
+
+impl Args {
+    pub fn parse(args: &[String]) -> Result<Args, CliError> {
+        let mut from = None;
+        let mut to = Format::Html;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| CliError::Usage(format!("missing value for {}", name)))
+            };
+
+            match arg.as_str() {
+                "--from" => {
+                    let path = value("--from")?;
+                    from = (path != "-").then(|| path.clone());
+                }
+                "--to" => {
+                    to = match value("--to")?.as_str() {
+                        "html" => Format::Html,
+                        "ast" => Format::Ast,
+                        "json" => Format::Json,
+                        "text" => Format::Text,
+                        other => return Err(CliError::Usage(format!("unknown format: {}", other))),
+                    }
+                }
+                other => return Err(CliError::Usage(format!("unknown argument: {}", other))),
+            }
+        }
+
+        Ok(Args { from, to })
+    }
+}
+
+/// Reads Markdown from the file given by the arguments, or `input` when there is none, and writes
+/// the document to `output` in the requested format.
+pub fn run(args: &[String], input: &mut dyn Read, output: &mut dyn Write) -> Result<(), CliError> {
+    let args = Args::parse(args)?;
+
+    let mut markdown = String::new();
+    match &args.from {
+        Some(path) => File::open(path)?.read_to_string(&mut markdown)?,
+        None => input.read_to_string(&mut markdown)?,
+    };
+
+    let document = parse(&markdown);
+    let rendered = match args.to {
+        Format::Html => render_html(&document),
+        Format::Ast => document.to_debug_tree(),
+        Format::Json => format!("{}\n", document.to_json()),
+        Format::Text => render_text(&document),
+    };
+
+    output.write_all(rendered.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn run_with_stdin(arguments: &[&str], stdin: &str) -> Result<String, CliError> {
+        let mut output = Vec::new();
+        run(&args(arguments), &mut stdin.as_bytes(), &mut output)?;
+
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn render_stdin() {
+        assert_eq!(
+            run_with_stdin(&["--to", "html"], "# hi").unwrap(),
+            "<h1>hi</h1>\n"
+        );
+        assert_eq!(
+            run_with_stdin(&["--from", "-", "--to", "json"], "# hi").unwrap(),
+            "{\"type\":\"document\",\"front_matter\":null,\"children\":[{\"type\":\"heading\",\"level\":1,\"children\":[{\"type\":\"text\",\"value\":\"hi\"}]}]}\n"
+        );
+        assert_eq!(run_with_stdin(&["--to", "text"], "# hi").unwrap(), "hi\n");
+        assert_eq!(
+            run_with_stdin(&["--to", "ast"], "# hi").unwrap(),
+            "Heading(1)\n  Text \"hi\"\n"
+        );
+    }
+
+    #[test]
+    fn render_file() {
+        let path = std::env::temp_dir().join("mdrs-cli-test.md");
+        std::fs::write(&path, "- a").unwrap();
+
+        let output = run_with_stdin(&["--from", path.to_str().unwrap()], "# ignored").unwrap();
+        assert_eq!(output, "<ul>\n<li>a</li>\n</ul>\n");
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reject_invalid_arguments() {
+        assert!(matches!(
+            run_with_stdin(&["--to", "pdf"], ""),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(
+            run_with_stdin(&["--to"], ""),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(
+            run_with_stdin(&["--verbose"], ""),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(
+            run_with_stdin(&["--from", "/does/not/exist.md"], ""),
+            Err(CliError::Io(_))
+        ));
+    }
+}
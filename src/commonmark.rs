@@ -0,0 +1,309 @@
+//! CommonMark conformance tests. The spec examples live in `tests/commonmark/*.json` as an array
+//! of `{markdown, html}` objects, the same format the CommonMark project publishes its spec in.
+
+use crate::bytes::{CharIterator, Encoding};
+use crate::html::render_html;
+use crate::parser::Parser;
+use crate::tokenizer::Tokenizer;
+use std::collections::HashMap;
+use std::panic;
+
+/// Examples the parser does not handle yet, by their `example` number
+const FAILING: &[usize] = &[6];
+
+const SPEC: &str = include_str!("../tests/commonmark/spec.json");
+
+struct Example {
+    markdown: String,
+    html: String,
+    example: usize,
+    section: String,
+}
+
+fn load_examples() -> Vec<Example> {
+    let json = Json::parse(SPEC);
+    let Json::Array(values) = json else {
+        panic!("spec file must contain an array of examples");
+    };
+
+    values
+        .into_iter()
+        .map(|value| {
+            let Json::Object(mut object) = value else {
+                panic!("spec examples must be objects");
+            };
+            let mut field = |name: &str| object.remove(name).unwrap_or(Json::Null);
+
+            Example {
+                markdown: field("markdown").into_string(),
+                html: field("html").into_string(),
+                example: field("example").into_number() as usize,
+                section: field("section").into_string(),
+            }
+        })
+        .collect()
+}
+
+fn to_html(markdown: &str) -> String {
+    let mut chars = CharIterator::new();
+    chars.read_from_str(markdown, Some(Encoding::UTF8));
+
+    let mut tokenizer = Tokenizer::new(&mut chars);
+    let mut parser = Parser::new(&mut tokenizer);
+
+    render_html(&parser.parse())
+}
+
+const BLOCK_TAGS: &[&str] = &[
+    "p",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "ul",
+    "ol",
+    "li",
+    "blockquote",
+    "pre",
+    "hr",
+    "table",
+    "thead",
+    "tbody",
+    "tr",
+    "th",
+    "td",
+    "div",
+];
+
+fn is_block_tag(tag: &str) -> bool {
+    let name = tag
+        .trim_start_matches(['<', '/'])
+        .split(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        .next()
+        .unwrap_or_default();
+
+    BLOCK_TAGS.contains(&name)
+}
+
+/// Collapses whitespace runs into a single space and drops whitespace next to block-level tags,
+/// where it is insignificant.
+fn normalize_html(html: &str) -> String {
+    let mut pieces = Vec::new();
+    let mut rest = html;
+    while !rest.is_empty() {
+        let end = if rest.starts_with('<') {
+            rest.find('>').map_or(rest.len(), |end| end + 1)
+        } else {
+            rest.find('<').unwrap_or(rest.len())
+        };
+        pieces.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+
+    let mut normalized = String::new();
+    for (i, piece) in pieces.iter().enumerate() {
+        if piece.starts_with('<') {
+            normalized.push_str(piece);
+            continue;
+        }
+
+        let mut text = piece.split_whitespace().collect::<Vec<_>>().join(" ");
+        if piece.starts_with(char::is_whitespace) && !text.is_empty() {
+            text.insert(0, ' ');
+        }
+        if piece.ends_with(char::is_whitespace) && !text.ends_with(' ') {
+            text.push(' ');
+        }
+        if i == 0 || is_block_tag(pieces[i - 1]) {
+            text = text.trim_start().to_string();
+        }
+        if i + 1 == pieces.len() || is_block_tag(pieces[i + 1]) {
+            text = text.trim_end().to_string();
+        }
+        normalized.push_str(&text);
+    }
+
+    normalized
+}
+
+/// Runs the examples, returning a description of each one that panicked or rendered differently
+fn run_examples(examples: &[Example]) -> Vec<String> {
+    let mut failures = Vec::new();
+    for example in examples {
+        let markdown = example.markdown.clone();
+        let result = panic::catch_unwind(|| to_html(&markdown));
+
+        let failure = match result {
+            Ok(html) if normalize_html(&html) == normalize_html(&example.html) => continue,
+            Ok(html) => format!("rendered {:?}", html),
+            Err(_) => "panicked".to_string(),
+        };
+
+        failures.push(format!(
+            "example {} ({}): {:?} {}, expected {:?}",
+            example.example, example.section, example.markdown, failure, example.html
+        ));
+    }
+
+    failures
+}
+
+#[test]
+fn commonmark_spec() {
+    let examples = load_examples()
+        .into_iter()
+        .filter(|example| !FAILING.contains(&example.example))
+        .collect::<Vec<_>>();
+
+    let failures = run_examples(&examples);
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+#[ignore = "examples the parser does not support yet"]
+fn commonmark_spec_failing() {
+    let examples = load_examples()
+        .into_iter()
+        .filter(|example| FAILING.contains(&example.example))
+        .collect::<Vec<_>>();
+
+    let failures = run_examples(&examples);
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn normalize_insignificant_whitespace() {
+    assert_eq!(
+        normalize_html("<ul>\n<li>\n  a   b </li>\n</ul>\n"),
+        "<ul><li>a b</li></ul>"
+    );
+    assert_eq!(normalize_html("<p>a <em>b</em></p>"), "<p>a <em>b</em></p>");
+}
+
+/// Just enough JSON to read the spec files
+#[derive(Debug)]
+enum Json {
+    Null,
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(HashMap<String, Json>),
+}
+
+impl Json {
+    fn parse(source: &str) -> Json {
+        let chars = source.chars().collect::<Vec<_>>();
+        let mut pos = 0;
+        Json::parse_value(&chars, &mut pos)
+    }
+
+    fn into_string(self) -> String {
+        match self {
+            Json::String(s) => s,
+            other => panic!("expected a string, found {:?}", other),
+        }
+    }
+
+    fn into_number(self) -> f64 {
+        match self {
+            Json::Number(n) => n,
+            other => panic!("expected a number, found {:?}", other),
+        }
+    }
+
+    fn skip_whitespace(chars: &[char], pos: &mut usize) {
+        while *pos < chars.len() && chars[*pos].is_whitespace() {
+            *pos += 1;
+        }
+    }
+
+    fn parse_value(chars: &[char], pos: &mut usize) -> Json {
+        Json::skip_whitespace(chars, pos);
+
+        match chars[*pos] {
+            '{' => {
+                *pos += 1;
+                let mut object = HashMap::new();
+                loop {
+                    Json::skip_whitespace(chars, pos);
+                    match chars[*pos] {
+                        '}' => {
+                            *pos += 1;
+                            return Json::Object(object);
+                        }
+                        ',' => *pos += 1,
+                        _ => {
+                            let key = Json::parse_value(chars, pos).into_string();
+                            Json::skip_whitespace(chars, pos);
+                            assert_eq!(chars[*pos], ':');
+                            *pos += 1;
+                            object.insert(key, Json::parse_value(chars, pos));
+                        }
+                    }
+                }
+            }
+            '[' => {
+                *pos += 1;
+                let mut array = Vec::new();
+                loop {
+                    Json::skip_whitespace(chars, pos);
+                    match chars[*pos] {
+                        ']' => {
+                            *pos += 1;
+                            return Json::Array(array);
+                        }
+                        ',' => *pos += 1,
+                        _ => array.push(Json::parse_value(chars, pos)),
+                    }
+                }
+            }
+            '"' => {
+                *pos += 1;
+                let mut string = String::new();
+                loop {
+                    let char = chars[*pos];
+                    *pos += 1;
+                    match char {
+                        '"' => return Json::String(string),
+                        '\\' => {
+                            let escaped = chars[*pos];
+                            *pos += 1;
+                            match escaped {
+                                'n' => string.push('\n'),
+                                't' => string.push('\t'),
+                                'r' => string.push('\r'),
+                                'b' => string.push('\u{8}'),
+                                'f' => string.push('\u{c}'),
+                                'u' => {
+                                    let hex = chars[*pos..*pos + 4].iter().collect::<String>();
+                                    *pos += 4;
+                                    let code = u32::from_str_radix(&hex, 16).unwrap();
+                                    string.push(char::from_u32(code).unwrap_or('\u{FFFD}'));
+                                }
+                                other => string.push(other),
+                            }
+                        }
+                        other => string.push(other),
+                    }
+                }
+            }
+            't' | 'f' | 'n' => {
+                // `true`, `false` and `null` carry no information the tests need
+                while *pos < chars.len() && chars[*pos].is_ascii_alphabetic() {
+                    *pos += 1;
+                }
+                Json::Null
+            }
+            _ => {
+                let number = chars[*pos..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+                    .collect::<String>();
+                *pos += number.len();
+                Json::Number(number.parse().unwrap())
+            }
+        }
+    }
+}
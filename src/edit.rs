@@ -0,0 +1,350 @@
+use crate::parser::{push_inline, trim_inline, Document, Element, InlineToken};
+use crate::text::is_whitespace;
+
+impl Document {
+    /// Replaces every element by the result of `f`, removing the ones it returns `None` for. With
+    /// `recursive` the elements inside list items and block quotes are edited as well, after the
+    /// element containing them.
+    pub fn filter_map_elements<F>(&mut self, recursive: bool, mut f: F)
+    where
+        F: FnMut(Element) -> Option<Element>,
+    {
+        let elements = std::mem::take(&mut self.elements);
+        let mut spans = std::mem::take(&mut self.spans).into_iter();
+        let mut descendant_spans = std::mem::take(&mut self.descendant_spans).into_iter();
+
+        for element in elements {
+            // spans are only kept for the top-level elements that remain, and for the elements
+            // nested in them when these weren't edited
+            let span = spans.next();
+            let descendants = descendant_spans.next();
+            if let Some(element) = f(element) {
+                let (element, descendants) = match recursive {
+                    true => (
+                        filter_map_children(element, &mut f),
+                        descendants.map(|_| Vec::new()),
+                    ),
+                    false => (element, descendants),
+                };
+                self.elements.push(element);
+                self.spans.extend(span);
+                self.descendant_spans.extend(descendants);
+            }
+        }
+    }
+
+    /// Removes the elements `f` returns false for, see `filter_map_elements`
+    pub fn retain_elements<F>(&mut self, recursive: bool, mut f: F)
+    where
+        F: FnMut(&Element) -> bool,
+    {
+        self.filter_map_elements(recursive, |element| f(&element).then_some(element));
+    }
+
+    /// Replaces every element by the result of `f`, see `filter_map_elements`
+    pub fn map_elements<F>(&mut self, recursive: bool, mut f: F)
+    where
+        F: FnMut(Element) -> Element,
+    {
+        self.filter_map_elements(recursive, |element| Some(f(element)));
+    }
+
+    /// Replaces the href of every link by the result of `f`, wherever the link is nested
+    pub fn rewrite_links<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&str) -> String,
+    {
+        for_each_inline_token(&mut self.elements, &mut |token| {
+            if let InlineToken::Link(link) = token {
+                link.href = f(&link.href);
+            }
+        });
+    }
+
+    /// Canonicalizes the whitespace in text so that documents differing only in insignificant
+    /// whitespace compare equal: runs of whitespace become a single space and the text at the
+    /// start and end of headings and paragraphs is trimmed. Code is left as is.
+    pub fn normalize(&mut self) {
+        normalize_elements(&mut self.elements);
+    }
+
+    /// Replaces the source of every image by the result of `f`, see `rewrite_links`
+    pub fn rewrite_images<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&str) -> String,
+    {
+        for_each_inline_token(&mut self.elements, &mut |token| {
+            if let InlineToken::Image(image) = token {
+                image.src = f(&image.src);
+            }
+        });
+    }
+}
+
+fn normalize_elements(elements: &mut [Element]) {
+    for element in elements {
+        match element {
+            Element::Heading(heading) => {
+                normalize_inline_tokens(&mut heading.tokens);
+                trim_inline(&mut heading.tokens);
+            }
+            Element::Paragraph(paragraph) => {
+                normalize_inline_tokens(&mut paragraph.0);
+                trim_inline(&mut paragraph.0);
+            }
+            Element::List(list) => {
+                for item in &mut list.items {
+                    normalize_elements(item);
+                }
+            }
+            Element::BlockQuote(elements) => normalize_elements(elements),
+            Element::Table(table) => {
+                for cell in table
+                    .header
+                    .iter_mut()
+                    .chain(table.rows.iter_mut().flatten())
+                {
+                    normalize_inline_tokens(cell);
+                    trim_inline(cell);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Merges consecutive text and collapses its whitespace, in nested tokens as well
+fn normalize_inline_tokens(tokens: &mut Vec<InlineToken>) {
+    let mut normalized = Vec::with_capacity(tokens.len());
+    for mut token in std::mem::take(tokens) {
+        match &mut token {
+            InlineToken::Bold(tokens) | InlineToken::Italic(tokens) => {
+                normalize_inline_tokens(tokens)
+            }
+            InlineToken::Link(link) => normalize_inline_tokens(&mut link.tokens),
+            _ => {}
+        }
+        push_inline(&mut normalized, token);
+    }
+
+    for token in &mut normalized {
+        if let InlineToken::Text(text) = token {
+            *text = collapse_whitespace(text);
+        }
+    }
+    *tokens = normalized;
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut in_whitespace = false;
+    for char in text.chars() {
+        if is_whitespace(char) {
+            in_whitespace = true;
+            continue;
+        }
+        if in_whitespace {
+            collapsed.push(' ');
+            in_whitespace = false;
+        }
+        collapsed.push(char);
+    }
+    if in_whitespace {
+        collapsed.push(' ');
+    }
+
+    collapsed
+}
+
+/// Calls `f` on every inline token of the elements, before the tokens nested inside it
+fn for_each_inline_token<F>(elements: &mut [Element], f: &mut F)
+where
+    F: FnMut(&mut InlineToken),
+{
+    for element in elements {
+        match element {
+            Element::Heading(heading) => for_each_nested_token(&mut heading.tokens, f),
+            Element::Paragraph(paragraph) => for_each_nested_token(&mut paragraph.0, f),
+            Element::List(list) => {
+                for item in &mut list.items {
+                    for_each_inline_token(item, f);
+                }
+            }
+            Element::BlockQuote(elements) => for_each_inline_token(elements, f),
+            Element::Table(table) => {
+                for cell in table
+                    .header
+                    .iter_mut()
+                    .chain(table.rows.iter_mut().flatten())
+                {
+                    for_each_nested_token(cell, f);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn for_each_nested_token<F>(tokens: &mut [InlineToken], f: &mut F)
+where
+    F: FnMut(&mut InlineToken),
+{
+    for token in tokens {
+        f(token);
+        match token {
+            InlineToken::Bold(tokens) | InlineToken::Italic(tokens) => {
+                for_each_nested_token(tokens, f)
+            }
+            InlineToken::Link(link) => for_each_nested_token(&mut link.tokens, f),
+            _ => {}
+        }
+    }
+}
+
+fn filter_map_children<F>(element: Element, f: &mut F) -> Element
+where
+    F: FnMut(Element) -> Option<Element>,
+{
+    match element {
+        Element::List(mut list) => {
+            list.items = list
+                .items
+                .into_iter()
+                .map(|item| filter_map_all(item, f))
+                .collect();
+            Element::List(list)
+        }
+        Element::BlockQuote(elements) => Element::BlockQuote(filter_map_all(elements, f)),
+        element => element,
+    }
+}
+
+fn filter_map_all<F>(elements: Vec<Element>, f: &mut F) -> Vec<Element>
+where
+    F: FnMut(Element) -> Option<Element>,
+{
+    let mut edited = Vec::with_capacity(elements.len());
+    for element in elements {
+        if let Some(element) = f(element) {
+            edited.push(filter_map_children(element, f));
+        }
+    }
+
+    edited
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse;
+    use crate::parser::{Document, Element, InlineToken, ListKind};
+
+    fn demote(element: Element) -> Element {
+        match element {
+            Element::Heading(mut heading) => {
+                heading.level = std::cmp::min(heading.level + 1, 6);
+                Element::Heading(heading)
+            }
+            element => element,
+        }
+    }
+
+    fn heading_levels(elements: &[Element]) -> Vec<usize> {
+        let mut levels = Vec::new();
+        for element in elements {
+            match element {
+                Element::Heading(heading) => levels.push(heading.level),
+                Element::BlockQuote(elements) => levels.extend(heading_levels(elements)),
+                _ => {}
+            }
+        }
+
+        levels
+    }
+
+    #[test]
+    fn demote_headings() {
+        let source = "# a\n## b\n> ###### c\n> # d";
+
+        let mut document = parse(source);
+        document.map_elements(true, demote);
+        assert_eq!(heading_levels(&document.elements), vec![2, 3, 6, 2]);
+
+        let mut document = parse(source);
+        document.map_elements(false, demote);
+        assert_eq!(heading_levels(&document.elements), vec![2, 3, 6, 1]);
+    }
+
+    #[test]
+    fn retain_elements() {
+        let mut document = parse("a\n\n- b\n\n---\n\n> c\n> ***\n\nd");
+
+        document.retain_elements(true, |element| !matches!(element, Element::Paragraph(_)));
+        assert_eq!(
+            document.elements,
+            vec![
+                Element::new_list(ListKind::Unordered, vec![vec![]]),
+                Element::ThematicBreak,
+                Element::new_blockquote(vec![Element::ThematicBreak]),
+            ]
+        );
+        assert_eq!(document.spans, vec![3..6, 8..11, 13..22]);
+
+        let mut document = parse("a\n\n- b");
+        document.retain_elements(false, |element| !matches!(element, Element::Paragraph(_)));
+        assert_eq!(
+            document.elements,
+            vec![Element::new_list(
+                ListKind::Unordered,
+                vec![vec![Element::new_paragraph(vec![InlineToken::new_text(
+                    "b"
+                )])]]
+            )]
+        );
+    }
+
+    #[test]
+    fn normalize_whitespace() {
+        let mut spaced = parse("a   b \n c\n\n- *c \t d*  [e  f](g)\n\n```\nh   i\n```");
+        let mut collapsed = parse("a b c\n\n- *c d* [e f](g)\n\n```\nh   i\n```");
+        assert_ne!(spaced, collapsed);
+
+        spaced.normalize();
+        collapsed.normalize();
+        assert_eq!(spaced, collapsed);
+
+        // a non-breaking space is part of a word, not whitespace
+        let mut document = parse("10\u{A0}\u{A0}km \u{2003} away");
+        document.normalize();
+        assert_eq!(document, parse("10\u{A0}\u{A0}km away"));
+
+        let mut document = Document::new(vec![Element::new_paragraph(vec![
+            InlineToken::new_text(" a "),
+            InlineToken::new_text("  b "),
+        ])]);
+        document.normalize();
+        assert_eq!(
+            document,
+            Document::new(vec![Element::new_paragraph(vec![InlineToken::new_text(
+                "a b"
+            )])])
+        );
+    }
+
+    #[test]
+    fn rewrite_links() {
+        let mut document =
+            parse("# [a](a.md)\n\n- *[b](./b.md)*\n\n> [![c](c.md)](c.md) [d](https://d)");
+
+        document.rewrite_links(|href| match href.strip_suffix(".md") {
+            Some(path) => format!("{}.html", path),
+            None => href.to_string(),
+        });
+        assert_eq!(
+            crate::html::render_html(&document),
+            "<h1><a href=\"a.html\">a</a></h1>\n<ul>\n<li><em><a href=\"./b.html\">b</a></em></li>\n</ul>\n<blockquote>\n<p><a href=\"c.html\"><img src=\"c.md\" alt=\"c\" /></a> <a href=\"https://d\">d</a></p>\n</blockquote>\n"
+        );
+
+        document.rewrite_images(|src| format!("/{}", src));
+        assert!(crate::html::render_html(&document).contains("<img src=\"/c.md\""));
+    }
+}
@@ -0,0 +1,169 @@
+use crate::parser::{push_inline, push_text, InlineToken};
+
+/// A run of `*` or `_` in inline content
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Delimiter {
+    char: char,
+    /// Number of delimiters of the run not matched yet
+    length: usize,
+    /// Number of delimiters of the whole run
+    run: usize,
+    can_open: bool,
+    can_close: bool,
+}
+
+impl Delimiter {
+    pub(crate) fn new(char: char, length: usize, can_open: bool, can_close: bool) -> Self {
+        Self {
+            char,
+            length,
+            run: length,
+            can_open,
+            can_close,
+        }
+    }
+
+    pub(crate) fn can_open(&self) -> bool {
+        self.can_open
+    }
+
+    pub(crate) fn can_close(&self) -> bool {
+        self.can_close
+    }
+
+    /// Returns true when the run can close emphasis opened by `opener`, see `delims_match`
+    fn closes(&self, opener: &Delimiter) -> bool {
+        self.char == opener.char
+            && delims_match(opener.run, opener.can_close, self.run, self.can_open)
+    }
+
+    /// The index of the closing runs like this one in `DelimiterStack::bottoms`. Runs of the same
+    /// character, that can open or not alike, and whose lengths are the same modulo three match
+    /// the same opening runs.
+    fn kind(&self) -> usize {
+        usize::from(self.char == '_') * 6 + usize::from(self.can_open) * 3 + self.run % 3
+    }
+
+    fn text(&self) -> String {
+        self.char.to_string().repeat(self.length)
+    }
+}
+
+/// Inline content whose runs of `*` and `_` are matched into emphasis as the content is parsed,
+/// following the delimiter stack of CommonMark: a run that can close emphasis closes the nearest
+/// run before it that it matches, and the runs in between are literal text. Each run is only
+/// matched once, so that matching takes linear time.
+#[derive(Debug, Default)]
+pub(crate) struct DelimiterStack {
+    /// The content before the first run
+    tokens: Vec<InlineToken>,
+    /// The runs not matched yet, each with the content up to the next one
+    runs: Vec<(Delimiter, Vec<InlineToken>)>,
+    /// The indices in `runs` of the runs that can open emphasis
+    openers: Vec<usize>,
+    /// For each kind of closing run, the number of openers it was matched against without success
+    /// already, see `Delimiter::kind`
+    bottoms: [usize; 12],
+}
+
+impl DelimiterStack {
+    /// The content after the last run, which inline content is appended to
+    pub(crate) fn tokens(&mut self) -> &mut Vec<InlineToken> {
+        match self.runs.last_mut() {
+            Some((_, tokens)) => tokens,
+            None => &mut self.tokens,
+        }
+    }
+
+    pub(crate) fn push(&mut self, token: InlineToken) {
+        push_inline(self.tokens(), token);
+    }
+
+    /// Appends a run of `*` or `_`, closing the emphasis it can. What's left of it may open
+    /// emphasis closed later on.
+    pub(crate) fn push_delimiter(&mut self, mut delimiter: Delimiter) {
+        if delimiter.can_close {
+            self.close(&mut delimiter);
+        }
+        if delimiter.length == 0 {
+            return;
+        }
+
+        if delimiter.can_open {
+            self.openers.push(self.runs.len());
+        }
+        self.runs.push((delimiter, Vec::new()));
+    }
+
+    /// Closes emphasis with the delimiters of `closer`, innermost first, as long as it has some
+    /// left and an opening run matches it. An odd number of delimiters on both sides makes italic
+    /// text, the rest bold text: `***a***` is italic text around bold text.
+    fn close(&mut self, closer: &mut Delimiter) {
+        let kind = closer.kind();
+        while closer.length > 0 {
+            let bottom = self.bottoms[kind];
+            let found = self.openers[bottom..]
+                .iter()
+                .rposition(|&index| closer.closes(&self.runs[index].0));
+            let Some(found) = found else {
+                self.bottoms[kind] = self.openers.len();
+                return;
+            };
+
+            // the runs after the opening one are literal text inside the emphasis
+            let index = self.openers[bottom + found];
+            self.openers.truncate(bottom + found + 1);
+            let mut content = std::mem::take(&mut self.runs[index].1);
+            for (delimiter, tokens) in self.runs.drain(index + 1..) {
+                push_text(&mut content, &delimiter.text());
+                for token in tokens {
+                    push_inline(&mut content, token);
+                }
+            }
+
+            let opener = &mut self.runs[index].0;
+            let count = match opener.length >= 2 && closer.length >= 2 {
+                true => 2,
+                false => 1,
+            };
+            opener.length -= count;
+            closer.length -= count;
+            if opener.length == 0 {
+                self.runs.pop();
+                self.openers.pop();
+            }
+            for bottom in &mut self.bottoms {
+                *bottom = (*bottom).min(self.openers.len());
+            }
+
+            self.push(match count {
+                2 => InlineToken::Bold(content),
+                _ => InlineToken::Italic(content),
+            });
+        }
+    }
+
+    /// Returns the content, where the runs left unmatched are literal text
+    pub(crate) fn finish(self) -> Vec<InlineToken> {
+        let mut content = self.tokens;
+        for (delimiter, tokens) in self.runs {
+            push_text(&mut content, &delimiter.text());
+            for token in tokens {
+                push_inline(&mut content, token);
+            }
+        }
+
+        content
+    }
+}
+
+/// Returns true when a run of `open` delimiters, which could close emphasis as well when
+/// `open_closes`, can be closed by a run of `close` delimiters, which could open emphasis as well
+/// when `close_opens`. When either run could do both, the sum of their lengths mustn't be a
+/// multiple of three unless both lengths are. That keeps `*foo**bar**baz*` italic text around
+/// bold text.
+fn delims_match(open: usize, open_closes: bool, close: usize, close_opens: bool) -> bool {
+    !(open_closes || close_opens)
+        || !(open + close).is_multiple_of(3)
+        || (open.is_multiple_of(3) && close.is_multiple_of(3))
+}
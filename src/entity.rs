@@ -0,0 +1,114 @@
+/// Named character references and the characters they stand for. The most common ones of the
+/// HTML list, other names are left as written.
+const NAMED: &[(&str, char)] = &[
+    ("amp", '&'),
+    ("lt", '<'),
+    ("gt", '>'),
+    ("quot", '"'),
+    ("apos", '\''),
+    ("nbsp", '\u{a0}'),
+    ("copy", '©'),
+    ("reg", '®'),
+    ("trade", '™'),
+    ("hellip", '…'),
+    ("ndash", '–'),
+    ("mdash", '—'),
+    ("lsquo", '‘'),
+    ("rsquo", '’'),
+    ("ldquo", '“'),
+    ("rdquo", '”'),
+    ("laquo", '«'),
+    ("raquo", '»'),
+    ("bull", '•'),
+    ("middot", '·'),
+    ("deg", '°'),
+    ("plusmn", '±'),
+    ("times", '×'),
+    ("divide", '÷'),
+    ("sect", '§'),
+    ("para", '¶'),
+    ("cent", '¢'),
+    ("pound", '£'),
+    ("euro", '€'),
+    ("yen", '¥'),
+];
+
+/// Replaces the entity references in text by the characters they stand for: named ones like
+/// `&amp;` and numeric ones like `&#35;` or `&#x22;`. An `&` that doesn't start a valid reference
+/// is kept as it is.
+pub fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        match decode_entity(rest) {
+            Some((char, length)) => {
+                decoded.push(char);
+                rest = &rest[length..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+
+    decoded
+}
+
+/// Decodes the entity reference at the start of `text`, returning the character it stands for and
+/// its length in bytes. Numeric references to code point 0 or to no character at all stand for
+/// the replacement character.
+pub fn decode_entity(text: &str) -> Option<(char, usize)> {
+    // no reference is longer than `&#x` and six digits or the longest name
+    let end = text.bytes().take(16).position(|byte| byte == b';')?;
+    let name = text.strip_prefix('&')?.get(..end - 1)?;
+
+    let char = match name.strip_prefix('#') {
+        Some(number) => {
+            let (digits, radix, max) = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => (hex, 16, 6),
+                None => (number, 10, 7),
+            };
+            if digits.is_empty()
+                || digits.len() > max
+                || !digits.chars().all(|char| char.is_digit(radix))
+            {
+                return None;
+            }
+            let code = u32::from_str_radix(digits, radix).ok()?;
+            char::from_u32(code)
+                .filter(|char| *char != '\0')
+                .unwrap_or(char::REPLACEMENT_CHARACTER)
+        }
+        None => NAMED
+            .iter()
+            .find(|(named, _)| *named == name)
+            .map(|(_, char)| *char)?,
+    };
+
+    Some((char, end + 1))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decode_references() {
+        let tests = vec![
+            ("a&amp;b", "a&b"),
+            ("&lt;&gt; &copy;", "<> ©"),
+            ("&#35; &#x22; &#X41;", "# \" A"),
+            ("&#0; &#1114112;", "\u{fffd} \u{fffd}"),
+            ("a & b &amp c", "a & b &amp c"),
+            ("&foo; &#; &#x; &#12345678;", "&foo; &#; &#x; &#12345678;"),
+            ("&&amp;;", "&&;"),
+        ];
+        for (text, decoded) in tests {
+            assert_eq!(decode_entities(text), decoded, "{:?}", text);
+        }
+    }
+}
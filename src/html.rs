@@ -0,0 +1,809 @@
+use crate::bytes::{encode, Encoding};
+use crate::parser::{
+    Alignment, CodeBlock, Document, Element, Heading, InlineToken, List, ListKind, Paragraph,
+};
+use crate::render::{walk, Node, Writer};
+use crate::sanitize::{sanitize_html, sanitize_url};
+use crate::slug::Slugger;
+
+/// Options for rendering a document to HTML
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// Gives every heading an `id` derived from its text, unique within the document
+    pub heading_anchors: bool,
+    /// Wraps the text of anchored headings in a link to the heading itself
+    pub heading_anchor_links: bool,
+    /// Added to the level of every heading, up to `<h6>`, for documents embedded in a page that
+    /// has headings of its own
+    pub heading_offset: usize,
+    /// Renders a paragraph holding nothing but an image as the bare `<img>`, without the `<p>`
+    pub unwrap_standalone_images: bool,
+    /// Makes the output safe for untrusted input: link and image URLs with a scheme other than
+    /// http(s), mailto, ftp or tel are rewritten to `#`. Enabled by default.
+    pub sanitize: bool,
+    /// Renders the content of code blocks from their language and code, for syntax highlighting.
+    /// The returned HTML is inserted as is, so the highlighter has to escape the code itself.
+    pub highlight: Option<fn(Option<&str>, &str) -> String>,
+    /// Opens external links, the absolute `http(s)://` ones, in the given browsing context, like
+    /// `_blank`. They are given `rel="noopener noreferrer"` as well, so that the page they open
+    /// can't reach back to this one.
+    pub external_link_target: Option<&'static str>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            heading_anchors: false,
+            heading_anchor_links: false,
+            heading_offset: 0,
+            unwrap_standalone_images: false,
+            sanitize: true,
+            highlight: None,
+            external_link_target: None,
+        }
+    }
+}
+
+/// Renders the document to an HTML string. Every block element is followed by a newline.
+pub fn render_html(document: &Document) -> String {
+    render_html_with(document, &RenderOptions::default())
+}
+
+/// Renders the document to an HTML string using the given options
+pub fn render_html_with(document: &Document, options: &RenderOptions) -> String {
+    let mut renderer = HtmlRenderer::new(options);
+    renderer.render_elements(&document.elements);
+
+    renderer.output
+}
+
+/// Renders the document to HTML using the given options and encodes it. Characters that ASCII
+/// can't hold are written as numeric character references rather than replaced.
+pub fn render_html_encoded(
+    document: &Document,
+    options: &RenderOptions,
+    encoding: Encoding,
+) -> Vec<u8> {
+    let html = render_html_with(document, options);
+    if encoding != Encoding::ASCII {
+        return encode(&html, encoding);
+    }
+
+    let mut ascii = String::with_capacity(html.len());
+    for char in html.chars() {
+        if char.is_ascii() {
+            ascii.push(char);
+        } else {
+            ascii.push_str(&format!("&#{};", char as u32));
+        }
+    }
+    ascii.into_bytes()
+}
+
+/// Escapes the characters that have a special meaning in HTML text and attribute values
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for char in text.chars() {
+        match char {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(char),
+        }
+    }
+
+    escaped
+}
+
+/// Returns true for absolute `http://` and `https://` URLs, which lead to other sites
+fn is_external(href: &str) -> bool {
+    let scheme = href.split_once("://").map(|(scheme, _)| scheme);
+    scheme.is_some_and(|scheme| {
+        scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https")
+    })
+}
+
+struct HtmlRenderer<'a> {
+    options: &'a RenderOptions,
+    output: String,
+    slugger: Slugger,
+    /// The open list items and block quotes. Paragraphs right inside an item of a tight list
+    /// aren't wrapped in `<p>` tags.
+    containers: Vec<Container>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Container {
+    BlockQuote,
+    TightItem,
+    LooseItem,
+}
+
+impl<'a> HtmlRenderer<'a> {
+    fn new(options: &'a RenderOptions) -> Self {
+        Self {
+            options,
+            output: String::new(),
+            slugger: Slugger::new(),
+            containers: Vec::new(),
+        }
+    }
+
+    fn render_elements(&mut self, elements: &[Element]) {
+        walk(elements, self);
+    }
+
+    fn in_list_item(&self) -> bool {
+        matches!(
+            self.containers.last(),
+            Some(Container::TightItem | Container::LooseItem)
+        )
+    }
+
+    fn in_tight_list_item(&self) -> bool {
+        self.containers.last() == Some(&Container::TightItem)
+    }
+
+    /// Writes the opening tag of a heading, giving it an id when it has one or anchors are enabled
+    fn open_heading(&mut self, heading: &Heading) {
+        let level = self.heading_level(heading);
+        // an explicit id is used even without generated anchors
+        let id = match heading.attributes.id.is_some() || self.options.heading_anchors {
+            true => Some(escape_html(&self.slugger.heading_id(heading))),
+            false => None,
+        };
+
+        self.output.push_str(&format!("<h{}", level));
+        if let Some(id) = &id {
+            self.output.push_str(&format!(" id=\"{}\"", id));
+        }
+        if !heading.attributes.classes.is_empty() {
+            self.output.push_str(&format!(
+                " class=\"{}\"",
+                escape_html(&heading.attributes.classes.join(" "))
+            ));
+        }
+        self.output.push('>');
+
+        if let Some(id) = id.filter(|_| self.options.heading_anchor_links) {
+            self.output.push_str(&format!("<a href=\"#{}\">", id));
+        }
+    }
+
+    fn close_heading(&mut self, heading: &Heading) {
+        let anchored = heading.attributes.id.is_some() || self.options.heading_anchors;
+        if anchored && self.options.heading_anchor_links {
+            self.output.push_str("</a>");
+        }
+        self.output
+            .push_str(&format!("</h{}>\n", self.heading_level(heading)));
+    }
+
+    fn heading_level(&self, heading: &Heading) -> usize {
+        std::cmp::min(heading.level + self.options.heading_offset, 6)
+    }
+
+    fn is_standalone_image(&self, paragraph: &Paragraph) -> bool {
+        self.options.unwrap_standalone_images
+            && matches!(paragraph.0.as_slice(), [InlineToken::Image(_)])
+    }
+
+    /// Line breaks inside a paragraph are kept as newlines, an empty paragraph isn't rendered.
+    /// Paragraphs inside an item of a tight list are not wrapped in `<p>` tags.
+    fn open_paragraph(&mut self, paragraph: &Paragraph) {
+        if !paragraph.0.is_empty()
+            && !self.is_standalone_image(paragraph)
+            && !self.in_tight_list_item()
+        {
+            self.output.push_str("<p>");
+        }
+    }
+
+    fn close_paragraph(&mut self, paragraph: &Paragraph) {
+        if paragraph.0.is_empty() || self.in_tight_list_item() {
+            return;
+        }
+        match self.is_standalone_image(paragraph) {
+            true => self.output.push('\n'),
+            false => self.output.push_str("</p>\n"),
+        }
+    }
+
+    fn open_table_cell(&mut self, tag: &str, alignment: Alignment) {
+        let align = match alignment {
+            Alignment::Left => Some("left"),
+            Alignment::Center => Some("center"),
+            Alignment::Right => Some("right"),
+            Alignment::None => None,
+        };
+        match align {
+            Some(align) => self
+                .output
+                .push_str(&format!("<{} style=\"text-align: {}\">", tag, align)),
+            None => self.output.push_str(&format!("<{}>", tag)),
+        }
+    }
+
+    fn render_code_block(&mut self, code_block: &CodeBlock) {
+        match &code_block.lang {
+            Some(lang) => self.output.push_str(&format!(
+                "<pre><code class=\"language-{}\">",
+                escape_html(lang)
+            )),
+            None => self.output.push_str("<pre><code>"),
+        }
+        match self.options.highlight {
+            Some(highlight) => self
+                .output
+                .push_str(&highlight(code_block.lang.as_deref(), &code_block.code)),
+            None => self.output.push_str(&escape_html(&code_block.code)),
+        }
+        self.output.push_str("</code></pre>\n");
+    }
+
+    fn list_tag(list: &List) -> &'static str {
+        match list.kind {
+            ListKind::Ordered => "ol",
+            ListKind::Unordered => "ul",
+        }
+    }
+
+    fn open_list(&mut self, list: &List) {
+        let tag = Self::list_tag(list);
+        match list.start {
+            Some(start) if start != 1 => self
+                .output
+                .push_str(&format!("<{} start=\"{}\">\n", tag, start)),
+            _ => self.output.push_str(&format!("<{}>\n", tag)),
+        }
+    }
+
+    fn open_list_item(&mut self, list: &List, index: usize) {
+        self.output.push_str("<li>");
+        match list.checked(index) {
+            Some(true) => self
+                .output
+                .push_str("<input type=\"checkbox\" disabled checked /> "),
+            Some(false) => self
+                .output
+                .push_str("<input type=\"checkbox\" disabled /> "),
+            None => {}
+        }
+    }
+
+    fn url<'u>(&self, url: &'u str) -> &'u str {
+        match self.options.sanitize {
+            true => sanitize_url(url),
+            false => url,
+        }
+    }
+
+    fn open_inline_token(&mut self, token: &InlineToken) {
+        match token {
+            InlineToken::Text(text) => self.output.push_str(&escape_html(text)),
+            InlineToken::Bold(_) => self.output.push_str("<strong>"),
+            InlineToken::Italic(_) => self.output.push_str("<em>"),
+            InlineToken::Code(code_span) => {
+                match &code_span.lang {
+                    Some(lang) => self
+                        .output
+                        .push_str(&format!("<code class=\"language-{}\">", escape_html(lang))),
+                    None => self.output.push_str("<code>"),
+                }
+                self.output.push_str(&escape_html(&code_span.code));
+                self.output.push_str("</code>");
+            }
+            InlineToken::Link(link) => {
+                let href = self.url(&link.href);
+                self.output
+                    .push_str(&format!("<a href=\"{}\"", escape_html(href)));
+                if let Some(title) = &link.title {
+                    self.output
+                        .push_str(&format!(" title=\"{}\"", escape_html(title)));
+                }
+                if let Some(target) = self.options.external_link_target {
+                    if is_external(href) {
+                        self.output.push_str(&format!(
+                            " target=\"{}\" rel=\"noopener noreferrer\"",
+                            escape_html(target)
+                        ));
+                    }
+                }
+                self.output.push('>');
+            }
+            InlineToken::Image(image) => {
+                self.output.push_str(&format!(
+                    "<img src=\"{}\" alt=\"{}\"",
+                    escape_html(self.url(&image.src)),
+                    escape_html(&image.alt)
+                ));
+                if let Some(title) = &image.title {
+                    self.output
+                        .push_str(&format!(" title=\"{}\"", escape_html(title)));
+                }
+                self.output.push_str(" />");
+            }
+            InlineToken::Comment(_) => {}
+            InlineToken::LineBreak => self.output.push_str("<br />\n"),
+        }
+    }
+
+    fn close_inline_token(&mut self, token: &InlineToken) {
+        match token {
+            InlineToken::Bold(_) => self.output.push_str("</strong>"),
+            InlineToken::Italic(_) => self.output.push_str("</em>"),
+            InlineToken::Link(_) => self.output.push_str("</a>"),
+            _ => {}
+        }
+    }
+}
+
+impl Writer for HtmlRenderer<'_> {
+    fn enter(&mut self, node: Node<'_>) -> bool {
+        match node {
+            Node::Element(element) => {
+                // blocks like a nested list start on a line of their own inside the item
+                if self.in_list_item()
+                    && (!matches!(element, Element::Paragraph(_)) || !self.in_tight_list_item())
+                    && !self.output.ends_with('\n')
+                {
+                    self.output.push('\n');
+                }
+                match element {
+                    Element::Heading(heading) => self.open_heading(heading),
+                    Element::Paragraph(paragraph) => self.open_paragraph(paragraph),
+                    Element::List(list) => self.open_list(list),
+                    Element::CodeBlock(code_block) => self.render_code_block(code_block),
+                    Element::BlockQuote(_) => {
+                        self.containers.push(Container::BlockQuote);
+                        self.output.push_str("<blockquote>\n");
+                    }
+                    Element::ThematicBreak => self.output.push_str("<hr />\n"),
+                    // comments are notes for whoever edits the source, not part of the output
+                    Element::Comment(_) => {}
+                    Element::Html(html) => {
+                        let html = match self.options.sanitize {
+                            true => sanitize_html(html),
+                            false => html.clone(),
+                        };
+                        // a block of nothing but dropped elements leaves no line behind
+                        if !html.trim().is_empty() {
+                            self.output.push_str(&html);
+                            self.output.push('\n');
+                        }
+                    }
+                    Element::Table(_) => self.output.push_str("<table>\n<thead>\n"),
+                }
+            }
+            Node::Item(list, index) => {
+                self.containers.push(match list.loose {
+                    true => Container::LooseItem,
+                    false => Container::TightItem,
+                });
+                self.open_list_item(list, index);
+            }
+            Node::Row(..) => self.output.push_str("<tr>\n"),
+            Node::Cell { header, alignment } => {
+                self.open_table_cell(if header { "th" } else { "td" }, alignment)
+            }
+            Node::Inline(token) => self.open_inline_token(token),
+        }
+
+        true
+    }
+
+    fn leave(&mut self, node: Node<'_>) {
+        match node {
+            Node::Element(element) => match element {
+                Element::Heading(heading) => self.close_heading(heading),
+                Element::Paragraph(paragraph) => self.close_paragraph(paragraph),
+                Element::List(list) => self
+                    .output
+                    .push_str(&format!("</{}>\n", Self::list_tag(list))),
+                Element::BlockQuote(_) => {
+                    self.containers.pop();
+                    self.output.push_str("</blockquote>\n");
+                }
+                // body rows get as many cells as the header, see `walk`
+                Element::Table(table) => {
+                    if !table.rows.is_empty() {
+                        self.output.push_str("</tbody>\n");
+                    }
+                    self.output.push_str("</table>\n");
+                }
+                _ => {}
+            },
+            Node::Item(..) => {
+                self.containers.pop();
+                self.output.push_str("</li>\n");
+            }
+            Node::Row(table, row) => {
+                self.output.push_str("</tr>\n");
+                if row.is_none() {
+                    self.output.push_str("</thead>\n");
+                    if !table.rows.is_empty() {
+                        self.output.push_str("<tbody>\n");
+                    }
+                }
+            }
+            Node::Cell { header, .. } => self
+                .output
+                .push_str(&format!("</{}>\n", if header { "th" } else { "td" })),
+            Node::Inline(token) => self.close_inline_token(token),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::InlineToken;
+
+    #[test]
+    fn render_document() {
+        let document = Document::new(vec![
+            Element::new_heading(1, vec![InlineToken::new_text("Title")]),
+            Element::new_paragraph(vec![
+                InlineToken::new_text("a "),
+                InlineToken::new_blod(vec![InlineToken::new_text("b")]),
+                InlineToken::new_text(" & "),
+                InlineToken::new_link(vec![InlineToken::new_text("c")], "/c"),
+            ]),
+            Element::new_list(
+                ListKind::Unordered,
+                vec![vec![Element::new_paragraph(vec![InlineToken::new_text(
+                    "item",
+                )])]],
+            ),
+        ]);
+
+        assert_eq!(
+            render_html(&document),
+            "<h1>Title</h1>\n<p>a <strong>b</strong> &amp; <a href=\"/c\">c</a></p>\n<ul>\n<li>item</li>\n</ul>\n"
+        );
+    }
+
+    #[test]
+    fn render_heading_anchors() {
+        let document = Document::new(vec![
+            Element::new_heading(2, vec![InlineToken::new_text("My Heading")]),
+            Element::new_heading(
+                3,
+                vec![
+                    InlineToken::new_text("My "),
+                    InlineToken::new_code("Heading"),
+                ],
+            ),
+        ]);
+        let mut options = RenderOptions {
+            heading_anchors: true,
+            ..RenderOptions::default()
+        };
+
+        assert_eq!(
+            render_html_with(&document, &options),
+            "<h2 id=\"my-heading\">My Heading</h2>\n<h3 id=\"my-heading-1\">My <code>Heading</code></h3>\n"
+        );
+
+        options.heading_anchor_links = true;
+        assert_eq!(
+            render_html_with(&document, &options),
+            "<h2 id=\"my-heading\"><a href=\"#my-heading\">My Heading</a></h2>\n<h3 id=\"my-heading-1\"><a href=\"#my-heading-1\">My <code>Heading</code></a></h3>\n"
+        );
+    }
+
+    #[test]
+    fn render_heading_attributes() {
+        let options = crate::parser::ParserOptions {
+            heading_attributes: true,
+            ..Default::default()
+        };
+        let document = crate::parse_with_options("# A {#x .y .z}\n\n## B", options);
+
+        assert_eq!(
+            render_html(&document),
+            "<h1 id=\"x\" class=\"y z\">A</h1>\n<h2>B</h2>\n"
+        );
+        assert_eq!(
+            render_html_with(
+                &document,
+                &RenderOptions {
+                    heading_anchors: true,
+                    ..RenderOptions::default()
+                }
+            ),
+            "<h1 id=\"x\" class=\"y z\">A</h1>\n<h2 id=\"b\">B</h2>\n"
+        );
+
+        // the slugs of the headings after an explicit id don't take it again
+        let options = crate::parser::ParserOptions {
+            heading_attributes: true,
+            ..Default::default()
+        };
+        let document = crate::parse_with_options("# Foo {#bar}\n\n# Bar\n\n# Bar", options);
+        assert_eq!(
+            render_html_with(
+                &document,
+                &RenderOptions {
+                    heading_anchors: true,
+                    ..RenderOptions::default()
+                }
+            ),
+            "<h1 id=\"bar\">Foo</h1>\n<h1 id=\"bar-1\">Bar</h1>\n<h1 id=\"bar-2\">Bar</h1>\n"
+        );
+    }
+
+    #[test]
+    fn render_paragraphs() {
+        assert_eq!(
+            render_html(&crate::parse("a **b *c* d** [`d`](e)\nf ![g](h)")),
+            "<p>a <strong>b <em>c</em> d</strong> <a href=\"e\"><code>d</code></a>\nf <img src=\"h\" alt=\"g\" /></p>\n"
+        );
+        assert_eq!(
+            render_html(&Document::new(vec![Element::new_paragraph(vec![])])),
+            ""
+        );
+    }
+
+    #[test]
+    fn render_standalone_images() {
+        let document = crate::parse("![a](b)\n\n![c](d) e");
+        assert_eq!(
+            render_html(&document),
+            "<p><img src=\"b\" alt=\"a\" /></p>\n<p><img src=\"d\" alt=\"c\" /> e</p>\n"
+        );
+
+        let options = RenderOptions {
+            unwrap_standalone_images: true,
+            ..RenderOptions::default()
+        };
+        assert_eq!(
+            render_html_with(&document, &options),
+            "<img src=\"b\" alt=\"a\" />\n<p><img src=\"d\" alt=\"c\" /> e</p>\n"
+        );
+    }
+
+    #[test]
+    fn render_image_titles() {
+        let document = crate::parse("![a](b \"<t>\")");
+        assert_eq!(
+            render_html(&document),
+            "<p><img src=\"b\" alt=\"a\" title=\"&lt;t&gt;\" /></p>\n"
+        );
+    }
+
+    #[test]
+    fn render_comments() {
+        let document = crate::parse("<!-- a -->\n\nb<!-- c -->d");
+        assert_eq!(render_html(&document), "<p>bd</p>\n");
+    }
+
+    #[test]
+    fn render_nested_lists() {
+        let item = |text| Element::new_paragraph(vec![InlineToken::new_text(text)]);
+        let document = Document::new(vec![Element::new_list(
+            ListKind::Unordered,
+            vec![
+                vec![
+                    item("a"),
+                    Element::new_ordered_list(
+                        2,
+                        vec![
+                            vec![item("b")],
+                            vec![Element::new_list(
+                                ListKind::Unordered,
+                                vec![vec![item("c")]],
+                            )],
+                        ],
+                    ),
+                ],
+                vec![item("d")],
+            ],
+        )]);
+
+        assert_eq!(
+            render_html(&document),
+            "<ul>\n<li>a\n<ol start=\"2\">\n<li>b</li>\n<li>\n<ul>\n<li>c</li>\n</ul>\n</li>\n</ol>\n</li>\n<li>d</li>\n</ul>\n"
+        );
+    }
+
+    #[test]
+    fn render_task_lists() {
+        let source = "- [x] done\n- [ ] todo\n- plain\n- [y] not a task";
+        let options = crate::parser::ParserOptions {
+            task_lists: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            render_html(&crate::parse_with_options(source, options)),
+            "<ul>\n<li><input type=\"checkbox\" disabled checked /> done</li>\n<li><input type=\"checkbox\" disabled /> todo</li>\n<li>plain</li>\n<li>[y] not a task</li>\n</ul>\n"
+        );
+        assert_eq!(
+            render_html(&crate::parse("- [x] done")),
+            "<ul>\n<li>[x] done</li>\n</ul>\n"
+        );
+    }
+
+    #[test]
+    fn render_tables() {
+        let cell = |text| vec![InlineToken::new_text(text)];
+        let document = Document::new(vec![Element::new_table(
+            vec![
+                Alignment::Left,
+                Alignment::Center,
+                Alignment::Right,
+                Alignment::None,
+            ],
+            vec![cell("a"), cell("b"), cell("c"), cell("d")],
+            vec![
+                vec![
+                    vec![InlineToken::new_blod(cell("1"))],
+                    cell("2"),
+                    cell("3"),
+                    cell("4"),
+                ],
+                vec![cell("5"), cell("6")],
+            ],
+        )]);
+
+        assert_eq!(
+            render_html(&document),
+            "<table>\n<thead>\n<tr>\n<th style=\"text-align: left\">a</th>\n<th style=\"text-align: center\">b</th>\n<th style=\"text-align: right\">c</th>\n<th>d</th>\n</tr>\n</thead>\n<tbody>\n<tr>\n<td style=\"text-align: left\"><strong>1</strong></td>\n<td style=\"text-align: center\">2</td>\n<td style=\"text-align: right\">3</td>\n<td>4</td>\n</tr>\n<tr>\n<td style=\"text-align: left\">5</td>\n<td style=\"text-align: center\">6</td>\n<td style=\"text-align: right\"></td>\n<td></td>\n</tr>\n</tbody>\n</table>\n"
+        );
+
+        let document = Document::new(vec![Element::new_table(
+            vec![Alignment::None],
+            vec![cell("a")],
+            vec![],
+        )]);
+        assert_eq!(
+            render_html(&document),
+            "<table>\n<thead>\n<tr>\n<th>a</th>\n</tr>\n</thead>\n</table>\n"
+        );
+    }
+
+    #[test]
+    fn render_heading_offset() {
+        let options = RenderOptions {
+            heading_offset: 2,
+            ..RenderOptions::default()
+        };
+
+        assert_eq!(
+            render_html_with(&crate::parse("# hi"), &options),
+            "<h3>hi</h3>\n"
+        );
+        assert_eq!(
+            render_html_with(&crate::parse("##### hi"), &options),
+            "<h6>hi</h6>\n"
+        );
+    }
+
+    #[test]
+    fn render_ordered_lists() {
+        let document = Document::new(vec![
+            Element::new_ordered_list(
+                3,
+                vec![vec![Element::new_paragraph(vec![InlineToken::new_text(
+                    "a",
+                )])]],
+            ),
+            Element::new_list(
+                ListKind::Ordered,
+                vec![vec![Element::new_paragraph(vec![InlineToken::new_text(
+                    "b",
+                )])]],
+            ),
+        ]);
+
+        assert_eq!(
+            render_html(&document),
+            "<ol start=\"3\">\n<li>a</li>\n</ol>\n<ol>\n<li>b</li>\n</ol>\n"
+        );
+        assert_eq!(
+            render_html(&crate::parse("3. a\n4. b")),
+            "<ol start=\"3\">\n<li>a</li>\n<li>b</li>\n</ol>\n"
+        );
+    }
+
+    #[test]
+    fn render_image_in_link() {
+        assert_eq!(
+            render_html(&crate::parse("[![alt](img.png) *b*](/href)")),
+            "<p><a href=\"/href\"><img src=\"img.png\" alt=\"alt\" /> <em>b</em></a></p>\n"
+        );
+    }
+
+    fn highlight(lang: Option<&str>, code: &str) -> String {
+        if lang != Some("rust") {
+            return escape_html(code);
+        }
+
+        code.split(' ')
+            .map(|word| match word {
+                "fn" | "let" => format!("<span class=\"keyword\">{}</span>", word),
+                word => escape_html(word),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn render_highlighted_code() {
+        let document = crate::parse("```rust\nlet a = b < c;\n```\n\n```\nlet x\n```");
+        let options = RenderOptions {
+            highlight: Some(highlight),
+            ..RenderOptions::default()
+        };
+
+        assert_eq!(
+            render_html_with(&document, &options),
+            "<pre><code class=\"language-rust\"><span class=\"keyword\">let</span> a = b &lt; c;\n</code></pre>\n<pre><code>let x\n</code></pre>\n"
+        );
+    }
+
+    #[test]
+    fn render_loose_lists() {
+        let document = crate::parse("- a\n\n- b\n\ntext\n\n- c\n- d");
+
+        assert_eq!(
+            render_html(&document),
+            "<ul>\n<li>\n<p>a</p>\n</li>\n<li>\n<p>b</p>\n</li>\n</ul>\n<p>text</p>\n<ul>\n<li>c</li>\n<li>d</li>\n</ul>\n"
+        );
+    }
+
+    #[test]
+    fn render_sanitized() {
+        let document =
+            crate::parse("[x](javascript:alert(1)) ![y](JavaScript:z)\n\n<script>alert</script>");
+
+        assert_eq!(
+            render_html(&document),
+            "<p><a href=\"#\">x</a> <img src=\"#\" alt=\"y\" /></p>\n"
+        );
+
+        let options = RenderOptions {
+            sanitize: false,
+            ..RenderOptions::default()
+        };
+        assert_eq!(
+            render_html_with(&document, &options),
+            "<p><a href=\"javascript:alert(1)\">x</a> <img src=\"JavaScript:z\" alt=\"y\" /></p>\n<script>alert</script>\n"
+        );
+    }
+
+    #[test]
+    fn render_encoded() {
+        let document = crate::parse("é");
+        let options = RenderOptions::default();
+
+        assert_eq!(
+            render_html_encoded(&document, &options, Encoding::UTF16LE),
+            b"\xFF\xFE<\0p\0>\0\xE9\0<\0/\0p\0>\0\n\0"
+        );
+        assert_eq!(
+            render_html_encoded(&document, &options, Encoding::ASCII),
+            b"<p>&#233;</p>\n"
+        );
+    }
+
+    #[test]
+    fn render_external_link_target() {
+        let document = crate::parse("[a](https://example.com) [b](/docs) [c](HTTP://x) [d](#top)");
+        let options = RenderOptions {
+            external_link_target: Some("_blank"),
+            ..RenderOptions::default()
+        };
+
+        assert_eq!(
+            render_html_with(&document, &options),
+            "<p><a href=\"https://example.com\" target=\"_blank\" rel=\"noopener noreferrer\">a</a> <a href=\"/docs\">b</a> <a href=\"HTTP://x\" target=\"_blank\" rel=\"noopener noreferrer\">c</a> <a href=\"#top\">d</a></p>\n"
+        );
+        assert_eq!(
+            render_html(&document),
+            "<p><a href=\"https://example.com\">a</a> <a href=\"/docs\">b</a> <a href=\"HTTP://x\">c</a> <a href=\"#top\">d</a></p>\n"
+        );
+    }
+}
@@ -0,0 +1,269 @@
+use crate::parser::{
+    Alignment, Document, Element, FrontMatterKind, InlineToken, ListKind, TableRow,
+};
+
+impl Document {
+    /// Serializes the document to a JSON string. Every node is an object with a `type` field,
+    /// container nodes hold their content in `children`.
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{\"type\":\"document\",\"front_matter\":");
+        match &self.front_matter {
+            Some((kind, raw)) => {
+                let kind = match kind {
+                    FrontMatterKind::Yaml => "yaml",
+                    FrontMatterKind::Toml => "toml",
+                    FrontMatterKind::Json => "json",
+                };
+                json.push_str(&format!(
+                    "{{\"kind\":{},\"raw\":{}}}",
+                    string(kind),
+                    string(raw)
+                ));
+            }
+            None => json.push_str("null"),
+        }
+        json.push_str(",\"children\":");
+        push_elements(&mut json, &self.elements);
+        json.push('}');
+
+        json
+    }
+}
+
+/// Quotes and escapes a string as a JSON string literal
+fn string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for char in s.chars() {
+        match char {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+fn optional_string(s: &Option<String>) -> String {
+    match s {
+        Some(s) => string(s),
+        None => "null".to_string(),
+    }
+}
+
+fn push_elements(json: &mut String, elements: &[Element]) {
+    json.push('[');
+    for (i, element) in elements.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        push_element(json, element);
+    }
+    json.push(']');
+}
+
+fn push_element(json: &mut String, element: &Element) {
+    match element {
+        Element::Heading(heading) => {
+            json.push_str(&format!(
+                "{{\"type\":\"heading\",\"level\":{},\"children\":",
+                heading.level
+            ));
+            push_inline_tokens(json, &heading.tokens);
+            json.push('}');
+        }
+        Element::Paragraph(paragraph) => {
+            json.push_str("{\"type\":\"paragraph\",\"children\":");
+            push_inline_tokens(json, &paragraph.0);
+            json.push('}');
+        }
+        Element::List(list) => {
+            json.push_str(&format!(
+                "{{\"type\":\"list\",\"ordered\":{},\"start\":{},\"loose\":{},\"items\":[",
+                list.kind == ListKind::Ordered,
+                list.start
+                    .map_or("null".to_string(), |start| start.to_string()),
+                list.is_loose()
+            ));
+            for (i, item) in list.items.iter().enumerate() {
+                if i > 0 {
+                    json.push(',');
+                }
+                push_elements(json, item);
+            }
+            json.push_str("]}");
+        }
+        Element::CodeBlock(code_block) => {
+            json.push_str(&format!(
+                "{{\"type\":\"code_block\",\"lang\":{},\"code\":{}}}",
+                optional_string(&code_block.lang),
+                string(&code_block.code)
+            ));
+        }
+        Element::BlockQuote(elements) => {
+            json.push_str("{\"type\":\"blockquote\",\"children\":");
+            push_elements(json, elements);
+            json.push('}');
+        }
+        Element::ThematicBreak => json.push_str("{\"type\":\"thematic_break\"}"),
+        Element::Comment(comment) => {
+            json.push_str(&format!(
+                "{{\"type\":\"comment\",\"value\":{}}}",
+                string(comment)
+            ));
+        }
+        Element::Html(html) => {
+            json.push_str(&format!("{{\"type\":\"html\",\"value\":{}}}", string(html)));
+        }
+        Element::Table(table) => {
+            let alignments = table
+                .alignments
+                .iter()
+                .map(|alignment| match alignment {
+                    Alignment::None => "null",
+                    Alignment::Left => "\"left\"",
+                    Alignment::Center => "\"center\"",
+                    Alignment::Right => "\"right\"",
+                })
+                .collect::<Vec<_>>();
+            json.push_str(&format!(
+                "{{\"type\":\"table\",\"alignments\":[{}],\"header\":",
+                alignments.join(",")
+            ));
+            push_table_row(json, &table.header);
+            json.push_str(",\"rows\":[");
+            for (i, row) in table.rows.iter().enumerate() {
+                if i > 0 {
+                    json.push(',');
+                }
+                push_table_row(json, row);
+            }
+            json.push_str("]}");
+        }
+    }
+}
+
+/// Pushes a row as an array of cells, each an array of inline tokens
+fn push_table_row(json: &mut String, row: &TableRow) {
+    json.push('[');
+    for (i, cell) in row.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        push_inline_tokens(json, cell);
+    }
+    json.push(']');
+}
+
+fn push_inline_tokens(json: &mut String, tokens: &[InlineToken]) {
+    json.push('[');
+    for (i, token) in tokens.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        push_inline_token(json, token);
+    }
+    json.push(']');
+}
+
+fn push_inline_token(json: &mut String, token: &InlineToken) {
+    match token {
+        InlineToken::Text(text) => {
+            json.push_str(&format!("{{\"type\":\"text\",\"value\":{}}}", string(text)));
+        }
+        InlineToken::Code(code_span) => {
+            json.push_str(&format!(
+                "{{\"type\":\"code\",\"lang\":{},\"value\":{}}}",
+                optional_string(&code_span.lang),
+                string(&code_span.code)
+            ));
+        }
+        InlineToken::Bold(tokens) => {
+            json.push_str("{\"type\":\"bold\",\"children\":");
+            push_inline_tokens(json, tokens);
+            json.push('}');
+        }
+        InlineToken::Italic(tokens) => {
+            json.push_str("{\"type\":\"italic\",\"children\":");
+            push_inline_tokens(json, tokens);
+            json.push('}');
+        }
+        InlineToken::Link(link) => {
+            json.push_str(&format!(
+                "{{\"type\":\"link\",\"href\":{},\"title\":{},\"children\":",
+                string(&link.href),
+                optional_string(&link.title)
+            ));
+            push_inline_tokens(json, &link.tokens);
+            json.push('}');
+        }
+        InlineToken::Image(image) => {
+            json.push_str(&format!(
+                "{{\"type\":\"image\",\"src\":{},\"alt\":{},\"title\":{}}}",
+                string(&image.src),
+                string(&image.alt),
+                optional_string(&image.title)
+            ));
+        }
+        InlineToken::Comment(comment) => {
+            json.push_str(&format!(
+                "{{\"type\":\"comment\",\"value\":{}}}",
+                string(comment)
+            ));
+        }
+        InlineToken::LineBreak => json.push_str("{\"type\":\"line_break\"}"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn escape_strings() {
+        assert_eq!(
+            string("a \"b\" \\ c\n\u{1}"),
+            "\"a \\\"b\\\" \\\\ c\\n\\u0001\""
+        );
+    }
+
+    #[test]
+    fn serialize_heading() {
+        assert_eq!(
+            crate::parse("# hi").to_json(),
+            "{\"type\":\"document\",\"front_matter\":null,\"children\":[{\"type\":\"heading\",\"level\":1,\"children\":[{\"type\":\"text\",\"value\":\"hi\"}]}]}"
+        );
+    }
+
+    #[test]
+    fn serialize_lists() {
+        let children = |json: String| {
+            json.strip_prefix("{\"type\":\"document\",\"front_matter\":null,\"children\":[")
+                .and_then(|json| json.strip_suffix("]}"))
+                .unwrap()
+                .to_string()
+        };
+
+        assert_eq!(
+            children(crate::parse("- a").to_json()),
+            "{\"type\":\"list\",\"ordered\":false,\"start\":null,\"loose\":false,\"items\":[[{\"type\":\"paragraph\",\"children\":[{\"type\":\"text\",\"value\":\"a\"}]}]]}"
+        );
+        assert_eq!(
+            children(crate::parse("3. a\n\n4. b").to_json()),
+            "{\"type\":\"list\",\"ordered\":true,\"start\":3,\"loose\":true,\"items\":[[{\"type\":\"paragraph\",\"children\":[{\"type\":\"text\",\"value\":\"a\"}]}],[{\"type\":\"paragraph\",\"children\":[{\"type\":\"text\",\"value\":\"b\"}]}]]}"
+        );
+    }
+
+    #[test]
+    fn serialize_image_titles() {
+        let json = crate::parse("![a](b \"t\") ![c](d)").to_json();
+        assert!(json.contains("{\"type\":\"image\",\"src\":\"b\",\"alt\":\"a\",\"title\":\"t\"}"));
+        assert!(json.contains("{\"type\":\"image\",\"src\":\"d\",\"alt\":\"c\",\"title\":null}"));
+    }
+}
@@ -0,0 +1,70 @@
+#![allow(clippy::upper_case_acronyms)]
+
+#[allow(dead_code)]
+pub mod bytes;
+#[allow(dead_code)]
+pub mod tokenizer;
+
+#[allow(dead_code)]
+pub mod parser;
+
+#[allow(dead_code)]
+pub mod reference;
+
+pub mod autolink;
+pub mod cli;
+pub mod edit;
+mod emphasis;
+pub mod entity;
+pub mod html;
+pub mod json;
+pub mod markdown;
+pub mod outline;
+mod render;
+pub mod reparse;
+pub mod sanitize;
+pub mod slug;
+pub mod smart;
+pub mod span;
+pub mod statistics;
+pub mod streaming;
+pub mod text;
+pub mod transform;
+mod tree;
+pub mod truncate;
+pub mod validate;
+
+#[cfg(test)]
+mod commonmark;
+
+use bytes::{CharIterator, Encoding};
+use parser::{Document, InlineToken, Parser, ParserOptions};
+use tokenizer::Tokenizer;
+
+/// Parses a Markdown string into a document
+pub fn parse(markdown: &str) -> Document {
+    parse_with_options(markdown, ParserOptions::default())
+}
+
+/// Parses a Markdown string into a document using the given options
+pub fn parse_with_options(markdown: &str, options: ParserOptions) -> Document {
+    let mut chars = CharIterator::new();
+    chars.read_from_str(markdown, Some(Encoding::UTF8));
+
+    let mut tokenizer = Tokenizer::new(&mut chars);
+    let mut parser = Parser::with_options(&mut tokenizer, options);
+
+    parser.parse()
+}
+
+/// Parses a Markdown string holding only inline content, such as a table cell, without wrapping it
+/// in blocks
+pub fn parse_inline(markdown: &str) -> Vec<InlineToken> {
+    let mut chars = CharIterator::new();
+    chars.read_from_str(markdown, Some(Encoding::UTF8));
+
+    let mut tokenizer = Tokenizer::new(&mut chars);
+    let mut parser = Parser::new(&mut tokenizer);
+
+    parser.parse_inline()
+}
@@ -0,0 +1,690 @@
+use crate::bytes::{encode, Encoding};
+use crate::entity::decode_entity;
+use crate::parser::{
+    Alignment, CodeBlock, Document, Element, FrontMatterKind, Heading, InlineToken, List, ListKind,
+    Table,
+};
+use crate::render::{walk, Node, Writer};
+
+/// How headings are written, see `MarkdownStyle::heading`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum HeadingStyle {
+    /// `# Title`
+    Atx,
+    /// The title underlined by `=` or `-`. Only levels 1 and 2 have such an underline, deeper
+    /// headings are written as ATX headings.
+    Setext,
+}
+
+/// How hard line breaks are written, see `MarkdownStyle::hard_break`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum HardBreakStyle {
+    /// Two spaces ending the line
+    Spaces,
+    /// A backslash ending the line, which unlike spaces shows in the source
+    Backslash,
+}
+
+/// The markup choices made when serializing a document back to Markdown
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct MarkdownStyle {
+    /// Delimiter of emphasis, `*` or `_`, doubled for strong emphasis
+    pub emphasis: char,
+    /// Marker of unordered list items, `-`, `*` or `+`
+    pub bullet: char,
+    pub heading: HeadingStyle,
+    /// Fence of code blocks, `` ` `` or `~`
+    pub fence: char,
+    /// Reflows paragraphs to lines of at most this many characters, indentation and block quote
+    /// markers included. Lines are only broken at whitespace, so a word, link or code span longer
+    /// than the width is left on a line of its own.
+    pub wrap_width: Option<usize>,
+    pub hard_break: HardBreakStyle,
+}
+
+impl Default for MarkdownStyle {
+    fn default() -> Self {
+        Self {
+            emphasis: '*',
+            bullet: '-',
+            heading: HeadingStyle::Atx,
+            fence: '`',
+            wrap_width: None,
+            hard_break: HardBreakStyle::Spaces,
+        }
+    }
+}
+
+/// Serializes the document to Markdown written in the given style. Blocks are separated by a
+/// blank line and the output ends with a newline.
+pub fn render_markdown(document: &Document, style: MarkdownStyle) -> String {
+    let mut renderer = MarkdownRenderer {
+        style,
+        outer_styles: Vec::new(),
+        blocks: vec![Vec::new()],
+    };
+    walk(&document.elements, &mut renderer);
+
+    let mut blocks = Vec::new();
+    if let Some((kind, raw)) = &document.front_matter {
+        blocks.push(match kind {
+            FrontMatterKind::Yaml => format!("---\n{}\n---", raw),
+            FrontMatterKind::Toml => format!("+++\n{}\n+++", raw),
+            FrontMatterKind::Json => raw.clone(),
+        });
+    }
+    blocks.push(join_blocks(renderer.close()));
+
+    let mut markdown = blocks.join("\n\n").trim_end().to_string();
+    if !markdown.is_empty() {
+        markdown.push('\n');
+    }
+
+    markdown
+}
+
+/// Serializes the document to Markdown written in the given style and encodes it, see `encode`
+pub fn render_markdown_encoded(
+    document: &Document,
+    style: MarkdownStyle,
+    encoding: Encoding,
+) -> Vec<u8> {
+    encode(&render_markdown(document, style), encoding)
+}
+
+/// Renders the blocks it is given one at a time, and the containers holding them once they are
+/// complete
+struct MarkdownRenderer {
+    /// The style of the innermost container, see `open`
+    style: MarkdownStyle,
+    /// The styles of the containers around it
+    outer_styles: Vec<MarkdownStyle>,
+    /// The blocks rendered so far in each open container, the document being the first one, along
+    /// with whether they are paragraphs
+    blocks: Vec<Vec<(bool, String)>>,
+}
+
+impl Writer for MarkdownRenderer {
+    fn enter(&mut self, node: Node<'_>) -> bool {
+        let element = match node {
+            Node::Element(element) => element,
+            Node::Item(list, index) => {
+                self.open(list_marker_width(list, index));
+                return true;
+            }
+            // table rows and inline tokens are rendered along with the element holding them
+            _ => return false,
+        };
+
+        let block = match element {
+            Element::Heading(heading) => self.render_heading(heading),
+            Element::Paragraph(paragraph) => self.render_paragraph(&paragraph.0),
+            Element::CodeBlock(code_block) => self.render_code_block(code_block),
+            Element::ThematicBreak => "---".to_string(),
+            Element::Comment(comment) => format!("<!--{}-->", comment),
+            Element::Html(html) => html.clone(),
+            Element::Table(table) => self.render_table(table),
+            Element::List(_) => {
+                self.open(0);
+                return true;
+            }
+            Element::BlockQuote(_) => {
+                self.open(2);
+                return true;
+            }
+        };
+        self.push_block(matches!(element, Element::Paragraph(_)), block);
+
+        false
+    }
+
+    fn leave(&mut self, node: Node<'_>) {
+        match node {
+            Node::Element(Element::List(list)) => {
+                let items = self.close().into_iter().map(|(_, item)| item);
+                let separator = if list.loose { "\n\n" } else { "\n" };
+                self.push_block(false, items.collect::<Vec<_>>().join(separator));
+            }
+            Node::Element(Element::BlockQuote(_)) => {
+                let quote = join_blocks(self.close())
+                    .lines()
+                    .map(|line| match line.is_empty() {
+                        true => ">".to_string(),
+                        false => format!("> {}", line),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                self.push_block(false, quote);
+            }
+            Node::Item(list, index) => {
+                let blocks = self.close();
+                let item = self.render_list_item(list, index, blocks);
+                self.push_block(false, item);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl MarkdownRenderer {
+    /// Starts a container block whose lines are indented by `indent` characters, which leaves its
+    /// content that much less room
+    fn open(&mut self, indent: usize) {
+        let wrap_width = self
+            .style
+            .wrap_width
+            .map(|width| width.saturating_sub(indent).max(1));
+        self.outer_styles.push(self.style);
+        self.style = MarkdownStyle {
+            wrap_width,
+            ..self.style
+        };
+        self.blocks.push(Vec::new());
+    }
+
+    /// Ends the innermost container, returning its blocks
+    fn close(&mut self) -> Vec<(bool, String)> {
+        if let Some(style) = self.outer_styles.pop() {
+            self.style = style;
+        }
+        self.blocks.pop().unwrap_or_default()
+    }
+
+    fn push_block(&mut self, paragraph: bool, block: String) {
+        if let Some(blocks) = self.blocks.last_mut() {
+            blocks.push((paragraph, block));
+        }
+    }
+
+    fn render_heading(&self, heading: &Heading) -> String {
+        let mut text = self.render_inline_tokens(&heading.tokens);
+        let attributes = &heading.attributes;
+        if attributes.id.is_some() || !attributes.classes.is_empty() {
+            let attributes = attributes
+                .id
+                .iter()
+                .map(|id| format!("#{}", id))
+                .chain(attributes.classes.iter().map(|class| format!(".{}", class)))
+                .collect::<Vec<_>>();
+            text.push_str(&format!(" {{{}}}", attributes.join(" ")));
+        }
+
+        let underline = match heading.level {
+            1 => '=',
+            _ => '-',
+        };
+        match self.style.heading {
+            HeadingStyle::Setext if heading.level <= 2 && !text.trim().is_empty() => {
+                let lines = text.split('\n').map(escape_line_start).collect::<Vec<_>>();
+                let width = lines.iter().map(|line| line.chars().count()).max();
+                let width = std::cmp::max(width.unwrap_or_default(), 3);
+                format!(
+                    "{}\n{}",
+                    lines.join("\n"),
+                    underline.to_string().repeat(width)
+                )
+            }
+            // the lines of a setext heading are joined, an ATX heading ends with its line
+            _ => format!("{} {}", "#".repeat(heading.level), text.replace('\n', " ")),
+        }
+    }
+
+    fn render_paragraph(&self, tokens: &[InlineToken]) -> String {
+        let width = match self.style.wrap_width {
+            Some(width) => width,
+            None => {
+                return self
+                    .render_inline_tokens(tokens)
+                    .split('\n')
+                    .map(escape_line_start)
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+        };
+
+        let mut words = Vec::new();
+        self.push_words(tokens, &mut words, &mut false);
+
+        // greedy: every word goes on the current line when it fits, else on a new line
+        let mut lines = Vec::new();
+        let mut line = String::new();
+        for word in words {
+            if line.is_empty() {
+                line = escape_line_start(&word);
+            } else if line.chars().count() + 1 + word.chars().count() <= width {
+                line.push(' ');
+                line.push_str(&word);
+            } else {
+                lines.push(line);
+                line = escape_line_start(&word);
+            }
+            // a word ending with a hard break ends its line
+            if let Some(broken) = line.strip_suffix('\n') {
+                lines.push(broken.to_string());
+                line = String::new();
+            }
+        }
+        if !line.is_empty() || lines.is_empty() {
+            lines.push(line);
+        }
+
+        lines.join("\n")
+    }
+
+    /// Splits the rendered tokens into the words a paragraph can be wrapped between. Whitespace in
+    /// text separates words, everything else is glued to the text around it. `glue` tells whether
+    /// the next token continues the last word.
+    fn push_words(&self, tokens: &[InlineToken], words: &mut Vec<String>, glue: &mut bool) {
+        let push = |words: &mut Vec<String>, glue: &mut bool, markup: &str| {
+            match words.last_mut() {
+                Some(word) if *glue => word.push_str(markup),
+                _ => words.push(markup.to_string()),
+            }
+            *glue = true;
+        };
+
+        for token in tokens {
+            match token {
+                InlineToken::Text(text) => {
+                    let text = escape_markdown(text);
+                    for (i, word) in text.split(char::is_whitespace).enumerate() {
+                        if i > 0 {
+                            *glue = false;
+                        }
+                        if !word.is_empty() {
+                            push(words, glue, word);
+                        }
+                    }
+                    *glue = !text.ends_with(char::is_whitespace);
+                }
+                InlineToken::Bold(tokens) | InlineToken::Italic(tokens) => {
+                    let delimiter = match token {
+                        InlineToken::Bold(_) => self.style.emphasis.to_string().repeat(2),
+                        _ => self.style.emphasis.to_string(),
+                    };
+                    push(words, glue, &delimiter);
+                    self.push_words(tokens, words, glue);
+                    push(words, glue, &delimiter);
+                }
+                // the break ends the word before it, and the line, see `render_paragraph`
+                InlineToken::LineBreak => {
+                    push(words, glue, &self.render_inline_token(token));
+                    *glue = false;
+                }
+                token => push(words, glue, &self.render_inline_token(token)),
+            }
+        }
+    }
+
+    /// Writes the item at `index` of the list from the blocks it holds. A paragraph is only kept apart
+    /// from the block before it by a blank line, and the lines after the first are indented to the
+    /// content column of the item.
+    fn render_list_item(&self, list: &List, index: usize, blocks: Vec<(bool, String)>) -> String {
+        let mut marker = match list.kind {
+            ListKind::Ordered => format!("{}.", list.start.unwrap_or(1) + index),
+            ListKind::Unordered => self.style.bullet.to_string(),
+        };
+        match list.checked(index) {
+            Some(true) => marker.push_str(" [x]"),
+            Some(false) => marker.push_str(" [ ]"),
+            None => {}
+        }
+
+        let mut content = String::new();
+        for (i, (paragraph, block)) in blocks.into_iter().enumerate() {
+            if i > 0 {
+                content.push_str(if paragraph { "\n\n" } else { "\n" });
+            }
+            content.push_str(&block);
+        }
+
+        let indent = " ".repeat(list_marker_width(list, index));
+        let lines = content
+            .lines()
+            .enumerate()
+            .map(|(i, line)| match (i, line.is_empty()) {
+                (0, true) => marker.clone(),
+                (0, false) => format!("{} {}", marker, line),
+                (_, true) => String::new(),
+                (_, false) => format!("{}{}", indent, line),
+            })
+            .collect::<Vec<_>>();
+        match lines.is_empty() {
+            true => marker,
+            false => lines.join("\n"),
+        }
+    }
+
+    /// Writes a pipe table, giving every row as many cells as the header
+    fn render_table(&self, table: &Table) -> String {
+        let columns = table.header.len();
+        let row = |cells: &[Vec<InlineToken>]| {
+            let cells = (0..columns)
+                .map(|column| match cells.get(column) {
+                    Some(cell) => self.render_inline_tokens(cell).replace('|', "\\|"),
+                    None => String::new(),
+                })
+                .collect::<Vec<_>>();
+            format!("| {} |", cells.join(" | "))
+        };
+
+        let delimiters = (0..columns)
+            .map(|column| match table.alignments.get(column) {
+                Some(Alignment::Left) => ":---",
+                Some(Alignment::Center) => ":---:",
+                Some(Alignment::Right) => "---:",
+                Some(Alignment::None) | None => "---",
+            })
+            .collect::<Vec<_>>();
+
+        let mut lines = vec![
+            row(&table.header),
+            format!("| {} |", delimiters.join(" | ")),
+        ];
+        lines.extend(table.rows.iter().map(|cells| row(cells)));
+        lines.join("\n")
+    }
+
+    fn render_code_block(&self, code_block: &CodeBlock) -> String {
+        // the fence is longer than any run of fence characters in the code
+        let longest = longest_run(&code_block.code, self.style.fence);
+        let fence = self
+            .style
+            .fence
+            .to_string()
+            .repeat(std::cmp::max(longest + 1, 3));
+
+        let mut code = code_block.code.clone();
+        if !code.is_empty() && !code.ends_with('\n') {
+            code.push('\n');
+        }
+        format!(
+            "{}{}\n{}{}",
+            fence,
+            code_block.lang.as_deref().unwrap_or(""),
+            code,
+            fence
+        )
+    }
+
+    fn render_inline_tokens(&self, tokens: &[InlineToken]) -> String {
+        tokens
+            .iter()
+            .map(|token| self.render_inline_token(token))
+            .collect()
+    }
+
+    fn render_inline_token(&self, token: &InlineToken) -> String {
+        match token {
+            InlineToken::Text(text) => escape_markdown(text),
+            InlineToken::Bold(tokens) => {
+                let delimiter = self.style.emphasis.to_string().repeat(2);
+                format!(
+                    "{}{}{}",
+                    delimiter,
+                    self.render_inline_tokens(tokens),
+                    delimiter
+                )
+            }
+            InlineToken::Italic(tokens) => format!(
+                "{}{}{}",
+                self.style.emphasis,
+                self.render_inline_tokens(tokens),
+                self.style.emphasis
+            ),
+            InlineToken::Code(code_span) => {
+                let code = match &code_span.lang {
+                    Some(lang) => format!("{}:{}", lang, code_span.code),
+                    None => code_span.code.clone(),
+                };
+                // the delimiter is a run of backticks of a length that doesn't appear in the code,
+                // code touching it is padded so that its backticks aren't taken for it
+                let runs = code.split(|c| c != '`').map(str::len).collect::<Vec<_>>();
+                let length = (1..).find(|length| !runs.contains(length)).unwrap_or(1);
+                let delimiter = "`".repeat(length);
+                match code.starts_with('`') || code.ends_with('`') {
+                    true => format!("{} {} {}", delimiter, code, delimiter),
+                    false => format!("{}{}{}", delimiter, code, delimiter),
+                }
+            }
+            InlineToken::Link(link) => {
+                format!(
+                    "[{}]({})",
+                    self.render_inline_tokens(&link.tokens),
+                    destination(&link.href, &link.title)
+                )
+            }
+            InlineToken::Image(image) => {
+                format!(
+                    "![{}]({})",
+                    escape_markdown(&image.alt),
+                    destination(&image.src, &image.title)
+                )
+            }
+            InlineToken::Comment(comment) => format!("<!--{}-->", comment),
+            InlineToken::LineBreak => match self.style.hard_break {
+                HardBreakStyle::Spaces => "  \n".to_string(),
+                HardBreakStyle::Backslash => "\\\n".to_string(),
+            },
+        }
+    }
+}
+
+/// Writes the destination of a link or an image, followed by its title in double quotes
+fn destination(href: &str, title: &Option<String>) -> String {
+    let mut destination = escape_entities(href);
+    if let Some(title) = title {
+        let title = title.replace('\\', "\\\\").replace('"', "\\\"");
+        destination.push_str(&format!(" \"{}\"", escape_entities(&title)));
+    }
+
+    destination
+}
+
+/// Escapes the first character of a paragraph line that would otherwise start a block
+fn escape_line_start(line: &str) -> String {
+    if line.starts_with(['#', '>', '-', '+', '=']) {
+        return format!("\\{}", line);
+    }
+    // only an ordered list starting at 1 interrupts a paragraph
+    match line.strip_prefix('1') {
+        Some(rest)
+            if rest.starts_with(['.', ')'])
+                && (rest.len() == 1 || rest[1..].starts_with([' ', '\t'])) =>
+        {
+            format!("1\\{}", rest)
+        }
+        _ => line.to_string(),
+    }
+}
+
+/// Blocks are separated by a blank line
+fn join_blocks(blocks: Vec<(bool, String)>) -> String {
+    let blocks = blocks.into_iter().map(|(_, block)| block);
+    blocks.collect::<Vec<_>>().join("\n\n")
+}
+
+/// Returns the width of the marker of the item at `index` along with the space following it
+fn list_marker_width(list: &List, index: usize) -> usize {
+    match list.kind {
+        ListKind::Ordered => (list.start.unwrap_or(1) + index).to_string().len() + 2,
+        ListKind::Unordered => 2,
+    }
+}
+
+/// Escapes the `&` of a destination or a title that would otherwise be read as an entity reference
+fn escape_entities(destination: &str) -> String {
+    let mut escaped = String::with_capacity(destination.len());
+    for (index, char) in destination.char_indices() {
+        if char == '&' && decode_entity(&destination[index..]).is_some() {
+            escaped.push('\\');
+        }
+        escaped.push(char);
+    }
+
+    escaped
+}
+
+/// Escapes the characters of text that would otherwise be read as inline markup
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for char in text.chars() {
+        if matches!(char, '\\' | '`' | '*' | '_' | '[' | ']' | '<') {
+            escaped.push('\\');
+        }
+        escaped.push(char);
+    }
+
+    escaped
+}
+
+/// Returns the length of the longest run of `char` in `text`
+fn longest_run(text: &str, char: char) -> usize {
+    text.split(|c| c != char).map(str::len).max().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse_with_options;
+    use crate::parser::ParserOptions;
+
+    const SOURCE: &str = "# Title {#top}\n\nSome *emphasis* and **strong** text, `co``de` with a [link](/a \"t\").\n\n- one\n- [x] two\n  1. nested\n\n> quoted \\*text\\*\n\n```rust\nlet x = 1;\n```\n";
+
+    fn document() -> Document {
+        let options = ParserOptions {
+            heading_attributes: true,
+            task_lists: true,
+            ..Default::default()
+        };
+        parse_with_options(SOURCE, options)
+    }
+
+    #[test]
+    fn render_default_style() {
+        let document = document();
+        let markdown = render_markdown(&document, MarkdownStyle::default());
+
+        assert_eq!(markdown, SOURCE);
+    }
+
+    #[test]
+    fn render_custom_style() {
+        let document = document();
+        let style = MarkdownStyle {
+            emphasis: '_',
+            bullet: '*',
+            heading: HeadingStyle::Setext,
+            fence: '~',
+            wrap_width: None,
+            hard_break: HardBreakStyle::Backslash,
+        };
+        let markdown = render_markdown(&document, style);
+
+        assert_eq!(
+            markdown,
+            "Title {#top}\n============\n\nSome _emphasis_ and __strong__ text, `co``de` with a [link](/a \"t\").\n\n* one\n* [x] two\n  1. nested\n\n> quoted \\*text\\*\n\n~~~rust\nlet x = 1;\n~~~\n"
+        );
+
+        let options = ParserOptions {
+            heading_attributes: true,
+            task_lists: true,
+            ..Default::default()
+        };
+        assert_eq!(parse_with_options(&markdown, options), document);
+    }
+
+    #[test]
+    fn render_built_document() {
+        use crate::parser::{Heading, Paragraph};
+
+        let document = Document::new(vec![
+            Heading::from_text(1, "Title").into(),
+            Paragraph::from_text("hi *not emphasis*").into(),
+            List::unordered(vec!["a", "b"]).into(),
+        ]);
+        let markdown = render_markdown(&document, MarkdownStyle::default());
+
+        assert_eq!(markdown, "# Title\n\nhi \\*not emphasis\\*\n\n- a\n- b\n");
+        assert_eq!(crate::parse(&markdown), document);
+    }
+
+    #[test]
+    fn render_hard_breaks() {
+        let document = crate::parse("a  \nb   \\\nc\nd \\\\\ne");
+        let tests = vec![
+            (HardBreakStyle::Spaces, "a  \nb  \nc\nd \\\\\ne\n"),
+            (HardBreakStyle::Backslash, "a\\\nb\\\nc\nd \\\\\ne\n"),
+        ];
+        for (hard_break, expected) in tests {
+            let style = MarkdownStyle {
+                hard_break,
+                ..Default::default()
+            };
+            let markdown = render_markdown(&document, style);
+
+            assert_eq!(markdown, expected);
+            assert_eq!(crate::parse(&markdown), document);
+        }
+
+        // a hard break ends the line of a wrapped paragraph wherever it is
+        let style = MarkdownStyle {
+            wrap_width: Some(10),
+            ..Default::default()
+        };
+        assert_eq!(render_markdown(&document, style), "a  \nb  \nc d \\\\ e\n");
+    }
+
+    #[test]
+    fn render_link_titles() {
+        let tests = vec![
+            ("[x](a \"t&amp;\")", "[x](a \"t&\")\n"),
+            ("[x]\n\n[x]: /a 'lit \\&amp;'", "[x](/a \"lit \\&amp;\")\n"),
+            (
+                "[x](/a\\&amp;b (q\\\\ \"b\"))",
+                "[x](/a\\&amp;b \"q\\\\ \\\"b\\\"\")\n",
+            ),
+            ("![a](/i 't')", "![a](/i \"t\")\n"),
+            ("![a][i]\n\n[i]: /i (\"t\")", "![a](/i \"\\\"t\\\"\")\n"),
+        ];
+        for (source, expected) in tests {
+            let document = crate::parse(source);
+            let markdown = render_markdown(&document, MarkdownStyle::default());
+
+            assert_eq!(markdown, expected);
+            assert_eq!(crate::parse(&markdown), document);
+        }
+    }
+
+    #[test]
+    fn wrap_paragraphs() {
+        let document = parse_with_options(
+            "A long paragraph with **strong words** and a [link that won't break](/x) and \
+             `code that stays whole`, then 1. more\ntext.\n\n- an item wrapped to the column",
+            ParserOptions::default(),
+        );
+        let style = MarkdownStyle {
+            wrap_width: Some(20),
+            ..Default::default()
+        };
+        let markdown = render_markdown(&document, style);
+
+        assert_eq!(
+            markdown,
+            "A long paragraph\nwith **strong\nwords** and a\n[link that won't break](/x)\nand\n`code that stays whole`,\nthen 1. more text.\n\n- an item wrapped to\n  the column\n"
+        );
+        for line in markdown.lines() {
+            let unbreakable =
+                !line.trim_start().contains(' ') || line.starts_with('[') || line.starts_with('`');
+            assert!(line.chars().count() <= 20 || unbreakable, "{:?}", line);
+        }
+
+        // a word moved to the start of a line doesn't start a list
+        let document = parse_with_options("aaaa 1. b - c", ParserOptions::default());
+        let style = MarkdownStyle {
+            wrap_width: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(render_markdown(&document, style), "aaaa\n1\\. b\n\\- c\n");
+    }
+}
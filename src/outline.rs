@@ -0,0 +1,378 @@
+use crate::markdown::{render_markdown, MarkdownStyle};
+use crate::parser::{Document, Element, Heading, InlineToken, ListItem, ListKind};
+use crate::slug::Slugger;
+use std::iter::Peekable;
+
+/// A heading along with the headings of the sections nested in its section
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct OutlineNode {
+    pub heading: Heading,
+    pub children: Vec<OutlineNode>,
+}
+
+impl Document {
+    /// Returns the section hierarchy of the document: every heading holds the headings of a
+    /// deeper level that follow it, up to the next heading of its level or above. Skipped levels
+    /// don't add nodes, an `h3` right after an `h1` is a child of the `h1`. Only top-level headings
+    /// make sections, the ones in block quotes and lists are left out.
+    pub fn headings_tree(&self) -> Vec<OutlineNode> {
+        let mut headings = self
+            .elements
+            .iter()
+            .filter_map(|element| match element {
+                Element::Heading(heading) => Some(heading),
+                _ => None,
+            })
+            .peekable();
+
+        outline_nodes(&mut headings, 0)
+    }
+
+    /// Returns a table of contents in Markdown: a bullet list of links to the top-level headings of
+    /// `max_level` or above, nested like `headings_tree`. The links point to the anchors of
+    /// `RenderOptions::heading_anchors`, which are given to every heading in document order, the
+    /// ones left out of the list included. Empty when there are no such headings.
+    pub fn to_outline_markdown(&self, max_level: usize) -> String {
+        let mut anchors = Vec::new();
+        anchor_headings(&self.elements, true, &mut Slugger::new(), &mut anchors);
+        let mut anchors = anchors
+            .into_iter()
+            .filter(|(heading, _)| heading.level <= max_level)
+            .peekable();
+
+        let items = outline_items(&mut anchors, 0);
+        if items.is_empty() {
+            return String::new();
+        }
+        let outline = Document::new(vec![Element::new_list(ListKind::Unordered, items)]);
+        render_markdown(&outline, MarkdownStyle::default())
+    }
+
+    /// Splits the document into chunks that start at a heading of `level` or a shallower one and
+    /// run up to the next such heading. Content before the first of them makes a leading chunk,
+    /// front matter included. Chunks keep the spans of their elements in the original source, not
+    /// the source itself or the diagnostics.
+    pub fn split_by_heading(&self, level: usize) -> Vec<Document> {
+        let mut chunks: Vec<Document> = Vec::new();
+        for (index, element) in self.elements.iter().enumerate() {
+            let starts_chunk =
+                matches!(element, Element::Heading(heading) if heading.level <= level);
+            if starts_chunk || chunks.is_empty() {
+                chunks.push(Document {
+                    front_matter: match chunks.is_empty() {
+                        true => self.front_matter.clone(),
+                        false => None,
+                    },
+                    ..Document::default()
+                });
+            }
+
+            let chunk = chunks.last_mut().unwrap();
+            chunk.elements.push(element.clone());
+            if let Some(span) = self.spans.get(index) {
+                chunk.spans.push(span.clone());
+            }
+            if let Some(spans) = self.descendant_spans.get(index) {
+                chunk.descendant_spans.push(spans.clone());
+            }
+        }
+
+        chunks
+    }
+
+    /// Returns the element with the given id. Headings are the only elements with ids: their
+    /// explicit `{#id}` or else the slug of their text, deduplicated in document order like the
+    /// anchors of `RenderOptions::heading_anchors`.
+    pub fn get_by_id(&self, id: &str) -> Option<&Element> {
+        find_by_id(&self.elements, id, &mut Slugger::new())
+    }
+
+    /// Returns the first element `predicate` holds for. The search is depth first: the elements in
+    /// a list item or block quote come right after the element holding them.
+    pub fn find<P>(&self, predicate: P) -> Option<&Element>
+    where
+        P: FnMut(&Element) -> bool,
+    {
+        self.find_all(predicate).next()
+    }
+
+    /// Returns all the elements `predicate` holds for, in the order of `find`
+    pub fn find_all<P>(&self, mut predicate: P) -> impl Iterator<Item = &Element>
+    where
+        P: FnMut(&Element) -> bool,
+    {
+        Descendants::new(&self.elements).filter(move |element| predicate(element))
+    }
+}
+
+/// The elements of a document and the elements nested in them, depth first
+pub(crate) struct Descendants<'a> {
+    /// The elements left to visit at each level, the innermost last
+    stack: Vec<std::slice::Iter<'a, Element>>,
+}
+
+impl<'a> Descendants<'a> {
+    pub(crate) fn new(elements: &'a [Element]) -> Self {
+        Descendants {
+            stack: vec![elements.iter()],
+        }
+    }
+}
+
+impl<'a> Iterator for Descendants<'a> {
+    type Item = &'a Element;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Some(element) = self.stack.last_mut()?.next() else {
+                self.stack.pop();
+                continue;
+            };
+            match element {
+                Element::BlockQuote(elements) => self.stack.push(elements.iter()),
+                Element::List(list) => {
+                    let items = list.items.iter().rev();
+                    self.stack.extend(items.map(|item| item.iter()));
+                }
+                _ => {}
+            }
+
+            return Some(element);
+        }
+    }
+}
+
+/// Takes the headings deeper than `level` from the front of `headings`, nesting them by level
+fn outline_nodes<'a, I>(headings: &mut Peekable<I>, level: usize) -> Vec<OutlineNode>
+where
+    I: Iterator<Item = &'a Heading>,
+{
+    let mut nodes = Vec::new();
+    while let Some(heading) = headings.next_if(|heading| heading.level > level) {
+        nodes.push(OutlineNode {
+            heading: heading.clone(),
+            children: outline_nodes(headings, heading.level),
+        });
+    }
+
+    nodes
+}
+
+/// Collects the headings of `elements` along with their anchors when `top_level` is true. The
+/// headings nested in block quotes and lists aren't collected but are slugged all the same, as
+/// they take a slug in the rendered HTML.
+fn anchor_headings<'a>(
+    elements: &'a [Element],
+    top_level: bool,
+    slugger: &mut Slugger,
+    anchors: &mut Vec<(&'a Heading, String)>,
+) {
+    for element in elements {
+        match element {
+            Element::Heading(heading) => {
+                let anchor = slugger.heading_id(heading);
+                if top_level {
+                    anchors.push((heading, anchor));
+                }
+            }
+            Element::BlockQuote(elements) => anchor_headings(elements, false, slugger, anchors),
+            Element::List(list) => {
+                for item in &list.items {
+                    anchor_headings(item, false, slugger, anchors);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Takes the headings deeper than `level` from the front of `anchors` and makes them list items
+/// linking to their anchors, nested by level like `outline_nodes`
+fn outline_items<'a, I>(anchors: &mut Peekable<I>, level: usize) -> Vec<ListItem>
+where
+    I: Iterator<Item = (&'a Heading, String)>,
+{
+    let mut items = Vec::new();
+    while let Some((heading, anchor)) = anchors.next_if(|(heading, _)| heading.level > level) {
+        let link = InlineToken::new_link(
+            vec![InlineToken::new_text(&heading.text())],
+            &format!("#{}", anchor),
+        );
+        let mut item = vec![Element::new_paragraph(vec![link])];
+        let children = outline_items(anchors, heading.level);
+        if !children.is_empty() {
+            item.push(Element::new_list(ListKind::Unordered, children));
+        }
+        items.push(item);
+    }
+
+    items
+}
+
+/// Looks for the element with the id `id` in `elements` and the elements nested in them, slugging
+/// the headings without an explicit id along the way
+fn find_by_id<'a>(elements: &'a [Element], id: &str, slugger: &mut Slugger) -> Option<&'a Element> {
+    for element in elements {
+        let found = match element {
+            Element::Heading(heading) => (slugger.heading_id(heading) == id).then_some(element),
+            Element::BlockQuote(elements) => find_by_id(elements, id, slugger),
+            Element::List(list) => list
+                .items
+                .iter()
+                .find_map(|item| find_by_id(item, id, slugger)),
+            _ => None,
+        };
+        if found.is_some() {
+            return found;
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parser::{Element, ParserOptions};
+    use crate::{parse, parse_with_options};
+
+    /// Renders the tree as the text of every heading followed by its children in parentheses
+    fn shape(nodes: &[super::OutlineNode]) -> String {
+        nodes
+            .iter()
+            .map(|node| match node.children.is_empty() {
+                true => node.heading.text(),
+                false => format!("{}({})", node.heading.text(), shape(&node.children)),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn nest_headings() {
+        let tests = vec![
+            ("# A\n## B\n## C\n# D", "A(B C) D"),
+            ("# A\n### B\n## C\n#### D\n# E", "A(B C(D)) E"),
+            ("## A\n# B\n## C", "A B(C)"),
+            ("# A\n\ntext\n\n> # B\n\n## C", "A(C)"),
+            ("text", ""),
+        ];
+        for (source, expected) in tests {
+            assert_eq!(
+                shape(&parse(source).headings_tree()),
+                expected,
+                "{:?}",
+                source
+            );
+        }
+
+        let tree = parse("# A\n## B\n## C\n# D").headings_tree();
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree[0].heading.level(), 1);
+        assert_eq!(tree[0].children.len(), 2);
+        assert!(tree[1].children.is_empty());
+    }
+
+    #[test]
+    fn outline_markdown() {
+        let document = parse("# Intro\n\n## Install\n\ntext\n\n## Usage *now*");
+        assert_eq!(
+            document.to_outline_markdown(6),
+            "- [Intro](#intro)\n  - [Install](#install)\n  - [Usage now](#usage-now)\n"
+        );
+        assert_eq!(document.to_outline_markdown(1), "- [Intro](#intro)\n");
+        assert_eq!(parse("text").to_outline_markdown(6), "");
+
+        // the quoted heading takes the first slug, like with heading anchors
+        let document = parse("> # A\n\n# A\n\n### B [c]\n\n# D");
+        assert_eq!(
+            document.to_outline_markdown(3),
+            "- [A](#a-1)\n  - [B \\[c\\]](#b-c)\n- [D](#d)\n"
+        );
+    }
+
+    #[test]
+    fn get_by_id() {
+        let options = ParserOptions {
+            heading_attributes: true,
+            ..Default::default()
+        };
+        let document = parse_with_options(
+            "# Intro {#start}\n\n## Usage\n\n> ## Usage\n\ntext",
+            options.clone(),
+        );
+
+        let heading = |id| match document.get_by_id(id) {
+            Some(Element::Heading(heading)) => Some(heading.text()),
+            _ => None,
+        };
+        assert_eq!(heading("start"), Some("Intro".to_string()));
+        assert_eq!(heading("usage"), Some("Usage".to_string()));
+        assert!(matches!(
+            document.get_by_id("usage-1"),
+            Some(Element::Heading(_))
+        ));
+        assert_eq!(heading("intro"), None);
+        assert_eq!(heading("text"), None);
+
+        let document = parse_with_options("# Foo {#bar}\n\n## Bar", options);
+        assert_eq!(
+            document.to_outline_markdown(2),
+            "- [Foo](#bar)\n  - [Bar](#bar-1)\n"
+        );
+        let heading = |id| match document.get_by_id(id) {
+            Some(Element::Heading(heading)) => Some(heading.text()),
+            _ => None,
+        };
+        assert_eq!(heading("bar"), Some("Foo".to_string()));
+        assert_eq!(heading("bar-1"), Some("Bar".to_string()));
+    }
+
+    #[test]
+    fn split_by_heading() {
+        let document = parse("intro\n\n## A\n\na\n\n### A.1\n\n## B\n\n- b\n\n## C");
+        let chunks = document.split_by_heading(2);
+
+        let texts = chunks
+            .iter()
+            .map(crate::text::render_text)
+            .collect::<Vec<_>>();
+        assert_eq!(texts, vec!["intro\n", "A\n\na\n\nA.1\n", "B\n\nb\n", "C\n"]);
+        assert_eq!(chunks[1].span_of(&chunks[1].elements[0]), Some(7..11));
+
+        let chunks = parse("# A\n## B\n# C").split_by_heading(2);
+        assert_eq!(chunks.len(), 3);
+        assert!(parse("").split_by_heading(1).is_empty());
+    }
+
+    #[test]
+    fn find_by_predicate() {
+        let document =
+            parse("# A\n\n- b\n\n  ```\n  c\n  ```\n\n> ## D\n>\n> ```\n> e\n> ```\n\n### F");
+
+        assert_eq!(
+            document.find(|element| matches!(element, Element::CodeBlock(_))),
+            Some(&Element::new_code_block(None, "c\n"))
+        );
+        assert!(document
+            .find(|element| matches!(element, Element::Table(_)))
+            .is_none());
+
+        let headings: Vec<_> = document
+            .find_all(|element| matches!(element, Element::Heading(_)))
+            .map(|element| match element {
+                Element::Heading(heading) => heading.text(),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(headings, ["A", "D", "F"]);
+        assert_eq!(
+            document
+                .find_all(|element| matches!(
+                    element,
+                    Element::BlockQuote(_) | Element::CodeBlock(_)
+                ))
+                .count(),
+            3
+        );
+    }
+}
@@ -1,26 +1,51 @@
-use crate::bytes::{CharIterator, Encoding};
-use crate::tokenizer::{Token, Tokenizer};
+use crate::autolink::autolink;
+use crate::bytes::{CharIterator, Checkpoint, DecodeError, Encoding};
+use crate::emphasis::{Delimiter, DelimiterStack};
+use crate::outline::Descendants;
+use crate::reference::{normalize_label, parse_definition, split_destination, LinkDefinition};
+use crate::sanitize::tag_at;
+use crate::smart::smarten;
+use crate::text::element_text;
+use crate::tokenizer::{Token, TokenKind, Tokenizer};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::ops::Range;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Link {
-    tokens: Vec<InlineToken>,
-    href: String,
+    pub(crate) tokens: Vec<InlineToken>,
+    pub(crate) href: String,
+    pub(crate) title: Option<String>,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Image {
-    src: String,
-    alt: String,
+    pub(crate) src: String,
+    pub(crate) alt: String,
+    pub(crate) title: Option<String>,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
 pub enum InlineToken {
     Text(String),
     Link(Link),
     Image(Image),
     Bold(Vec<InlineToken>),
     Italic(Vec<InlineToken>),
-    Code(String),
+    Code(CodeSpan),
+    /// An HTML comment within a line, holding the text between `<!--` and `-->`
+    Comment(String),
+    /// A hard line break: a line of a paragraph ending with two spaces or more, or a backslash.
+    /// Other line breaks are kept in the text as newlines.
+    LineBreak,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CodeSpan {
+    /// Language written before the code as `lang:code`, see `ParserOptions::code_span_lang`
+    pub(crate) lang: Option<String>,
+    pub(crate) code: String,
 }
 
 impl InlineToken {
@@ -32,6 +57,7 @@ impl InlineToken {
         InlineToken::Link(Link {
             tokens,
             href: href.to_string(),
+            title: None,
         })
     }
 
@@ -44,59 +70,390 @@ impl InlineToken {
     }
 
     pub fn new_code(code: &str) -> Self {
-        InlineToken::Code(code.to_string())
+        InlineToken::new_code_span(None, code)
+    }
+
+    pub fn new_code_span(lang: Option<&str>, code: &str) -> Self {
+        InlineToken::Code(CodeSpan {
+            lang: lang.map(|lang| lang.to_string()),
+            code: code.to_string(),
+        })
     }
 
     pub fn new_img(src: &str, alt: &str) -> Self {
         InlineToken::Image(Image {
             src: src.to_string(),
             alt: alt.to_string(),
+            title: None,
         })
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
-pub struct Paragraph(Vec<InlineToken>);
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Paragraph(pub(crate) Vec<InlineToken>);
+
+impl Paragraph {
+    /// Builds a paragraph of plain text. Markdown syntax in the text isn't parsed, it is escaped
+    /// when the paragraph is serialized.
+    pub fn from_text(text: &str) -> Self {
+        Paragraph(text_tokens(text))
+    }
+
+    /// Returns the text content of the paragraph, see `Heading::text`
+    pub fn text(&self) -> String {
+        plain_text(&self.0)
+    }
+}
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Heading {
-    level: usize,
-    tokens: Vec<InlineToken>,
+    pub(crate) level: usize,
+    pub(crate) tokens: Vec<InlineToken>,
+    /// Written after the content as `{#id .class}`, see `ParserOptions::heading_attributes`
+    pub(crate) attributes: HeadingAttrs,
+}
+
+/// The id and classes set explicitly on a heading
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct HeadingAttrs {
+    pub id: Option<String>,
+    pub classes: Vec<String>,
+}
+
+impl Heading {
+    /// Builds a heading of plain text, see `Paragraph::from_text`
+    pub fn from_text(level: usize, text: &str) -> Self {
+        Heading {
+            level,
+            tokens: text_tokens(text),
+            attributes: HeadingAttrs::default(),
+        }
+    }
+
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    pub fn attributes(&self) -> &HeadingAttrs {
+        &self.attributes
+    }
+
+    /// Returns the text content of the heading: the text of emphasis and links is included,
+    /// images are replaced by their alt text and code spans are kept verbatim.
+    pub fn text(&self) -> String {
+        plain_text(&self.tokens)
+    }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum ListKind {
     Ordered,
     Unordered,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct List {
-    kind: ListKind,
-    items: Vec<ListItem>,
+    pub(crate) kind: ListKind,
+    /// Number of the first item of an ordered list
+    pub(crate) start: Option<usize>,
+    pub(crate) items: Vec<ListItem>,
+    /// Whether each item is a checked task, `None` for plain items. Empty when no item is a task,
+    /// see `ParserOptions::task_lists`.
+    pub(crate) checked: Vec<Option<bool>>,
+    /// Whether a blank line separates two of the items, or two blocks right inside an item
+    pub(crate) loose: bool,
 }
 
 pub type ListItem = Vec<Element>;
 
-#[derive(Debug, PartialEq, Eq)]
-pub struct Document(Vec<Element>);
+impl List {
+    /// Builds a bullet list with an item holding a paragraph of plain text for each of the texts
+    pub fn unordered(items: Vec<&str>) -> Self {
+        List {
+            kind: ListKind::Unordered,
+            start: None,
+            items: items
+                .into_iter()
+                .map(|text| vec![Element::Paragraph(Paragraph::from_text(text))])
+                .collect(),
+            checked: Vec::new(),
+            loose: false,
+        }
+    }
+
+    pub fn kind(&self) -> &ListKind {
+        &self.kind
+    }
+
+    pub fn is_ordered(&self) -> bool {
+        self.kind == ListKind::Ordered
+    }
+
+    /// Returns the number of the first item of an ordered list, `None` for a bullet list
+    pub fn start(&self) -> Option<usize> {
+        self.start
+    }
+
+    pub fn items(&self) -> &[ListItem] {
+        &self.items
+    }
+
+    /// Returns whether the item at `index` is a checked task, `None` when it isn't a task
+    pub fn checked(&self, index: usize) -> Option<bool> {
+        self.checked.get(index).copied().flatten()
+    }
+
+    /// Returns true for a loose list, whose items are separated by blank lines or hold blocks that
+    /// are. The paragraphs of a tight list aren't wrapped in `<p>` tags.
+    pub fn is_loose(&self) -> bool {
+        self.loose
+    }
+
+    /// Returns the number of items in the list
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns the text content of the item at `index`, with its blocks put on lines of their own
+    pub fn item_text(&self, index: usize) -> Option<String> {
+        let item = self.items.get(index)?;
+        let text = item
+            .iter()
+            .filter_map(element_text)
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        Some(text)
+    }
+}
+
+/// Horizontal alignment of the cells of a table column
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Alignment {
+    /// Left to the renderer
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Table {
+    /// Alignment of each column, the header row has as many cells as there are columns
+    pub(crate) alignments: Vec<Alignment>,
+    pub(crate) header: TableRow,
+    /// Body rows, which may have fewer or more cells than the header
+    pub(crate) rows: Vec<TableRow>,
+}
+
+pub type TableRow = Vec<TableCell>;
+
+pub type TableCell = Vec<InlineToken>;
+
+impl Table {
+    pub fn alignments(&self) -> &[Alignment] {
+        &self.alignments
+    }
+
+    pub fn header(&self) -> &TableRow {
+        &self.header
+    }
+
+    pub fn rows(&self) -> &[TableRow] {
+        &self.rows
+    }
+}
+
+/// The format of a front matter block, told apart by its delimiters
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FrontMatterKind {
+    /// Fenced by `---` lines
+    Yaml,
+    /// Fenced by `+++` lines
+    Toml,
+    /// A JSON object starting at the first character
+    Json,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CodeBlock {
+    pub(crate) lang: Option<String>,
+    pub(crate) code: String,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Document {
+    pub(crate) elements: Vec<Element>,
+    /// Kind and raw content of the front matter block, when front matter parsing is enabled
+    pub(crate) front_matter: Option<(FrontMatterKind, String)>,
+    /// Source offsets (in chars) of the top-level elements, empty for documents that weren't parsed
+    pub(crate) spans: Vec<Range<usize>>,
+    /// Source offsets of the elements nested in each top-level element, in depth first order
+    pub(crate) descendant_spans: Vec<Vec<Range<usize>>>,
+    /// The parsed input, when source retention is enabled
+    pub(crate) source: Option<String>,
+    /// Malformed blocks that were skipped while parsing
+    pub(crate) diagnostics: Vec<Diagnostic>,
+}
+
+/// Documents are equal when their content is, regardless of the source they were parsed from
+impl PartialEq for Document {
+    fn eq(&self, other: &Self) -> bool {
+        self.elements == other.elements && self.front_matter == other.front_matter
+    }
+}
+
+impl Eq for Document {}
+
+impl TryFrom<&[u8]> for Document {
+    type Error = DecodeError;
+
+    /// Decodes the bytes in their detected encoding and parses them, see `Document::try_from_bytes`
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Document::try_from_bytes(bytes, None)
+    }
+}
 
 impl Document {
     pub fn new(elements: Vec<Element>) -> Self {
-        Self(elements)
+        Self {
+            elements,
+            front_matter: None,
+            spans: Vec::new(),
+            descendant_spans: Vec::new(),
+            source: None,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// Decodes the bytes and parses them with the default options. Without an encoding it is
+    /// detected from the bytes. Bytes that aren't valid in the encoding are an error.
+    pub fn try_from_bytes(bytes: &[u8], encoding: Option<Encoding>) -> Result<Self, DecodeError> {
+        let mut chars = CharIterator::new();
+        chars.read_from_bytes_strict(bytes, encoding)?;
+
+        let mut tokenizer = Tokenizer::new(&mut chars);
+        Ok(Parser::new(&mut tokenizer).parse())
+    }
+
+    /// Returns the source offsets (in chars) of an element of the document, at the top level or
+    /// nested in a list item or block quote. Elements added or edited after parsing have none.
+    pub fn span_of(&self, element: &Element) -> Option<Range<usize>> {
+        for (index, top) in self.elements.iter().enumerate() {
+            if std::ptr::eq(top, element) {
+                return self.spans.get(index).cloned();
+            }
+
+            let descendants = || Descendants::new(std::slice::from_ref(top)).skip(1);
+            if let Some(position) = descendants().position(|nested| std::ptr::eq(nested, element)) {
+                // the spans are out of step with the elements once these are edited
+                let spans = self.descendant_spans.get(index)?;
+                return match spans.len() == descendants().count() {
+                    true => spans.get(position).cloned(),
+                    false => None,
+                };
+            }
+        }
+
+        None
+    }
+
+    /// Returns the exact source text an element was parsed from, see `span_of`. Only available
+    /// when the document was parsed with `ParserOptions::retain_source`.
+    pub fn source_of(&self, element: &Element) -> Option<&str> {
+        let source = self.source.as_deref()?;
+        let span = self.span_of(element)?;
+
+        let byte_offset = |offset: usize| {
+            source
+                .char_indices()
+                .nth(offset)
+                .map_or(source.len(), |(i, _)| i)
+        };
+
+        Some(&source[byte_offset(span.start)..byte_offset(span.end)])
+    }
+
+    /// Pretty-prints the top-level elements with their source offsets after the element kind, as
+    /// in `Heading@0..4(...)`, for debugging spans
+    pub fn debug_with_spans(&self) -> String {
+        let mut output = String::new();
+        for (i, element) in self.elements.iter().enumerate() {
+            let debug = format!("{:#?}", element);
+            let kind_end = debug
+                .find(|char: char| !char.is_alphanumeric())
+                .unwrap_or(debug.len());
+
+            output.push_str(&debug[..kind_end]);
+            if let Some(span) = self.spans.get(i) {
+                output.push_str(&format!("@{}..{}", span.start, span.end));
+            }
+            output.push_str(&debug[kind_end..]);
+            output.push('\n');
+        }
+
+        output
+    }
+
+    pub fn front_matter(&self) -> Option<(FrontMatterKind, &str)> {
+        self.front_matter
+            .as_ref()
+            .map(|(kind, raw)| (*kind, raw.as_str()))
+    }
+
+    /// Returns the errors of the blocks left out of the document because they are malformed, along
+    /// with the list marker switches reported by `ParserOptions::strict_lists`
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+}
+
+impl<'a> IntoIterator for &'a Document {
+    type Item = &'a Element;
+    type IntoIter = std::slice::Iter<'a, Element>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.elements.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Document {
+    type Item = &'a mut Element;
+    type IntoIter = std::slice::IterMut<'a, Element>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.elements.iter_mut()
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
 pub enum Element {
     Heading(Heading),
     Paragraph(Paragraph),
     List(List),
+    CodeBlock(CodeBlock),
+    BlockQuote(Vec<Element>),
+    ThematicBreak,
+    /// An HTML comment on lines of its own, holding the text between `<!--` and `-->`
+    Comment(String),
+    /// Raw HTML starting a line, like a `<div>` block, kept as written up to the end of its last
+    /// line
+    Html(String),
+    Table(Table),
 }
 
 impl Element {
     pub fn new_heading(level: usize, tokens: Vec<InlineToken>) -> Self {
-        Element::Heading(Heading { level, tokens })
+        Element::Heading(Heading {
+            level,
+            tokens,
+            attributes: HeadingAttrs::default(),
+        })
     }
 
     pub fn new_paragraph(tokens: Vec<InlineToken>) -> Self {
@@ -104,32 +461,363 @@ impl Element {
     }
 
     pub fn new_list(kind: ListKind, items: Vec<ListItem>) -> Self {
-        Element::List(List { kind, items })
+        let start = match kind {
+            ListKind::Ordered => Some(1),
+            ListKind::Unordered => None,
+        };
+        Element::List(List {
+            kind,
+            start,
+            items,
+            checked: Vec::new(),
+            loose: false,
+        })
+    }
+
+    pub fn new_ordered_list(start: usize, items: Vec<ListItem>) -> Self {
+        Element::List(List {
+            kind: ListKind::Ordered,
+            start: Some(start),
+            items,
+            checked: Vec::new(),
+            loose: false,
+        })
+    }
+
+    pub fn new_blockquote(elements: Vec<Element>) -> Self {
+        Element::BlockQuote(elements)
+    }
+
+    pub fn new_code_block(lang: Option<&str>, code: &str) -> Self {
+        Element::CodeBlock(CodeBlock {
+            lang: lang.map(|lang| lang.to_string()),
+            code: code.to_string(),
+        })
+    }
+
+    pub fn new_table(alignments: Vec<Alignment>, header: TableRow, rows: Vec<TableRow>) -> Self {
+        Element::Table(Table {
+            alignments,
+            header,
+            rows,
+        })
+    }
+}
+
+impl From<Heading> for Element {
+    fn from(heading: Heading) -> Self {
+        Element::Heading(heading)
+    }
+}
+
+impl From<Paragraph> for Element {
+    fn from(paragraph: Paragraph) -> Self {
+        Element::Paragraph(paragraph)
+    }
+}
+
+impl From<List> for Element {
+    fn from(list: List) -> Self {
+        Element::List(list)
+    }
+}
+
+/// Returns the inline tokens of plain text, none for empty text
+fn text_tokens(text: &str) -> Vec<InlineToken> {
+    match text.is_empty() {
+        true => Vec::new(),
+        false => vec![InlineToken::new_text(text)],
+    }
+}
+
+/// A token the grammar requires is missing
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ParseError {
+    pub expected: TokenKind,
+    pub found: Token,
+    /// Source offset of the token found instead
+    pub offset: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Expected {:?}, found {:?} at offset {}",
+            self.expected,
+            self.found.kind(),
+            self.offset
+        )
     }
 }
 
+impl std::error::Error for ParseError {}
+
+/// A block the parser failed to parse and skipped, a link or an image that doesn't close and is
+/// kept as literal text, whose span runs up to where it fails, or a bullet marker switching lists
+/// in strict mode, whose span is the marker
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Diagnostic {
+    pub error: ParseError,
+    /// Source offsets (in chars) of what the diagnostic is about
+    pub span: Range<usize>,
+}
+
+/// Options controlling the syntax the parser recognizes
+#[derive(Debug, Default, Clone)]
+pub struct ParserOptions {
+    /// Captures a YAML (`---`), TOML (`+++`) or JSON (`{ ... }`) block at the very start of the
+    /// document as front matter
+    pub front_matter: bool,
+    /// Keeps a copy of the input in the document, see `Document::source_of`
+    pub retain_source: bool,
+    /// Reads a language prefix in code spans: `` `rust:let x` `` is the code `let x` in Rust
+    pub code_span_lang: bool,
+    /// Turns bare URLs and email addresses in text into links, see `autolink::autolink`
+    pub autolink: bool,
+    /// Reads an id and classes written at the end of a heading: `## Title {#id .class}`
+    pub heading_attributes: bool,
+    /// Reads a `[ ]` or `[x]` checkbox starting a list item as a task, see `List::checked`
+    pub task_lists: bool,
+    /// Turns straight quotes into curly ones and `--`, `---` and `...` into dashes and an
+    /// ellipsis, see `smart::smarten`
+    pub smart_punctuation: bool,
+    /// Reports a bullet list followed by items with another bullet marker, like `- a` then `+ b`,
+    /// as a diagnostic. The items still start a new list.
+    pub strict_lists: bool,
+}
+
+/// The state of a parser at some point of the stream, to go back to when what follows turns out
+/// not to be the construct it looked like, see `Parser::rewind`
+struct Snapshot {
+    lookahead: Option<Token>,
+    lookahead_end: Option<Checkpoint>,
+    cursor: usize,
+    start: usize,
+    last_end: usize,
+    previous: Option<Token>,
+    line_start: bool,
+}
+
 pub struct Parser<'stream> {
     tokenizer: &'stream mut Tokenizer<'stream>,
+    options: ParserOptions,
     lookahead: Option<Token>,
+    /// Stream position where the lookahead ends
+    lookahead_end: Option<Checkpoint>,
+    /// Tokens read from the tokenizer along with where they end, the one before `cursor` being the
+    /// lookahead. The consumed ones are kept until the end of the block, so that going back to a
+    /// snapshot is cheap.
+    buffer: VecDeque<(Token, Checkpoint)>,
+    /// Index in `buffer` of the token following the lookahead, see `peek`
+    cursor: usize,
+    /// Source offset where the lookahead starts
+    start: usize,
+    /// Source offset where the last consumed token that isn't whitespace ends
+    last_end: usize,
+    /// The last consumed token
+    previous: Option<Token>,
+    /// True when the lookahead token is the first token of a line
+    line_start: bool,
+    /// True while parsing a paragraph, whose inline constructs can span several lines
+    in_paragraph: bool,
+    /// Number of links and images around the content being parsed, which fail when a link inside
+    /// them does
+    link_depth: usize,
+    /// The links and images starting at these offsets that are literal text, with why they don't
+    /// close when they don't, see `parse_or_literal`
+    literal: HashMap<usize, Option<ParseError>>,
+    /// Why the destinations opened at these offsets don't close, see `parse_destination`
+    unclosed_destinations: HashMap<usize, ParseError>,
+    /// Link reference definitions by label
+    definitions: HashMap<String, LinkDefinition>,
+    /// Source offsets of the elements nested in the element being parsed, see `parse_nested`
+    descendant_spans: Vec<Range<usize>>,
+    /// Number of container blocks around the content being parsed
+    depth: usize,
+    diagnostics: Vec<Diagnostic>,
 }
 
 impl<'stream> Parser<'stream> {
     pub fn new(tokenizer: &'stream mut Tokenizer<'stream>) -> Self {
+        Self::with_options(tokenizer, ParserOptions::default())
+    }
+
+    pub fn with_options(
+        tokenizer: &'stream mut Tokenizer<'stream>,
+        options: ParserOptions,
+    ) -> Self {
         Self {
             tokenizer,
+            options,
             lookahead: None,
+            lookahead_end: None,
+            buffer: VecDeque::new(),
+            cursor: 0,
+            start: 0,
+            last_end: 0,
+            previous: None,
+            line_start: true,
+            in_paragraph: false,
+            link_depth: 0,
+            literal: HashMap::new(),
+            unclosed_destinations: HashMap::new(),
+            definitions: HashMap::new(),
+            descendant_spans: Vec::new(),
+            depth: 0,
+            diagnostics: Vec::new(),
         }
     }
 
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            lookahead: self.lookahead.clone(),
+            lookahead_end: self.lookahead_end,
+            cursor: self.cursor,
+            start: self.start,
+            last_end: self.last_end,
+            previous: self.previous.clone(),
+            line_start: self.line_start,
+        }
+    }
+
+    /// Goes back to the point of the stream where `snapshot` was taken, to parse what follows it
+    /// again
+    fn rewind(&mut self, snapshot: Snapshot) {
+        self.lookahead = snapshot.lookahead;
+        self.lookahead_end = snapshot.lookahead_end;
+        self.cursor = snapshot.cursor;
+        self.start = snapshot.start;
+        self.last_end = snapshot.last_end;
+        self.previous = snapshot.previous;
+        self.line_start = snapshot.line_start;
+    }
+
+    /// Adds link reference definitions that references can resolve to, besides the ones in the
+    /// stream. They come first, so they win over definitions of the same label in the stream.
+    pub(crate) fn set_definitions(&mut self, definitions: HashMap<String, LinkDefinition>) {
+        self.definitions = definitions;
+    }
+
+    /// Takes the link reference definitions known to the parser, see `set_definitions`
+    pub(crate) fn take_definitions(&mut self) -> HashMap<String, LinkDefinition> {
+        std::mem::take(&mut self.definitions)
+    }
+
     /// ```txt
     /// Document
-    ///     : Elements
+    ///     : FrontMatter Elements
+    ///     | Elements
     ///     ;
     /// ```
     pub fn parse(&mut self) -> Document {
-        self.lookahead = Some(self.tokenizer.consume());
+        let front_matter = match self.options.front_matter {
+            true => self.parse_front_matter(),
+            false => None,
+        };
+
+        self.start = self.tokenizer.offset();
+        self.read_lookahead();
+        self.collect_definitions();
+
+        let document = self.parse_elements_with_spans();
+        let source = match self.options.retain_source {
+            true => Some(self.tokenizer.source()),
+            false => None,
+        };
+
+        Document {
+            front_matter,
+            source,
+            diagnostics: std::mem::take(&mut self.diagnostics),
+            ..document
+        }
+    }
+
+    /// Reads the front matter block at the start of the stream, before any token is consumed
+    fn parse_front_matter(&mut self) -> Option<(FrontMatterKind, String)> {
+        if let Some(raw) = self.tokenizer.consume_front_matter("---") {
+            return Some((FrontMatterKind::Yaml, raw));
+        }
+
+        if let Some(raw) = self.tokenizer.consume_front_matter("+++") {
+            return Some((FrontMatterKind::Toml, raw));
+        }
+
+        self.tokenizer
+            .consume_json_front_matter()
+            .map(|raw| (FrontMatterKind::Json, raw))
+    }
+
+    /// Reads the whole token stream ahead and collects the link reference definitions, so that
+    /// references can be resolved to definitions that follow them in the document.
+    fn collect_definitions(&mut self) {
+        let mut index = 0;
+        let mut block_start = true;
+
+        loop {
+            let token = self.token_at(index);
+            if token.is_eof() {
+                break;
+            }
+
+            if block_start && token == Token::OpeningBracket {
+                if let Some((label, definition, count)) = self.match_definition(index) {
+                    self.definitions.entry(label).or_insert(definition);
+                    index += count;
+                    continue;
+                }
+            }
+
+            block_start =
+                token.is_blank_line() || (block_start && (is_newline(&token) || token.is_indent()));
+            index += 1;
+        }
+    }
+
+    /// Consumes the link reference definition at the lookahead, if there is one. Definitions are
+    /// metadata, they are not part of the document.
+    fn skip_definition(&mut self) -> bool {
+        if self.lookahead != Some(Token::OpeningBracket) {
+            return false;
+        }
+
+        match self.match_definition(0) {
+            Some((label, definition, count)) => {
+                self.definitions.entry(label).or_insert(definition);
+                for _ in 0..count {
+                    self.eat();
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Matches a link reference definition starting at the token at `index`, returning the label,
+    /// the definition and the number of tokens it spans.
+    fn match_definition(&mut self, index: usize) -> Option<(String, LinkDefinition, usize)> {
+        let mut source = String::new();
+        let mut ends = Vec::new();
+        let mut length = 0;
+
+        loop {
+            let token = self.token_at(index + ends.len());
+            if token.is_eof() || token.is_blank_line() {
+                break;
+            }
+
+            let text = token.to_string();
+            length += text.chars().count();
+            source.push_str(&text);
+            ends.push(length);
+        }
 
-        Document(self.parse_elements())
+        let (label, definition, length) = parse_definition(&source)?;
+        // the definition has to end on a token boundary
+        let count = ends.iter().position(|end| *end == length)? + 1;
+
+        Some((label, definition, count))
     }
 
     /// ```txt
@@ -139,13 +827,40 @@ impl<'stream> Parser<'stream> {
     ///     ;
     /// ```
     pub fn parse_elements(&mut self) -> Vec<Element> {
-        let mut elements = Vec::new();
+        self.parse_elements_with_spans().elements
+    }
+
+    /// Parses elements like `parse_elements`, along with the source offsets each one and the
+    /// elements nested in it span. Malformed elements are skipped and recorded as diagnostics.
+    fn parse_elements_with_spans(&mut self) -> Document {
+        let mut document = Document::default();
 
         loop {
-            println!("parse_elements loops");
+            // whitespace between blocks carries no meaning
+            self.consume_whitespace();
+            self.drop_consumed();
+
+            if self.skip_definition() {
+                continue;
+            }
+
             if let Some(token) = self.lookahead.clone() {
                 if !token.is_eof() {
-                    elements.push(self.parse_element())
+                    let start = self.start;
+                    self.descendant_spans.clear();
+                    match self.parse_element() {
+                        Ok(element) => {
+                            document.elements.push(element);
+                            document.spans.push(start..self.last_end);
+                            let descendants = std::mem::take(&mut self.descendant_spans);
+                            document.descendant_spans.push(descendants);
+                        }
+                        Err(error) => {
+                            self.recover_to_block_boundary();
+                            let span = start..self.last_end;
+                            self.diagnostics.push(Diagnostic { error, span });
+                        }
+                    }
                 } else {
                     break;
                 }
@@ -154,7 +869,18 @@ impl<'stream> Parser<'stream> {
             }
         }
 
-        elements
+        document
+    }
+
+    /// Skips the rest of a malformed block: everything up to the next blank line or the next line
+    /// starting another block.
+    fn recover_to_block_boundary(&mut self) {
+        while let Some(token) = self.lookahead.clone() {
+            if token.is_eof() || token.is_blank_line() || self.starts_block() {
+                break;
+            }
+            self.eat();
+        }
     }
 
     /// ```txt
@@ -162,222 +888,3565 @@ impl<'stream> Parser<'stream> {
     ///     : Heading
     ///     | Paragraph
     ///     | List
+    ///     | CodeBlock
+    ///     | BlockQuote
+    ///     | ThematicBreak
+    ///     | <comment-token>
+    ///     | HtmlBlock
     ///     ;
     /// ```
-    pub fn parse_element(&mut self) -> Element {
+    pub fn parse_element(&mut self) -> Result<Element, ParseError> {
         if let Some(token) = self.lookahead.clone() {
-            if token.is_hash() {
-                return Element::Heading(self.parse_heading());
+            if let Some(Token::Indent(indent @ 4..)) = self.previous {
+                if self.line_start && !token.is_eof() {
+                    return Ok(Element::CodeBlock(self.parse_indented_code_block(indent)));
+                }
             }
-        }
 
-        todo!()
-    }
+            if self.line_start && matches!(token, Token::Backticks(3..) | Token::Tilde(3..)) {
+                return Ok(Element::CodeBlock(self.parse_code_block()));
+            }
 
-    /// ```txt
-    /// Heading
-    ///     : <#-token> InlineTokens
-    ///     ;
-    /// ```
-    pub fn parse_heading(&mut self) -> Heading {
-        // consuem <#-token>
-        let level = self.eat().to_string().len();
-        let tokens = self.parse_inline_tokens();
+            if let Token::Comment(comment) = &token {
+                if self.is_block_comment() {
+                    // consume <comment-token>
+                    self.eat();
+                    return Ok(Element::Comment(comment.clone()));
+                }
+            }
 
-        Heading { level, tokens }
-    }
+            if let Some(end) = self.html_block_start(false) {
+                return Ok(Element::Html(self.parse_html_block(end)));
+            }
 
-    /// ```txt
-    /// List
-    ///     : ListItem ...
-    ///     ;
-    /// ```
-    pub fn parse_list(&mut self) -> List {
-        let mut items = Vec::new();
-        let mut kind = ListKind::Unordered;
+            if let Some(length) = self.thematic_break_length() {
+                // consume the <---tokens> and the spaces between them
+                for _ in 0..length {
+                    self.eat();
+                }
+                return Ok(Element::ThematicBreak);
+            }
 
-        List { kind, items }
-    }
+            if self.is_atx_heading() {
+                return Ok(Element::Heading(self.parse_heading()?));
+            }
 
-    pub fn parse_ordered_list(&mut self) {}
+            if self.opens_blockquote() {
+                return Ok(Element::BlockQuote(self.parse_blockquote()));
+            }
 
-    pub fn parse_unordered_list(&mut self) {}
+            if self.list_marker().is_some() {
+                return Ok(Element::List(self.parse_list()));
+            }
+        }
 
-    /// ```txt
-    /// ListItem
-    ///     : <dash-token> Elements
-    ///     ;
-    /// ```
-    pub fn parse_list_item(&mut self) -> ListItem {
-        // consuem <dash-token>
-        self.eat();
-        self.parse_elements()
+        let paragraph = self.parse_paragraph()?;
+        match self.setext_level() {
+            Some(level) if !paragraph.0.is_empty() => Ok(Element::Heading(
+                self.parse_setext_heading(paragraph, level),
+            )),
+            _ => Ok(Element::Paragraph(paragraph)),
+        }
     }
 
     /// ```txt
-    /// InlineTokens
-    ///     : InlineToken
-    ///     | InlineTokens InlineToken -> InlineToken InlineToken InlineToken ...
+    /// Paragraph
+    ///     : InlineTokens
+    ///     | Paragraph <newline> InlineTokens
     ///     ;
     /// ```
-    pub fn parse_inline_tokens(&mut self) -> Vec<InlineToken> {
-        let mut tokens = Vec::new();
+    ///
+    /// A paragraph runs up to a blank line or a line starting another block. Its lines are joined
+    /// by a newline, without their indentation, or by a hard line break when the line ends with
+    /// two spaces or more or with a backslash. The last line can't end with a hard break. The
+    /// inline content of the lines is one, emphasis and code spans can span several of them.
+    pub fn parse_paragraph(&mut self) -> Result<Paragraph, ParseError> {
+        let mut stack = DelimiterStack::default();
+        self.in_paragraph = true;
 
         loop {
-            println!("parse_inline_tokens loops");
-            if let Some(token) = self.lookahead.clone() {
-                if !token.is_eof() {
-                    tokens.push(self.parse_inline_token())
-                } else {
-                    break;
-                }
-            } else {
+            self.parse_line_into(&mut stack)?;
+
+            if !self.parse_line_end(stack.tokens()) {
                 break;
             }
         }
+        self.in_paragraph = false;
 
-        tokens
+        Ok(Paragraph(self.transform_inline(stack.finish())))
     }
 
-    /// ```txt
-    /// InlineTokens
-    ///     : Text
-    ///     | Link
-    ///     | Bold
-    ///     | Italic
-    ///     | Code
-    ///     | Image
+    /// Consumes the end of a line when the next line continues the paragraph, pushing the soft or
+    /// hard line break it stands for. Returns false when the paragraph ends there, or when the
+    /// lookahead doesn't end a line.
+    fn parse_line_end(&mut self, tokens: &mut Vec<InlineToken>) -> bool {
+        let newline = match self.lookahead.clone() {
+            Some(token) if is_newline(&token) && !token.is_blank_line() => token,
+            _ => return false,
+        };
+        // an escaped backslash is a token of its own, a backslash ending a line isn't
+        let backslash = matches!(&self.previous, Some(Token::String(s)) if s.ends_with('\\'));
+        let spaces = newline
+            .to_string()
+            .trim_end_matches(['\n', '\r'])
+            .ends_with("  ");
+
+        // consume <newline-token>
+        self.eat();
+        if self
+            .lookahead
+            .as_ref()
+            .is_some_and(|token| token.is_indent())
+        {
+            self.eat();
+        }
+
+        // an underline makes the paragraph a heading, and takes precedence over thematic breaks
+        if self.token_at(0).is_eof() || self.setext_level().is_some() || self.starts_block() {
+            return false;
+        }
+        match (backslash && take_backslash(tokens)) || spaces {
+            true => tokens.push(InlineToken::LineBreak),
+            false => push_text(tokens, "\n"),
+        }
+
+        true
+    }
+
+    /// Returns true when the lookahead starts a line opening a heading, a list, a code block, a
+    /// block quote, a thematic break, an HTML comment or another HTML block.
+    fn starts_block(&mut self) -> bool {
+        if !self.line_start {
+            return false;
+        }
+
+        let opens = matches!(
+            self.lookahead,
+            Some(Token::Backticks(3..)) | Some(Token::Tilde(3..))
+        );
+
+        // only an ordered list starting at 1 can interrupt a paragraph
+        let opens_list = match self.list_marker() {
+            Some('.' | ')') => list_number(&self.token_at(0)) == Some(1),
+            marker => marker.is_some(),
+        };
+
+        opens
+            || self.opens_blockquote()
+            || self.is_atx_heading()
+            || self.is_thematic_break()
+            || self.is_block_comment()
+            || self.html_block_start(true).is_some()
+            || opens_list
+    }
+
+    /// Returns true when the lookahead is an HTML comment with nothing else on its lines. A comment
+    /// sharing a line with other content is inline.
+    fn is_block_comment(&mut self) -> bool {
+        let next = self.peek();
+        self.line_start
+            && self.token_at(0).kind() == TokenKind::Comment
+            && (next.is_eof() || is_newline(&next))
+    }
+
+    /// ```txt
+    /// Heading
+    ///     : <#-token> <whitespace-token> InlineTokens
     ///     ;
     /// ```
-    pub fn parse_inline_token(&mut self) -> InlineToken {
-        if let Some(token) = self.lookahead.clone() {
-            println!("parse_inline_token: {:?}", token);
-            return match token {
-                Token::ExclamationMark => todo!(),                    // image
-                Token::Backticks(1) => todo!(),                       // code
-                Token::Asterisk(1) | Token::Underscore(1) => todo!(), // italic
-                Token::Asterisk(2) => todo!(),                        // bold
-                Token::OpeningBracket => InlineToken::Link(self.parse_link()),
-                Token::String(_) | Token::Whitespace(_) => InlineToken::Text(self.parse_text()),
-                _ => todo!(),
-            };
+    ///
+    /// The level is the number of `#`, from 1 to 6. A longer run of `#` is literal text.
+    pub fn parse_heading(&mut self) -> Result<Heading, ParseError> {
+        // consuem <#-token>
+        let level = self.eat().to_string().len();
+        self.skip_marker_space();
+        let mut tokens = self.parse_inline_tokens()?;
+        // the spacing around the content isn't part of it, `Document::source_of` keeps the original
+        trim_inline(&mut tokens);
+
+        let attributes = match self.options.heading_attributes {
+            true => take_heading_attributes(&mut tokens),
+            false => HeadingAttrs::default(),
+        };
+
+        Ok(Heading {
+            level,
+            tokens,
+            attributes,
+        })
+    }
+
+    /// ```txt
+    /// SetextHeading
+    ///     : Paragraph <newline> <=-token>
+    ///     | Paragraph <newline> <--token>
+    ///     ;
+    /// ```
+    ///
+    /// The lines of the paragraph, which has been parsed already, are the content of the heading.
+    /// Consumes the underline at the lookahead, see `setext_level`.
+    pub fn parse_setext_heading(&mut self, paragraph: Paragraph, level: usize) -> Heading {
+        while let Some(token) = self.lookahead.clone() {
+            if token.is_eof() || is_newline(&token) {
+                break;
+            }
+            self.eat();
         }
 
-        todo!()
+        let mut tokens = paragraph.0;
+        trim_inline(&mut tokens);
+        let attributes = match self.options.heading_attributes {
+            true => take_heading_attributes(&mut tokens),
+            false => HeadingAttrs::default(),
+        };
+
+        Heading {
+            level,
+            tokens,
+            attributes,
+        }
     }
 
     /// ```txt
-    /// Text
-    ///   : <string-token> ...
-    ///   ;
+    /// CodeBlock
+    ///     : <```-token> InfoString <newline> Code <```-token>
+    ///     | <~~~-token> InfoString <newline> Code <~~~-token>
+    ///     ;
     /// ```
-    pub fn parse_text(&mut self) -> String {
-        let mut text = String::new();
+    ///
+    /// The closing fence is a run of at least as many of the same fence characters as the opening
+    /// one on a line of its own, indented by up to three spaces. Without one the code block runs to
+    /// the end of the document.
+    pub fn parse_code_block(&mut self) -> CodeBlock {
+        // consume <```-token>
+        let fence = self.eat();
+
+        let mut info = String::new();
+        while let Some(token) = self.lookahead.clone() {
+            if token.is_eof() || is_newline(&token) {
+                break;
+            }
+            info.push_str(&self.eat().to_string());
+        }
+        let lang = info.split_whitespace().next().map(|lang| lang.to_string());
+
+        // the line ending after the info string isn't part of the code
+        let mut code = match self.lookahead.clone() {
+            Some(Token::Whitespace(s)) => {
+                self.eat();
+                s.split_once('\n')
+                    .map(|(_, rest)| rest.to_string())
+                    .unwrap_or_default()
+            }
+            _ => String::new(),
+        };
+
+        while let Some(token) = self.lookahead.clone() {
+            if token.is_eof() || self.is_closing_fence(&fence) {
+                break;
+            }
+            code.push_str(&self.eat().to_string());
+        }
+
+        // consume the closing fence, along with its indentation
+        if self
+            .lookahead
+            .as_ref()
+            .is_some_and(|token| token.is_indent())
+        {
+            self.eat();
+        }
+        if self
+            .lookahead
+            .as_ref()
+            .is_some_and(|token| closes_fence(token, &fence))
+        {
+            self.eat();
+        }
+
+        CodeBlock { lang, code }
+    }
+
+    /// ```txt
+    /// IndentedCodeBlock
+    ///     : <indent-token> Code
+    ///     | IndentedCodeBlock <newline> <indent-token> Code
+    ///     ;
+    /// ```
+    ///
+    /// A run of lines indented by four columns or more, `indent` being the width of the first one,
+    /// whose indentation has already been consumed. Tabs in the indentation count up to the next
+    /// multiple of four columns. The first four columns are stripped and the rest is kept as
+    /// spaces, while tabs after the indentation are kept as they are. Blank lines between the
+    /// lines belong to the code, blank lines after them don't.
+    pub fn parse_indented_code_block(&mut self, indent: usize) -> CodeBlock {
+        let mut code = String::new();
+        let mut indent = indent;
 
         loop {
-            println!("parse_text");
-            if let Some(token) = self.lookahead.clone() {
-                if token.is_whitespace() {
-                    text.push_str(&self.eat().to_string());
-                    continue;
+            code.push_str(&" ".repeat(indent - 4));
+            while let Some(token) = self.lookahead.clone() {
+                if token.is_eof() || is_newline(&token) {
+                    break;
                 }
+                code.push_str(&self.eat().to_string());
+            }
+            code.push('\n');
 
-                if token.is_string() {
-                    text.push_str(&self.eat().to_string());
-                    continue;
+            match (self.lookahead.clone(), self.peek()) {
+                (Some(newline), Token::Indent(width)) if is_newline(&newline) && width >= 4 => {
+                    // consume <newline-token> <indent-token>
+                    self.eat();
+                    self.eat();
+                    code.push_str(&"\n".repeat(newline.newline_count() - 1));
+                    indent = width;
                 }
+                _ => break,
+            }
+        }
 
-                if token.is_eof() {
+        CodeBlock { lang: None, code }
+    }
+
+    /// ```txt
+    /// HtmlBlock
+    ///     : Line
+    ///     | HtmlBlock <newline> Line
+    ///     ;
+    /// ```
+    ///
+    /// The lines are kept as written, up to the line meeting `end` or the end of the document,
+    /// without the line ending after them.
+    fn parse_html_block(&mut self, end: HtmlBlockEnd) -> String {
+        let mut html = String::new();
+
+        loop {
+            let mut line = String::new();
+            while let Some(token) = self.lookahead.clone() {
+                if token.is_eof() || is_newline(&token) {
                     break;
                 }
+                line.push_str(&self.eat().to_string());
+            }
+            html.push_str(&line);
+
+            let line = line.to_ascii_lowercase();
+            let closed = match end {
+                HtmlBlockEnd::Marker(marker) => line.contains(marker),
+                HtmlBlockEnd::RawTag => RAW_TAGS
+                    .iter()
+                    .any(|tag| line.contains(&format!("</{}>", tag))),
+                HtmlBlockEnd::BlankLine => false,
+            };
+            match self.lookahead.clone() {
+                Some(token)
+                    if is_newline(&token)
+                        && !closed
+                        && !(end == HtmlBlockEnd::BlankLine && token.is_blank_line()) =>
+                {
+                    // consume <newline-token>, blank lines included
+                    html.push_str(&self.eat().to_string());
+                }
+                _ => break,
+            }
+        }
+        // a line ending before the end of the document isn't part of the last line
+        html.truncate(html.trim_end().len());
+
+        html
+    }
+
+    /// Returns how the HTML block the lookahead opens ends, when it starts a line opening one,
+    /// see `html_block_end`
+    fn html_block_start(&mut self, interrupting: bool) -> Option<HtmlBlockEnd> {
+        if !self.line_start
+            || !matches!(
+                self.lookahead,
+                Some(Token::OpeningAngleBracket) | Some(Token::Comment(_))
+            )
+        {
+            return None;
+        }
 
+        let mut line = String::new();
+        for index in 0.. {
+            let token = self.token_at(index);
+            if token.is_eof() || is_newline(&token) {
                 break;
-            } else {
+            }
+            line.push_str(&token.to_string());
+        }
+
+        html_block_end(&line, interrupting)
+    }
+
+    /// ```txt
+    /// BlockQuote
+    ///     : <>-token> Line
+    ///     | BlockQuote <>-token> Line
+    ///     | BlockQuote Line       -> lazy continuation
+    ///     ;
+    /// ```
+    ///
+    /// The lines of the quote are collected without their `>` marker and the single space
+    /// following it, then parsed as a document of their own. A line without a marker still belongs
+    /// to the quote when it continues a paragraph.
+    pub fn parse_blockquote(&mut self) -> Vec<Element> {
+        let mut content = String::new();
+        // where each piece of the content taken as is starts in the content and in the source
+        let mut lines = vec![(0, self.start)];
+        let mut paragraph = false;
+
+        loop {
+            let lazy = self.lookahead != Some(Token::AngleBracket);
+            if !lazy {
+                // consume <>-token>
+                self.eat();
+                if let Some(Token::Whitespace(space)) = self.lookahead.clone() {
+                    if !space.contains('\n') {
+                        // one space or tab after the marker is part of it
+                        let rest = space.strip_prefix([' ', '\t']).unwrap_or(&space);
+                        let marker = space.chars().count() - rest.chars().count();
+                        lines.push((content.chars().count(), self.start + marker));
+                        self.eat();
+                        content.push_str(rest);
+                    }
+                }
+            } else if !paragraph || self.starts_block() {
                 break;
             }
+
+            let mut line = String::new();
+            let start = self.start;
+            while let Some(token) = self.lookahead.clone() {
+                if token.is_eof() || is_newline(&token) {
+                    break;
+                }
+                line.push_str(&self.eat().to_string());
+            }
+            paragraph = !line.trim().is_empty()
+                && !opens_unlazy_block(&line)
+                && html_block_end(line.trim_start(), false).is_none();
+            // a lazy line continues the paragraph as text, it can't underline it
+            if lazy && is_setext_underline(&line) {
+                content.push('\\');
+            }
+            lines.push((content.chars().count(), start));
+            content.push_str(&line);
+
+            match self.lookahead.clone() {
+                Some(token) if is_newline(&token) && !token.is_blank_line() => {
+                    // consume <newline-token>, along with the spaces that may make a hard break
+                    self.eat();
+                    content.push_str(&token.to_string());
+                }
+                _ => break,
+            }
+
+            // the marker may be indented by up to three spaces, lazy lines by any amount
+            if let Some(Token::Indent(width)) = self.lookahead {
+                if width <= 3 || self.peek() != Token::AngleBracket {
+                    self.eat();
+                }
+            }
         }
 
-        text
+        self.parse_nested(&content, |offset| source_offset(&lines, offset))
+            .elements
+    }
+
+    /// Parses the content of a container block as a document of its own. Its diagnostics and the
+    /// spans of its elements are recorded along with the ones of the container, at the source
+    /// offsets `offset` maps their offsets in `content` to.
+    fn parse_nested(&mut self, content: &str, offset: impl Fn(usize) -> usize) -> Document {
+        let mut chars = CharIterator::new();
+        chars.read_from_str(content, Some(Encoding::UTF8));
+        let mut tokenizer = Tokenizer::new(&mut chars);
+        let options = ParserOptions {
+            front_matter: false,
+            retain_source: false,
+            ..self.options.clone()
+        };
+        let mut parser = Parser::with_options(&mut tokenizer, options);
+        parser.definitions = self.definitions.clone();
+        parser.depth = self.depth + 1;
+
+        let mut document = parser.parse();
+        for diagnostic in std::mem::take(&mut document.diagnostics) {
+            let error = ParseError {
+                offset: offset(diagnostic.error.offset),
+                ..diagnostic.error
+            };
+            let span = offset(diagnostic.span.start)..offset(diagnostic.span.end);
+            self.diagnostics.push(Diagnostic { error, span });
+        }
+
+        // an element ends at a line end, which is mapped from its last char so that the span
+        // doesn't take in the markers of the next line
+        let span = |span: &Range<usize>| match span.is_empty() {
+            true => offset(span.start)..offset(span.start),
+            false => offset(span.start)..offset(span.end - 1) + 1,
+        };
+        for (top, descendants) in document.spans.iter().zip(&document.descendant_spans) {
+            self.descendant_spans.push(span(top));
+            self.descendant_spans.extend(descendants.iter().map(span));
+        }
+
+        document
+    }
+
+    /// Returns true when the lookahead starts a line closing a code block opened by `fence`
+    fn is_closing_fence(&mut self, fence: &Token) -> bool {
+        if !self.line_start {
+            return false;
+        }
+
+        let mut index = 0;
+        if let Some(Token::Indent(width)) = self.lookahead {
+            if width > 3 {
+                return false;
+            }
+            index += 1;
+        }
+
+        let closes = closes_fence(&self.token_at(index), fence);
+        let next = self.token_at(index + 1);
+
+        closes && (next.is_eof() || is_newline(&next))
     }
 
     /// ```txt
-    /// Link
-    ///   : <[-token> InlineTokens <]-token> <(-token> Text  <)-token>
-    ///   ;
+    /// List
+    ///     : ListItem ...
+    ///     ;
     /// ```
-    pub fn parse_link(&mut self) -> Link {
-        // todo: error handling
+    ///
+    /// All items of a list share the same bullet marker, or the same delimiter after their number
+    /// for an ordered list. A different marker starts a new list.
+    pub fn parse_list(&mut self) -> List {
+        let mut items = Vec::new();
+        let marker = self.list_marker();
+        let (kind, start) = match marker {
+            Some('.' | ')') => (ListKind::Ordered, list_number(&self.token_at(0))),
+            _ => (ListKind::Unordered, None),
+        };
+
+        let mut checked = Vec::new();
+        let mut loose = false;
+        loop {
+            let (task, item, spaced) = self.parse_task_list_item();
+            items.push(item);
+            checked.push(task);
+            loose |= spaced;
+            // the line ending the item, blank lines included, is left for the list
+            let blank = self
+                .lookahead
+                .as_ref()
+                .is_some_and(|token| token.is_blank_line());
+            self.consume_whitespace();
+
+            if self.list_marker() != marker {
+                break;
+            }
+            loose |= blank;
+        }
+        if self.options.strict_lists && kind == ListKind::Unordered {
+            self.report_bullet_switch(marker);
+        }
+        if checked.iter().all(Option::is_none) {
+            checked.clear();
+        }
+
+        List {
+            kind,
+            start,
+            items,
+            checked,
+            loose,
+        }
+    }
+
+    /// Records a diagnostic when the lookahead is a bullet other than `bullet`, which ended the
+    /// list before it
+    fn report_bullet_switch(&mut self, bullet: Option<char>) {
+        let expected = match bullet {
+            Some('-') => TokenKind::Dash,
+            Some('*') => TokenKind::Asterisk,
+            _ => TokenKind::Plus,
+        };
+        if let Some('-' | '*' | '+') = self.list_marker() {
+            let error = ParseError {
+                expected,
+                found: self.token_at(0),
+                offset: self.start,
+            };
+            let span = self.start..self.start + 1;
+            self.diagnostics.push(Diagnostic { error, span });
+        }
+    }
 
-        // consume <[-token>
-        self.tokenizer.consume();
+    /// ```txt
+    /// ListItem
+    ///     : <marker-token> <whitespace-token> Line
+    ///     | <number-token> <)-token> <whitespace-token> Line
+    ///     | ListItem <indent-token> Line
+    ///     ;
+    /// ```
+    ///
+    /// The content of an item starts at its content column: the column of the marker plus the
+    /// width of the marker and of up to four spaces following it. The lines indented to that column
+    /// or further, blank lines between them included, continue the item and are parsed as a
+    /// document of their own without that indentation. A line indented less ends the item.
+    pub fn parse_list_item(&mut self) -> ListItem {
+        self.parse_task_list_item().1
+    }
 
-        let tokens = self.parse_inline_tokens();
+    /// Parses a list item along with whether it is a checked task, see `parse_task_marker`, and
+    /// whether a blank line separates two of its blocks
+    fn parse_task_list_item(&mut self) -> (Option<bool>, ListItem, bool) {
+        let indent = match &self.previous {
+            Some(Token::Indent(width)) if self.line_start => *width,
+            _ => 0,
+        };
 
-        // consume <]-token>
-        self.tokenizer.consume();
+        // consume <marker-token>
+        let marker = self.eat();
+        let mut width = marker.to_string().chars().count();
+        if marker.is_string() && self.lookahead == Some(Token::ClosingParenthesis) {
+            self.eat();
+            width += 1;
+        }
 
-        // consume <(-token>
-        self.tokenizer.consume();
+        // with five spaces or more the content starts after the first one
+        let spaces = match &self.lookahead {
+            Some(Token::Whitespace(space)) if !space.contains('\n') => space.chars().count(),
+            _ => 0,
+        };
+        let column = indent + width + if (1..=4).contains(&spaces) { spaces } else { 1 };
 
-        let href = self.parse_text();
+        self.skip_marker_space();
+        let checked = match self.options.task_lists {
+            true => self.parse_task_marker(),
+            false => None,
+        };
 
-        // consume <)-token>
-        self.tokenizer.consume();
+        let (content, lines) = self.list_item_content(column);
+        let item = self.parse_nested(&content, |offset| source_offset(&lines, offset));
+        let content = content.chars().collect::<Vec<_>>();
+        let spaced = item.spans.windows(2).any(|pair| {
+            let between = &content[pair[0].end..pair[1].start];
+            between.iter().filter(|char| **char == '\n').count() >= 2
+        });
 
-        Link { tokens, href }
+        (checked, item.elements, spaced)
     }
 
-    pub fn eat(&mut self) -> Token {
-        if let Some(token) = self.lookahead.clone() {
-            self.lookahead = Some(self.tokenizer.consume());
-            return token;
+    /// Collects the rest of the line at the lookahead, and the following lines indented to
+    /// `column` or further with that much of their indentation removed. Also returns where each
+    /// line starts in the content and in the source, both in chars.
+    fn list_item_content(&mut self, column: usize) -> (String, Vec<(usize, usize)>) {
+        let mut content = String::new();
+        let mut lines = vec![(0, self.start)];
+        loop {
+            while let Some(token) = self.lookahead.clone() {
+                if token.is_eof() || is_newline(&token) {
+                    break;
+                }
+                content.push_str(&self.eat().to_string());
+            }
+
+            match (self.token_at(0), self.peek()) {
+                (newline, Token::Indent(width)) if is_newline(&newline) && width >= column => {
+                    // consume <newline-token> <indent-token>
+                    self.eat();
+                    self.eat();
+                    content.push_str(&newline.to_string());
+                    content.push_str(&" ".repeat(width - column));
+                    lines.push((content.chars().count(), self.start));
+                }
+                (newline, _) => {
+                    // the line ending the item is left for the list
+                    if is_newline(&newline) {
+                        content.push('\n');
+                    }
+                    break;
+                }
+            }
         }
 
-        todo!()
+        (content, lines)
     }
 
-    // todo: remove
-    pub fn consume_whitespace(&mut self) {
-        if let Some(token) = self.lookahead.clone() {
-            if token.is_whitespace() {
-                self.eat();
+    /// ```txt
+    /// TaskMarker
+    ///     : <[-token> <whitespace-token> <]-token> <whitespace-token>
+    ///     | <[-token> <x-token> <]-token> <whitespace-token>
+    ///     ;
+    /// ```
+    ///
+    /// Consumes the checkbox starting a task list item and returns whether it is checked. The
+    /// checkbox is `[ ]`, or `[x]` or `[X]` when checked, followed by whitespace on the same line.
+    fn parse_task_marker(&mut self) -> Option<bool> {
+        if self.lookahead != Some(Token::OpeningBracket)
+            || self.token_at(2) != Token::ClosingBracket
+        {
+            return None;
+        }
+        let checked = match self.token_at(1) {
+            Token::Whitespace(space) if space == " " => false,
+            Token::String(x) if x == "x" || x == "X" => true,
+            _ => return None,
+        };
+        let after = self.token_at(3);
+        if !after.is_whitespace() || is_newline(&after) {
+            return None;
+        }
+
+        // consume <[-token> <x-token> <]-token>
+        for _ in 0..3 {
+            self.eat();
+        }
+        self.skip_marker_space();
+        Some(checked)
+    }
+
+    /// Consumes the whitespace separating a heading or list marker from the content on its line
+    fn skip_marker_space(&mut self) {
+        if matches!(&self.lookahead, Some(token) if token.is_whitespace() && !is_newline(token)) {
+            self.eat();
+        }
+    }
+
+    /// Returns true when the lookahead is a run of one to six `#` followed by whitespace or the end
+    /// of the input, which opens a heading. `#hashtag` and `#5` stay text.
+    fn is_atx_heading(&mut self) -> bool {
+        matches!(self.lookahead, Some(Token::Hash(1..=6))) && {
+            let next = self.token_at(1);
+            next.is_whitespace() || next.is_eof()
+        }
+    }
+
+    /// Returns true when the lookahead starts a thematic break, see `thematic_break_length`
+    fn is_thematic_break(&mut self) -> bool {
+        self.thematic_break_length().is_some()
+    }
+
+    /// Returns the number of tokens making up the thematic break at the lookahead: a line of
+    /// nothing but three or more of the same `-`, `*` or `_`, which may be separated by spaces. The
+    /// spaces after the last marker aren't counted.
+    fn thematic_break_length(&mut self) -> Option<usize> {
+        if !self.line_start {
+            return None;
+        }
+
+        let marker = self.token_at(0);
+        if !matches!(
+            marker,
+            Token::Dash(_) | Token::Asterisk(_) | Token::Underscore(_)
+        ) {
+            return None;
+        }
+
+        let mut count = 0;
+        let mut length = 0;
+        let mut index = 0;
+        loop {
+            let token = self.token_at(index);
+            match token {
+                Token::Dash(n) | Token::Asterisk(n) | Token::Underscore(n)
+                    if token.kind() == marker.kind() =>
+                {
+                    count += n;
+                    length = index + 1;
+                }
+                Token::Whitespace(_) if !is_newline(&token) => {}
+                _ if token.is_eof() || is_newline(&token) => break,
+                _ => return None,
             }
+            index += 1;
         }
+
+        (count >= 3).then_some(length)
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
+    /// Returns the level of the setext heading underlined at the lookahead: a line of nothing but
+    /// `=` for level 1 or `-` for level 2, indented by up to three spaces and possibly followed by
+    /// spaces. Only the line after a paragraph is an underline.
+    fn setext_level(&mut self) -> Option<usize> {
+        if !self.line_start || matches!(self.previous, Some(Token::Indent(4..))) {
+            return None;
+        }
 
-    macro_rules! assert_ast {
-        ($raw:expr, $doc_ast:expr) => {
-            let mut chars = CharIterator::new();
-            chars.read_from_str($raw, Some(Encoding::UTF8));
+        let level = match self.token_at(0) {
+            Token::String(s) if s.bytes().all(|byte| byte == b'=') => 1,
+            Token::Dash(_) => 2,
+            _ => return None,
+        };
+        let mut end = self.token_at(1);
+        if end.is_whitespace() && !is_newline(&end) {
+            end = self.token_at(2);
+        }
 
-            let mut tokenizer = Tokenizer::new(&mut chars);
-            let mut parser = Parser::new(&mut tokenizer);
+        (end.is_eof() || is_newline(&end)).then_some(level)
+    }
 
-            assert_eq!(parser.parse(), $doc_ast);
+    /// Returns true when the lookahead is a `>` at the start of a line, which opens a block quote
+    /// unless the content is nested too deep already, see `MAX_NESTING`
+    fn opens_blockquote(&self) -> bool {
+        self.line_start && self.lookahead == Some(Token::AngleBracket) && self.depth < MAX_NESTING
+    }
+
+    /// Returns the list marker at the lookahead: a `-`, `+` or `*` bullet, or the `.` or `)`
+    /// following the number of an ordered list item. Markers are at the start of a line and
+    /// followed by whitespace. Past `MAX_NESTING` there are no more markers.
+    fn list_marker(&mut self) -> Option<char> {
+        if !self.line_start || self.depth >= MAX_NESTING {
+            return None;
+        }
+
+        let (marker, length) = match self.lookahead.clone()? {
+            Token::Dash(1) => ('-', 1),
+            Token::Plus => ('+', 1),
+            Token::Asterisk(1) => ('*', 1),
+            Token::OrderedMarker { delim, .. } => (delim.char(), 1),
+            // numbers with leading zeros aren't tokenized as markers
+            Token::String(number) => match number.strip_suffix('.') {
+                Some(number) if is_list_number(number) => ('.', 1),
+                None if is_list_number(&number) && self.peek() == Token::ClosingParenthesis => {
+                    (')', 2)
+                }
+                _ => return None,
+            },
+            _ => return None,
         };
+
+        self.token_at(length).is_whitespace().then_some(marker)
     }
 
-    #[test]
-    fn parse_heading() {
-        let tests = vec![
-            ("# h1", 1, " h1"),
-            ("## h2", 2, " h2"),
-            ("### h3", 3, " h3"),
-            ("#### I am heading", 4, " I am heading"),
-        ];
-        for (raw, level, text) in tests {
-            assert_ast!(
-                raw,
-                Document::new(vec![Element::new_heading(
-                    level,
-                    vec![InlineToken::new_text(text)]
-                )])
-            );
+    /// Parses the whole stream as inline content, for snippets like a table cell that can't hold
+    /// blocks. Lines are joined by a newline without their indentation, and a construct that
+    /// fails to parse is kept as literal text.
+    pub fn parse_inline(&mut self) -> Vec<InlineToken> {
+        self.start = self.tokenizer.offset();
+        self.read_lookahead();
+
+        let mut stack = DelimiterStack::default();
+        while let Some(token) = self.lookahead.clone() {
+            if token.is_eof() {
+                break;
+            }
+            if is_newline(&token) || token.is_indent() {
+                self.eat();
+                if is_newline(&token) && !self.token_at(0).is_eof() {
+                    push_text(stack.tokens(), "\n");
+                }
+                continue;
+            }
+
+            let start = self.start;
+            if self.parse_inline_into(&mut stack).is_err() {
+                let source = self.tokenizer.source();
+                let text = source.chars().skip(start).take(self.start - start);
+                push_text(stack.tokens(), &text.collect::<String>());
+            }
         }
+
+        self.transform_inline(stack.finish())
+    }
+
+    /// ```txt
+    /// InlineTokens
+    ///     : InlineToken
+    ///     | InlineTokens InlineToken -> InlineToken InlineToken InlineToken ...
+    ///     ;
+    /// ```
+    ///
+    /// Inline content ends with its line, blocks spanning several lines parse each of them and
+    /// decide whether the next line still belongs to them, see `starts_block`. A paragraph parses
+    /// the inline content of all its lines as one, see `parse_paragraph`.
+    pub fn parse_inline_tokens(&mut self) -> Result<Vec<InlineToken>, ParseError> {
+        let mut stack = DelimiterStack::default();
+        self.parse_line_into(&mut stack)?;
+
+        Ok(self.transform_inline(stack.finish()))
+    }
+
+    /// Parses the inline content up to the end of the line into `stack`
+    fn parse_line_into(&mut self, stack: &mut DelimiterStack) -> Result<(), ParseError> {
+        while let Some(token) = self.lookahead.clone() {
+            if token.is_eof() || is_newline(&token) {
+                break;
+            }
+
+            self.parse_inline_into(stack)?;
+        }
+
+        Ok(())
+    }
+
+    /// Parses the inline token at the lookahead into `stack`, where runs of `*` and `_` are matched
+    /// with the ones before them, see `parse_delimiter_run`
+    fn parse_inline_into(&mut self, stack: &mut DelimiterStack) -> Result<(), ParseError> {
+        match self.parse_delimiter_run() {
+            Some(delimiter) => stack.push_delimiter(delimiter),
+            None => stack.push(self.parse_inline_token()?),
+        }
+
+        Ok(())
+    }
+
+    /// Applies the optional passes over parsed inline tokens. Smart punctuation comes last so that
+    /// the URLs found by autolinking are left as written.
+    fn transform_inline(&self, tokens: Vec<InlineToken>) -> Vec<InlineToken> {
+        let tokens = match self.options.autolink {
+            true => autolink(tokens),
+            false => tokens,
+        };
+        match self.options.smart_punctuation {
+            true => smarten(tokens),
+            false => tokens,
+        }
+    }
+
+    /// ```txt
+    /// InlineTokens
+    ///     : Text
+    ///     | Link
+    ///     | Bold
+    ///     | Italic
+    ///     | Code
+    ///     | Image
+    ///     | <comment-token>
+    ///     ;
+    /// ```
+    pub fn parse_inline_token(&mut self) -> Result<InlineToken, ParseError> {
+        if let Some(token) = self.lookahead.clone() {
+            return match token {
+                Token::ExclamationMark if self.peek() == Token::OpeningBracket => {
+                    self.parse_or_literal(Self::parse_image)
+                }
+                Token::ExclamationMark => Ok(InlineToken::Text(self.eat().to_string())),
+                Token::Backticks(_) => Ok(self.parse_code_span()),
+                Token::Tilde(_)
+                | Token::Asterisk(_)
+                | Token::Underscore(_)
+                | Token::Dash(_)
+                | Token::Plus
+                | Token::OrderedMarker { .. }
+                | Token::Hash(_)
+                | Token::OpeningAngleBracket
+                | Token::AngleBracket
+                | Token::ClosingBracket
+                | Token::OpeningParenthesis
+                | Token::ClosingParenthesis => Ok(InlineToken::Text(self.eat().to_string())),
+                Token::OpeningBracket => self.parse_or_literal(Self::parse_link),
+                Token::Comment(comment) => {
+                    self.eat();
+                    Ok(InlineToken::Comment(comment))
+                }
+                Token::String(_) | Token::Escaped(_) | Token::Whitespace(_) => {
+                    Ok(InlineToken::Text(self.parse_text()))
+                }
+                // only the end of the stream holds no inline content
+                Token::EOF => Err(ParseError {
+                    expected: TokenKind::String,
+                    found: token,
+                    offset: self.start,
+                }),
+                Token::Url(_) | Token::Indent(_) => Ok(InlineToken::Text(self.eat().to_string())),
+            };
+        }
+
+        Err(ParseError {
+            expected: TokenKind::String,
+            found: Token::EOF,
+            offset: self.start,
+        })
+    }
+
+    /// Parses the link or image at the lookahead with `parse`. One that doesn't close, like `[a`
+    /// or `[a](b`, is no link at all: its `[` (or `!`) is literal text and what follows is parsed
+    /// again on its own, and the error is reported as a diagnostic. Inside the text of another link
+    /// it fails that link instead, which is then literal text in turn. A reference to an undefined
+    /// label closes but isn't a link either, its `[` is literal text as well, without a diagnostic.
+    /// The links that are literal text are remembered, so that parsing what follows them again
+    /// doesn't try them again.
+    fn parse_or_literal(
+        &mut self,
+        parse: fn(&mut Self) -> Result<Option<InlineToken>, ParseError>,
+    ) -> Result<InlineToken, ParseError> {
+        let start = self.start;
+        let error = match self.literal.get(&start) {
+            Some(error) => error.clone(),
+            // past `MAX_NESTING` links, the innermost one fails all of them
+            None if self.link_depth >= MAX_NESTING => {
+                return Err(ParseError {
+                    expected: TokenKind::ClosingBracket,
+                    found: self.token_at(0),
+                    offset: start,
+                });
+            }
+            None => {
+                let snapshot = self.snapshot();
+                self.link_depth += 1;
+                let result = parse(self);
+                self.link_depth -= 1;
+
+                match result {
+                    Ok(Some(token)) => return Ok(token),
+                    result => {
+                        self.rewind(snapshot);
+                        let error = result.err();
+                        self.literal.insert(start, error.clone());
+                        error
+                    }
+                }
+            }
+        };
+
+        if let Some(error) = error {
+            if self.link_depth > 0 {
+                return Err(error);
+            }
+            let span = start..error.offset;
+            self.diagnostics.push(Diagnostic { error, span });
+        }
+        Ok(InlineToken::Text(self.eat().to_string()))
+    }
+
+    /// ```txt
+    /// Bold
+    ///   : <**-token> InlineTokens <**-token>
+    ///   | <__-token> InlineTokens <__-token>
+    ///   ;
+    ///
+    /// Italic
+    ///   : <*-token> InlineTokens <*-token>
+    ///   | <_-token> InlineTokens <_-token>
+    ///   ;
+    /// ```
+    ///
+    /// Consumes the run of `*` or `_` at the lookahead, which is matched into emphasis with the
+    /// other runs of the inline content holding it, see `DelimiterStack`. A run can open emphasis
+    /// when content follows it and close emphasis when it follows content, except for an
+    /// underscore inside a word. Returns `None` without consuming anything for other tokens.
+    fn parse_delimiter_run(&mut self) -> Option<Delimiter> {
+        let delimiter = self.delimiter_run()?;
+        self.eat();
+
+        Some(delimiter)
+    }
+
+    /// Returns the run of `*` or `_` at the lookahead, see `parse_delimiter_run`
+    fn delimiter_run(&mut self) -> Option<Delimiter> {
+        let (char, length) = match self.lookahead {
+            Some(Token::Asterisk(length)) => ('*', length),
+            Some(Token::Underscore(length)) => ('_', length),
+            _ => return None,
+        };
+
+        let previous = self.previous.clone().unwrap_or(Token::EOF);
+        let next = self.peek();
+        let in_word = char == '_' && previous.is_string();
+        let can_open = !next.is_whitespace() && !next.is_eof() && !in_word;
+        let in_word = char == '_' && next.is_string();
+        let can_close = !previous.is_whitespace() && !previous.is_eof() && !in_word;
+
+        Some(Delimiter::new(char, length, can_open, can_close))
+    }
+
+    /// ```txt
+    /// Code
+    ///   : <`-token> ... <`-token>
+    ///   ;
+    /// ```
+    ///
+    /// The code span is closed by the next run of the same number of backticks in the paragraph,
+    /// without one the backticks are literal text. When enabled, a leading `lang:` is read as the
+    /// language of the code.
+    pub fn parse_code_span(&mut self) -> InlineToken {
+        let fence = self.token_at(0);
+        let Token::Backticks(count) = fence else {
+            return InlineToken::Text(self.eat().to_string());
+        };
+
+        let Some(opening_end) = self.lookahead_end else {
+            return InlineToken::Text(self.eat().to_string());
+        };
+
+        // the content is read verbatim from right after the opening backticks, dropping the tokens
+        // read past them. It continues on the next lines of a paragraph, its line endings are
+        // spaces, and a paragraph ending first leaves the backticks literal.
+        let snapshot = self.snapshot();
+        let mut from = opening_end;
+        let mut code = String::new();
+        loop {
+            self.tokenizer.reset_to(from);
+            self.buffer.truncate(self.cursor);
+            if let Some(raw) = self.tokenizer.read_raw_until('`', count) {
+                code.push_str(&raw);
+                break;
+            }
+
+            let line = self.tokenizer.read_raw_line();
+            code.push_str(line.strip_suffix('\r').unwrap_or(&line));
+            self.lookahead_end = Some(self.tokenizer.mark());
+            self.eat();
+            if !self.in_paragraph || !self.parse_line_end(&mut Vec::new()) {
+                self.rewind(snapshot);
+                self.tokenizer.reset_to(opening_end);
+                self.buffer.truncate(self.cursor);
+                return InlineToken::Text(self.eat().to_string());
+            }
+            code.push(' ');
+            // the line starts where the token before the lookahead ends
+            from = self.buffer[self.cursor - 2].1;
+        }
+        // a space on both sides is padding, so that the code can start or end with a backtick,
+        // unless the code is nothing but spaces
+        if code.len() >= 2
+            && code.starts_with(' ')
+            && code.ends_with(' ')
+            && !code.chars().all(|c| c == ' ')
+        {
+            code = code[1..code.len() - 1].to_string();
+        }
+
+        // the span ends after the closing backticks, where the tokenizer stopped, and the tokens
+        // of its lines are dropped
+        let end = self.tokenizer.mark();
+        self.rewind(snapshot);
+        self.buffer.truncate(self.cursor);
+        self.lookahead_end = Some(end);
+        self.eat();
+
+        if self.options.code_span_lang {
+            if let Some((lang, rest)) = code.split_once(':') {
+                let is_lang = lang
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+'));
+                if !lang.is_empty() && is_lang && !rest.is_empty() {
+                    return InlineToken::new_code_span(Some(lang), rest);
+                }
+            }
+        }
+
+        InlineToken::Code(CodeSpan { lang: None, code })
+    }
+
+    /// ```txt
+    /// Text
+    ///   : <string-token> ...
+    ///   | <escaped-token> ...
+    ///   ;
+    /// ```
+    ///
+    /// Runs of `*` and `_` that can't open or close emphasis are part of the text.
+    pub fn parse_text(&mut self) -> String {
+        let mut text = String::new();
+
+        while let Some(token) = self.lookahead.clone() {
+            if token.is_whitespace() && !is_newline(&token) {
+                text.push_str(&self.eat().to_string());
+                continue;
+            }
+
+            if let Token::Escaped(char) = token {
+                self.eat();
+                text.push(char);
+                continue;
+            }
+
+            let literal = self
+                .delimiter_run()
+                .is_some_and(|delimiter| !delimiter.can_open() && !delimiter.can_close());
+            if token.is_string() || literal {
+                text.push_str(&self.eat().to_string());
+                continue;
+            }
+
+            break;
+        }
+
+        text
+    }
+
+    /// ```txt
+    /// Link
+    ///   : <[-token> LinkText <]-token> Destination
+    ///   | <[-token> LinkText <]-token> <[-token> Text <]-token>  -> full reference
+    ///   | <[-token> LinkText <]-token> <[-token> <]-token>       -> collapsed reference
+    ///   | <[-token> LinkText <]-token>                           -> shortcut reference
+    ///   ;
+    /// ```
+    ///
+    /// A reference without a matching definition is no link, see `parse_or_literal`.
+    pub fn parse_link(&mut self) -> Result<Option<InlineToken>, ParseError> {
+        self.expect(TokenKind::OpeningBracket)?;
+        let tokens = self.parse_link_text()?;
+        self.expect(TokenKind::ClosingBracket)?;
+
+        if self.lookahead == Some(Token::OpeningParenthesis) {
+            let (href, title) = self.parse_destination()?;
+            return Ok(Some(InlineToken::Link(Link {
+                tokens,
+                href,
+                title,
+            })));
+        }
+
+        let definition = self.parse_reference(&plain_text(&tokens))?;
+        Ok(definition.map(|definition| {
+            InlineToken::Link(Link {
+                tokens,
+                href: definition.href,
+                title: definition.title,
+            })
+        }))
+    }
+
+    /// ```txt
+    /// Reference
+    ///   : <[-token> Text <]-token>
+    ///   | <[-token> <]-token>
+    ///   |
+    ///   ;
+    /// ```
+    ///
+    /// Parses the label following the text of a reference link or image, returning the definition
+    /// it refers to. An empty or missing label refers to `text`. Labels match regardless of case
+    /// and of the whitespace between their words, see `normalize_label`.
+    fn parse_reference(&mut self, text: &str) -> Result<Option<LinkDefinition>, ParseError> {
+        let mut label = text.to_string();
+        if self.lookahead == Some(Token::OpeningBracket) {
+            self.expect(TokenKind::OpeningBracket)?;
+            let mut reference = self.parse_text();
+            // like the link text, the label continues on the next line of a paragraph
+            while self.in_paragraph && self.parse_line_end(&mut Vec::new()) {
+                reference.push('\n');
+                reference.push_str(&self.parse_text());
+            }
+            self.expect(TokenKind::ClosingBracket)?;
+
+            if !reference.is_empty() {
+                label = reference;
+            }
+        }
+
+        Ok(self.definitions.get(&normalize_label(&label)).cloned())
+    }
+
+    /// ```txt
+    /// Image
+    ///   : <!-token> <[-token> LinkText <]-token> Destination
+    ///   | <!-token> <[-token> LinkText <]-token> Reference
+    ///   ;
+    /// ```
+    ///
+    /// A reference without a matching definition is no image, see `parse_or_literal`.
+    pub fn parse_image(&mut self) -> Result<Option<InlineToken>, ParseError> {
+        self.expect(TokenKind::ExclamationMark)?;
+        self.expect(TokenKind::OpeningBracket)?;
+        let alt = plain_text(&self.parse_link_text()?);
+        self.expect(TokenKind::ClosingBracket)?;
+
+        if self.lookahead != Some(Token::OpeningParenthesis) {
+            let definition = self.parse_reference(&alt)?;
+            return Ok(definition.map(|definition| {
+                InlineToken::Image(Image {
+                    src: definition.href,
+                    alt,
+                    title: definition.title,
+                })
+            }));
+        }
+
+        let (src, title) = self.parse_destination()?;
+        Ok(Some(InlineToken::Image(Image { src, alt, title })))
+    }
+
+    /// ```txt
+    /// Destination
+    ///   : <(-token> ... <)-token>
+    ///   | <(-token> ... <whitespace-token> Title <)-token>
+    ///   ;
+    /// ```
+    ///
+    /// Parentheses inside the destination are kept as long as they are balanced, only the `)`
+    /// matching the opening parenthesis ends it. A `"title"`, `'title'` or `(title)` may follow the
+    /// destination, see `split_destination`. Escapes are resolved and entity references decoded,
+    /// unless their `&` is escaped, like in link reference definitions.
+    pub fn parse_destination(&mut self) -> Result<(String, Option<String>), ParseError> {
+        if let Some(error) = self.unclosed_destinations.get(&self.start) {
+            return Err(error.clone());
+        }
+        let mut opened = vec![self.start];
+        self.expect(TokenKind::OpeningParenthesis)?;
+
+        let mut raw = String::new();
+        while let Some(token) = self.lookahead.clone() {
+            match token {
+                Token::EOF => break,
+                _ if is_newline(&token) => break,
+                Token::ClosingParenthesis if opened.len() == 1 => break,
+                Token::OpeningParenthesis => opened.push(self.start),
+                Token::ClosingParenthesis => {
+                    opened.pop();
+                }
+                _ => {}
+            }
+            raw.push_str(&self.eat().to_string());
+        }
+
+        // the parentheses left open don't close either, whichever link they follow
+        if let Err(error) = self.expect(TokenKind::ClosingParenthesis) {
+            for start in opened {
+                self.unclosed_destinations.insert(start, error.clone());
+            }
+            return Err(error);
+        }
+
+        Ok(split_destination(&raw))
+    }
+
+    /// ```txt
+    /// LinkText
+    ///   : InlineTokens
+    ///   | LinkText <[-token> LinkText <]-token> LinkText
+    ///   ;
+    /// ```
+    ///
+    /// Brackets inside the link text are kept as literal text as long as they are balanced, only
+    /// the `]` matching the opening bracket of the link ends it. When the text doesn't end, the
+    /// brackets opened in it that aren't closed don't close either, they are remembered as such
+    /// so that each link doesn't look for its end through the rest of the paragraph again.
+    pub fn parse_link_text(&mut self) -> Result<Vec<InlineToken>, ParseError> {
+        let mut stack = DelimiterStack::default();
+        let mut opened = Vec::new();
+
+        while let Some(token) = self.lookahead.clone() {
+            match token {
+                Token::EOF => break,
+                // the text continues on the next line of a paragraph
+                _ if is_newline(&token) => {
+                    if !self.in_paragraph || !self.parse_line_end(stack.tokens()) {
+                        break;
+                    }
+                }
+                Token::ClosingBracket if opened.is_empty() => return Ok(stack.finish()),
+                Token::OpeningBracket | Token::ClosingBracket => {
+                    if token == Token::OpeningBracket {
+                        opened.push(self.start);
+                    } else {
+                        opened.pop();
+                    }
+                    push_text(stack.tokens(), &self.eat().to_string());
+                }
+                _ => self.parse_inline_into(&mut stack)?,
+            }
+        }
+
+        let error = ParseError {
+            expected: TokenKind::ClosingBracket,
+            found: self.token_at(0),
+            offset: self.start,
+        };
+        for start in opened {
+            self.literal
+                .entry(start)
+                .or_insert_with(|| Some(error.clone()));
+        }
+
+        Ok(stack.finish())
+    }
+
+    pub fn eat(&mut self) -> Token {
+        if let Some(token) = self.lookahead.clone() {
+            let end = self.lookahead_end.map_or(self.start, |end| end.offset());
+            if !token.is_whitespace() && !token.is_indent() && !token.is_eof() {
+                self.last_end = end;
+            }
+            self.start = end;
+
+            self.read_lookahead();
+            self.line_start = is_newline(&token) || token.is_indent();
+            self.previous = Some(token.clone());
+            return token;
+        }
+
+        // nothing was read yet
+        Token::EOF
+    }
+
+    /// Consumes the lookahead when it is of the given kind, fails without consuming anything
+    /// otherwise.
+    pub fn expect(&mut self, kind: TokenKind) -> Result<Token, ParseError> {
+        let found = self.token_at(0);
+        if found.kind() != kind {
+            return Err(ParseError {
+                expected: kind,
+                found,
+                offset: self.start,
+            });
+        }
+
+        Ok(self.eat())
+    }
+
+    /// Makes the token following the lookahead the new lookahead, reading it from the tokenizer
+    /// unless it was read already
+    fn read_lookahead(&mut self) {
+        if self.cursor == self.buffer.len() {
+            self.read_token();
+        }
+        let (token, end) = self.buffer[self.cursor].clone();
+        self.cursor += 1;
+        self.lookahead = Some(token);
+        self.lookahead_end = Some(end);
+    }
+
+    /// Reads the next token from the tokenizer into the buffer, recording where it ends
+    fn read_token(&mut self) {
+        let token = self.tokenizer.consume();
+        self.buffer.push_back((token, self.tokenizer.mark()));
+    }
+
+    /// Drops the consumed tokens but the lookahead, which no snapshot can go back to anymore
+    fn drop_consumed(&mut self) {
+        let consumed = self.cursor.saturating_sub(1);
+        self.buffer.drain(..consumed);
+        self.cursor -= consumed;
+    }
+
+    /// Returns the token following the lookahead without consuming anything
+    pub fn peek(&mut self) -> Token {
+        self.token_at(1)
+    }
+
+    /// Returns the token `index` tokens ahead, where the lookahead is at index 0, reading tokens
+    /// from the tokenizer as needed.
+    fn token_at(&mut self, index: usize) -> Token {
+        if index == 0 {
+            return self.lookahead.clone().unwrap_or(Token::EOF);
+        }
+
+        let index = self.cursor + index - 1;
+        while self.buffer.len() <= index {
+            self.read_token();
+        }
+
+        self.buffer[index].0.clone()
+    }
+
+    // todo: remove
+    pub fn consume_whitespace(&mut self) {
+        while let Some(token) = self.lookahead.clone() {
+            if !token.is_whitespace() && !token.is_indent() {
+                break;
+            }
+            self.eat();
+        }
+    }
+}
+
+/// Appends an inline token, merging text into the last token when it is text as well so that
+/// consecutive text ends up in a single node
+pub(crate) fn push_inline(tokens: &mut Vec<InlineToken>, token: InlineToken) {
+    match token {
+        InlineToken::Text(text) => push_text(tokens, &text),
+        token => tokens.push(token),
+    }
+}
+
+/// Appends text to the inline tokens, extending the last token when it is text as well
+pub(crate) fn push_text(tokens: &mut Vec<InlineToken>, text: &str) {
+    match tokens.last_mut() {
+        Some(InlineToken::Text(last)) => last.push_str(text),
+        _ => tokens.push(InlineToken::new_text(text)),
+    }
+}
+
+/// Removes the whitespace at the start and end of inline content, leaving out text tokens that
+/// held nothing else
+pub(crate) fn trim_inline(tokens: &mut Vec<InlineToken>) {
+    if let Some(InlineToken::Text(text)) = tokens.first_mut() {
+        *text = text.trim_start().to_string();
+    }
+    if let Some(InlineToken::Text(text)) = tokens.last_mut() {
+        text.truncate(text.trim_end().len());
+    }
+    tokens.retain(|token| !matches!(token, InlineToken::Text(text) if text.is_empty()));
+}
+
+/// Removes a `{#id .class ...}` block from the end of the heading content and returns the
+/// attributes it sets. Braces holding anything else are left as text.
+fn take_heading_attributes(tokens: &mut Vec<InlineToken>) -> HeadingAttrs {
+    let Some(InlineToken::Text(text)) = tokens.last_mut() else {
+        return HeadingAttrs::default();
+    };
+    let Some(open) = text.strip_suffix('}').and_then(|text| text.rfind('{')) else {
+        return HeadingAttrs::default();
+    };
+
+    let mut attributes = HeadingAttrs::default();
+    for attribute in text[open + 1..text.len() - 1].split_whitespace() {
+        match (attribute.strip_prefix('#'), attribute.strip_prefix('.')) {
+            (Some(id), _) if !id.is_empty() => attributes.id = Some(id.to_string()),
+            (_, Some(class)) if !class.is_empty() => attributes.classes.push(class.to_string()),
+            _ => return HeadingAttrs::default(),
+        }
+    }
+    if attributes == HeadingAttrs::default() {
+        return attributes;
+    }
+
+    text.truncate(open);
+    trim_inline(tokens);
+    attributes
+}
+
+/// Maps an offset in the content of a container block to the source, given where the lines of the
+/// content start in both
+fn source_offset(lines: &[(usize, usize)], offset: usize) -> usize {
+    let line = lines.partition_point(|&(start, _)| start <= offset) - 1;
+    let (start, source) = lines[line];
+    source + offset - start
+}
+
+/// Removes the backslash ending the text of the last token along with the spaces before it,
+/// returns false when it doesn't end with one
+fn take_backslash(tokens: &mut Vec<InlineToken>) -> bool {
+    let Some(InlineToken::Text(text)) = tokens.last_mut() else {
+        return false;
+    };
+    if !text.ends_with('\\') {
+        return false;
+    }
+    text.pop();
+    text.truncate(text.trim_end_matches(' ').len());
+    if text.is_empty() {
+        tokens.pop();
+    }
+
+    true
+}
+
+/// Flattens inline tokens into the plain text they display
+pub(crate) fn plain_text(tokens: &[InlineToken]) -> String {
+    tokens
+        .iter()
+        .map(|token| match token {
+            InlineToken::Text(text) => text.clone(),
+            InlineToken::Code(code_span) => code_span.code.clone(),
+            InlineToken::Bold(tokens) | InlineToken::Italic(tokens) => plain_text(tokens),
+            InlineToken::Link(link) => plain_text(&link.tokens),
+            InlineToken::Image(image) => image.alt.clone(),
+            InlineToken::Comment(_) => String::new(),
+            InlineToken::LineBreak => "\n".to_string(),
+        })
+        .collect()
+}
+
+/// Returns true when the token is a run of the same fence character at least as long as `fence`
+fn closes_fence(token: &Token, fence: &Token) -> bool {
+    match (token, fence) {
+        (Token::Backticks(m), Token::Backticks(n)) | (Token::Tilde(m), Token::Tilde(n)) => m >= n,
+        _ => false,
+    }
+}
+
+/// Returns true when a line opens a block that a following line can't continue lazily
+fn opens_unlazy_block(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("```") || line.starts_with("~~~") || line.starts_with('#')
+}
+
+/// Number of block quotes and list items, or of links and images, that can be nested in one
+/// another. The markers of deeper ones are text, so that parsing them can't run out of stack.
+const MAX_NESTING: usize = 32;
+
+/// Elements whose content is raw text, which may hold blank lines. The HTML block one of them
+/// opens ends with the line closing any of them.
+const RAW_TAGS: &[&str] = &["pre", "script", "style", "textarea"];
+
+/// Elements opening an HTML block that ends before the next blank line, as per CommonMark
+const BLOCK_TAGS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "base",
+    "basefont",
+    "blockquote",
+    "body",
+    "caption",
+    "center",
+    "col",
+    "colgroup",
+    "dd",
+    "details",
+    "dialog",
+    "dir",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "frame",
+    "frameset",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "header",
+    "hr",
+    "html",
+    "iframe",
+    "legend",
+    "li",
+    "link",
+    "main",
+    "menu",
+    "menuitem",
+    "nav",
+    "noframes",
+    "ol",
+    "optgroup",
+    "option",
+    "p",
+    "param",
+    "search",
+    "section",
+    "summary",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "title",
+    "tr",
+    "track",
+    "ul",
+];
+
+/// How an HTML block ends, see `html_block_end`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HtmlBlockEnd {
+    /// With the line holding the marker, in any case
+    Marker(&'static str),
+    /// With the line holding the closing tag of one of `RAW_TAGS`
+    RawTag,
+    /// Before the next blank line
+    BlankLine,
+}
+
+/// Returns how the HTML block a line opens ends, or `None` when the line doesn't open one. Raw
+/// text elements, comments, processing instructions, declarations and CDATA sections run up to
+/// their end, the elements of `BLOCK_TAGS` up to a blank line. So does a line holding nothing but
+/// a complete tag of any other name, unless `interrupting` a paragraph.
+fn html_block_end(line: &str, interrupting: bool) -> Option<HtmlBlockEnd> {
+    let lower = line.to_ascii_lowercase();
+    let rest = lower.strip_prefix('<')?;
+
+    if rest.starts_with("!--") {
+        return Some(HtmlBlockEnd::Marker("-->"));
+    }
+    if rest.starts_with('?') {
+        return Some(HtmlBlockEnd::Marker("?>"));
+    }
+    if rest.starts_with("![cdata[") {
+        return Some(HtmlBlockEnd::Marker("]]>"));
+    }
+    if rest.starts_with("!") && rest[1..].starts_with(|char: char| char.is_ascii_alphabetic()) {
+        return Some(HtmlBlockEnd::Marker(">"));
+    }
+
+    let closing = rest.starts_with('/');
+    let tag = rest.strip_prefix('/').unwrap_or(rest);
+    let length = tag
+        .find(|char: char| !char.is_ascii_alphanumeric())
+        .unwrap_or(tag.len());
+    let (name, after) = tag.split_at(length);
+    let ends_name = after.is_empty() || after.starts_with([' ', '\t', '>']);
+
+    if !closing && RAW_TAGS.contains(&name) && ends_name {
+        return Some(HtmlBlockEnd::RawTag);
+    }
+    if BLOCK_TAGS.contains(&name) && (ends_name || after.starts_with("/>")) {
+        return Some(HtmlBlockEnd::BlankLine);
+    }
+    match tag_at(line) {
+        Some((name, length))
+            if !interrupting
+                && !RAW_TAGS.contains(&name.as_str())
+                && line[length..].trim().is_empty() =>
+        {
+            Some(HtmlBlockEnd::BlankLine)
+        }
+        _ => None,
+    }
+}
+
+/// Returns true for a line of nothing but `=` or nothing but `-`, apart from surrounding spaces
+fn is_setext_underline(line: &str) -> bool {
+    let line = line.trim();
+    !line.is_empty()
+        && (line.bytes().all(|byte| byte == b'=') || line.bytes().all(|byte| byte == b'-'))
+}
+
+/// Returns true for the number of an ordered list item, up to nine digits
+fn is_list_number(number: &str) -> bool {
+    (1..=9).contains(&number.len()) && number.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+/// Returns the number of an ordered list item from the token opening its marker
+fn list_number(token: &Token) -> Option<usize> {
+    match token {
+        Token::OrderedMarker { number, .. } => Some(*number),
+        token => token.to_string().trim_end_matches('.').parse().ok(),
+    }
+}
+
+/// Returns true when the token is whitespace that ends the current line
+fn is_newline(token: &Token) -> bool {
+    token.newline_count() > 0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bytes::{CharIterator, Encoding};
+    use std::time::{Duration, Instant};
+
+    macro_rules! assert_ast {
+        ($raw:expr, $doc_ast:expr) => {
+            let mut chars = CharIterator::new();
+            chars.read_from_str($raw, Some(Encoding::UTF8));
+
+            let mut tokenizer = Tokenizer::new(&mut chars);
+            let mut parser = Parser::new(&mut tokenizer);
+
+            assert_eq!(parser.parse(), $doc_ast);
+        };
+    }
+
+    macro_rules! assert_inline {
+        ($raw:expr, $tokens:expr) => {
+            let mut chars = CharIterator::new();
+            chars.read_from_str($raw, Some(Encoding::UTF8));
+
+            let mut tokenizer = Tokenizer::new(&mut chars);
+            let mut parser = Parser::new(&mut tokenizer);
+            parser.read_lookahead();
+
+            assert_eq!(parser.parse_inline_tokens(), Ok($tokens));
+        };
+    }
+
+    #[test]
+    fn parse_heading() {
+        let tests = vec![
+            ("# h1", 1, "h1"),
+            ("## h2", 2, "h2"),
+            ("### h3", 3, "h3"),
+            ("#### I am heading", 4, "I am heading"),
+        ];
+        for (raw, level, text) in tests {
+            assert_ast!(
+                raw,
+                Document::new(vec![Element::new_heading(
+                    level,
+                    vec![InlineToken::new_text(text)]
+                )])
+            );
+        }
+        // the hashes must be followed by whitespace
+        for raw in ["#hashtag", "#5 bolt", "##a"] {
+            assert_ast!(
+                raw,
+                Document::new(vec![Element::new_paragraph(vec![InlineToken::new_text(
+                    raw
+                )])])
+            );
+        }
+    }
+
+    #[test]
+    fn parse_bullet_lists() {
+        for marker in ["-", "+", "*"] {
+            assert_ast!(
+                &format!("{marker} a\n{marker} b"),
+                Document::new(vec![Element::new_list(
+                    ListKind::Unordered,
+                    vec![
+                        vec![Element::new_paragraph(vec![InlineToken::new_text("a")])],
+                        vec![Element::new_paragraph(vec![InlineToken::new_text("b")])],
+                    ]
+                )])
+            );
+        }
+    }
+
+    #[test]
+    fn skip_marker_space() {
+        assert_ast!(
+            "-   a\n-\tb *c*",
+            Document::new(vec![Element::new_list(
+                ListKind::Unordered,
+                vec![
+                    vec![Element::new_paragraph(vec![InlineToken::new_text("a")])],
+                    vec![Element::new_paragraph(vec![
+                        InlineToken::new_text("b "),
+                        InlineToken::new_italic(vec![InlineToken::new_text("c")]),
+                    ])],
+                ]
+            )])
+        );
+        assert_ast!(
+            "#\th",
+            Document::new(vec![Element::new_heading(
+                1,
+                vec![InlineToken::new_text("h")]
+            )])
+        );
+    }
+
+    #[test]
+    fn parse_ordered_lists() {
+        let item = |text| vec![Element::new_paragraph(vec![InlineToken::new_text(text)])];
+
+        assert_ast!(
+            "- a\n- b\n1. c\n2. d",
+            Document::new(vec![
+                Element::new_list(ListKind::Unordered, vec![item("a"), item("b")]),
+                Element::new_list(ListKind::Ordered, vec![item("c"), item("d")]),
+            ])
+        );
+        assert_ast!(
+            "1) a\n2. b",
+            Document::new(vec![
+                Element::new_list(ListKind::Ordered, vec![item("a")]),
+                Element::new_ordered_list(2, vec![item("b")]),
+            ])
+        );
+        assert_ast!(
+            "In\n1984. a",
+            Document::new(vec![Element::new_paragraph(vec![InlineToken::new_text(
+                "In\n1984. a"
+            )])])
+        );
+    }
+
+    #[test]
+    fn parse_setext_headings() {
+        let paragraph = |text| Element::new_paragraph(vec![InlineToken::new_text(text)]);
+        let heading = |level, text| Element::new_heading(level, vec![InlineToken::new_text(text)]);
+
+        assert_ast!("text\n===", Document::new(vec![heading(1, "text")]));
+        assert_ast!(
+            "text\n# next",
+            Document::new(vec![paragraph("text"), heading(1, "next")])
+        );
+        assert_ast!("text\nmore", Document::new(vec![paragraph("text\nmore")]));
+        assert_ast!("a\nb  \n---  ", Document::new(vec![heading(2, "a\nb")]));
+        assert_ast!("a\n    ===", Document::new(vec![paragraph("a\n===")]));
+        assert_ast!("a\n= =", Document::new(vec![paragraph("a\n= =")]));
+        assert_ast!(
+            "> a\n===",
+            Document::new(vec![Element::BlockQuote(vec![paragraph("a\n===")])])
+        );
+        assert_ast!(
+            "> a\n> ===",
+            Document::new(vec![Element::BlockQuote(vec![heading(1, "a")])])
+        );
+    }
+
+    #[test]
+    fn parse_paragraph_before_block() {
+        let paragraph = |text| Element::new_paragraph(vec![InlineToken::new_text(text)]);
+        let list = |text| Element::new_list(ListKind::Unordered, vec![vec![paragraph(text)]]);
+
+        assert_ast!("a\n- b", Document::new(vec![paragraph("a"), list("b")]));
+        assert_ast!(
+            "a *b\n* c*",
+            Document::new(vec![paragraph("a *b"), list("c*")])
+        );
+        assert_ast!(
+            "a `b\n- c`",
+            Document::new(vec![paragraph("a `b"), list("c`")])
+        );
+        assert_ast!(
+            "a **b\n## c**",
+            Document::new(vec![
+                paragraph("a **b"),
+                Element::new_heading(2, vec![InlineToken::new_text("c**")]),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_inline_across_lines() {
+        let paragraph = |tokens| Element::new_paragraph(tokens);
+        let text = InlineToken::new_text;
+
+        assert_ast!(
+            "*a\nb*",
+            Document::new(vec![paragraph(vec![InlineToken::new_italic(vec![text(
+                "a\nb"
+            )])])])
+        );
+        assert_ast!(
+            "a `b\nc` d",
+            Document::new(vec![paragraph(vec![
+                text("a "),
+                InlineToken::new_code("b c"),
+                text(" d"),
+            ])])
+        );
+        assert_ast!(
+            "`a\r\n  b`",
+            Document::new(vec![paragraph(vec![InlineToken::new_code("a b")])])
+        );
+        assert_ast!(
+            "> *a\n> b*",
+            Document::new(vec![Element::BlockQuote(vec![paragraph(vec![
+                InlineToken::new_italic(vec![text("a\nb")])
+            ])])])
+        );
+        assert_ast!(
+            "[a `b\nc` `d",
+            Document::new(vec![paragraph(vec![
+                text("[a "),
+                InlineToken::new_code("b c"),
+                text(" `d"),
+            ])])
+        );
+        // the paragraph ends first, the delimiters are literal
+        assert_ast!(
+            "a `b\n\nc`",
+            Document::new(vec![
+                paragraph(vec![text("a `b")]),
+                paragraph(vec![text("c`")])
+            ])
+        );
+        assert_ast!(
+            "*a\n\nb*",
+            Document::new(vec![
+                paragraph(vec![text("*a")]),
+                paragraph(vec![text("b*")])
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_hard_breaks() {
+        let text = InlineToken::new_text;
+
+        assert_ast!(
+            "a  \nb \\\n\\\nc \\\\\nd\ne\\",
+            Document::new(vec![Element::new_paragraph(vec![
+                text("a"),
+                InlineToken::LineBreak,
+                text("b"),
+                InlineToken::LineBreak,
+                InlineToken::LineBreak,
+                text("c \\\nd\ne\\"),
+            ])])
+        );
+        // a paragraph can't end with a hard break
+        assert_ast!(
+            "a  \n# b",
+            Document::new(vec![
+                Element::new_paragraph(vec![text("a")]),
+                Element::new_heading(1, vec![text("b")]),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_plain_text() {
+        let paragraph = |text| Element::new_paragraph(vec![InlineToken::new_text(text)]);
+
+        assert_ast!(
+            "Just some text.",
+            Document::new(vec![paragraph("Just some text.")])
+        );
+        assert_ast!(
+            "# Title\nSome text",
+            Document::new(vec![
+                Element::new_heading(1, vec![InlineToken::new_text("Title")]),
+                paragraph("Some text"),
+            ])
+        );
+        for text in ["!important", ")x", "]x", "~x", "+x", "...", "1.x", "\\"] {
+            let document = crate::parse(text);
+            assert!(
+                document.diagnostics().is_empty()
+                    && matches!(document.elements.as_slice(), [Element::Paragraph(_)]),
+                "{:?}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn parse_comments() {
+        let paragraph = |text| Element::new_paragraph(vec![InlineToken::new_text(text)]);
+
+        assert_ast!(
+            "a\n\n<!-- b\n\nc -->\n\nd\n<!--e-->\nf",
+            Document::new(vec![
+                paragraph("a"),
+                Element::Comment(" b\n\nc ".to_string()),
+                paragraph("d"),
+                Element::Comment("e".to_string()),
+                paragraph("f"),
+            ])
+        );
+        assert_ast!(
+            "a <!-- *b* --> c",
+            Document::new(vec![Element::new_paragraph(vec![
+                InlineToken::new_text("a "),
+                InlineToken::Comment(" *b* ".to_string()),
+                InlineToken::new_text(" c"),
+            ])])
+        );
+    }
+
+    #[test]
+    fn parse_html_blocks() {
+        let paragraph = |text| Element::new_paragraph(vec![InlineToken::new_text(text)]);
+        let html = |html: &str| Element::Html(html.to_string());
+
+        assert_ast!(
+            "<div class=\"note\">\n*a*\n</div>\n\nb\n<DIV>\nc\n\n<x-card>\nd",
+            Document::new(vec![
+                html("<div class=\"note\">\n*a*\n</div>"),
+                paragraph("b"),
+                html("<DIV>\nc"),
+                html("<x-card>\nd"),
+            ])
+        );
+        assert_ast!(
+            "<!-- a\n\nb --> c\nd\n\n<pre>\n\n*e*\n</pre>\nf\n<span>g</span>\nh",
+            Document::new(vec![
+                html("<!-- a\n\nb --> c"),
+                paragraph("d"),
+                html("<pre>\n\n*e*\n</pre>"),
+                paragraph("f\n<span>g</span>\nh"),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_list_content_column() {
+        let paragraph = |text| Element::new_paragraph(vec![InlineToken::new_text(text)]);
+        let ordered = |items| Element::new_list(ListKind::Ordered, items);
+
+        // the blank line between the paragraphs of the item makes the list loose
+        assert_ast!(
+            "1.  a\n    b\n\n    c",
+            Document::new(vec![Element::List(List {
+                kind: ListKind::Ordered,
+                start: Some(1),
+                items: vec![vec![paragraph("a\nb"), paragraph("c")]],
+                checked: Vec::new(),
+                loose: true,
+            })])
+        );
+        assert_ast!(
+            "1.  a\n\n   b",
+            Document::new(vec![ordered(vec![vec![paragraph("a")]]), paragraph("b")])
+        );
+        assert_ast!(
+            " -   a\n     - b",
+            Document::new(vec![Element::new_list(
+                ListKind::Unordered,
+                vec![vec![
+                    paragraph("a"),
+                    Element::new_list(ListKind::Unordered, vec![vec![paragraph("b")]]),
+                ]]
+            )])
+        );
+    }
+
+    #[test]
+    fn parse_loose_lists() {
+        let paragraph = |text| Element::new_paragraph(vec![InlineToken::new_text(text)]);
+        let list = |items, loose| {
+            Element::List(List {
+                kind: ListKind::Unordered,
+                start: None,
+                items,
+                checked: Vec::new(),
+                loose,
+            })
+        };
+
+        assert_ast!(
+            "- a\n\n- b",
+            Document::new(vec![list(
+                vec![vec![paragraph("a")], vec![paragraph("b")]],
+                true
+            )])
+        );
+        assert_ast!(
+            "- a\n\ntext\n\n- b",
+            Document::new(vec![
+                list(vec![vec![paragraph("a")]], false),
+                paragraph("text"),
+                list(vec![vec![paragraph("b")]], false),
+            ])
+        );
+        // a blank line inside a nested list or after the last item leaves the list tight
+        assert_ast!(
+            "- a\n  - b\n\n  - c\n- d\n\n",
+            Document::new(vec![list(
+                vec![
+                    vec![
+                        paragraph("a"),
+                        list(vec![vec![paragraph("b")], vec![paragraph("c")]], true),
+                    ],
+                    vec![paragraph("d")],
+                ],
+                false
+            )])
+        );
+    }
+
+    #[test]
+    fn parse_list_marker_change() {
+        assert_ast!(
+            "- a\n+ b\n* c",
+            Document::new(vec![
+                Element::new_list(
+                    ListKind::Unordered,
+                    vec![vec![Element::new_paragraph(vec![InlineToken::new_text(
+                        "a"
+                    )])]]
+                ),
+                Element::new_list(
+                    ListKind::Unordered,
+                    vec![vec![Element::new_paragraph(vec![InlineToken::new_text(
+                        "b"
+                    )])]]
+                ),
+                Element::new_list(
+                    ListKind::Unordered,
+                    vec![vec![Element::new_paragraph(vec![InlineToken::new_text(
+                        "c"
+                    )])]]
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn read_list_items() {
+        let document = crate::parse("- **a** b\n- c\n- d");
+        let list = match &document.elements[0] {
+            Element::List(list) => list,
+            element => panic!("expected a list, found {:?}", element),
+        };
+
+        assert!(!list.is_ordered());
+        assert_eq!(list.kind(), &ListKind::Unordered);
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.items().len(), 3);
+        assert_eq!(list.item_text(0), Some("a b".to_string()));
+        assert_eq!(list.item_text(3), None);
+    }
+
+    #[test]
+    fn parse_link_with_brackets() {
+        assert_inline!(
+            "[a [b] c](url)",
+            vec![InlineToken::new_link(
+                vec![InlineToken::new_text("a [b] c")],
+                "url"
+            )]
+        );
+        assert_inline!(
+            "[link [foo [bar]]](/uri)",
+            vec![InlineToken::new_link(
+                vec![InlineToken::new_text("link [foo [bar]]")],
+                "/uri"
+            )]
+        );
+    }
+
+    #[test]
+    fn parse_link_followed_by_text() {
+        assert_inline!(
+            "see [a](b) and more text",
+            vec![
+                InlineToken::new_text("see "),
+                InlineToken::new_link(vec![InlineToken::new_text("a")], "b"),
+                InlineToken::new_text(" and more text"),
+            ]
+        );
+        assert_ast!(
+            "- [a](b) c\n- d",
+            Document::new(vec![Element::new_list(
+                ListKind::Unordered,
+                vec![
+                    vec![Element::new_paragraph(vec![
+                        InlineToken::new_link(vec![InlineToken::new_text("a")], "b"),
+                        InlineToken::new_text(" c"),
+                    ])],
+                    vec![Element::new_paragraph(vec![InlineToken::new_text("d")])],
+                ]
+            )])
+        );
+    }
+
+    #[test]
+    fn decode_destination_entities() {
+        let link = |href| {
+            Document::new(vec![Element::new_paragraph(vec![InlineToken::new_link(
+                vec![InlineToken::new_text("x")],
+                href,
+            )])])
+        };
+
+        assert_ast!("[x](a&amp;b)", link("a&b"));
+        assert_ast!("[x](a&#35;b&#x41;)", link("a#bA"));
+        assert_ast!("[x](a&b &foo; &amp)", link("a&b &foo; &amp"));
+        assert_ast!("[x](\\&amp;)", link("&amp;"));
+        assert_ast!(
+            "[x](a \"t&amp;\")",
+            Document::new(vec![Element::new_paragraph(vec![InlineToken::Link(
+                Link {
+                    tokens: vec![InlineToken::new_text("x")],
+                    href: "a".to_string(),
+                    title: Some("t&".to_string()),
+                }
+            )])])
+        );
+        assert_ast!(
+            "![y](a&lt;b)",
+            Document::new(vec![Element::new_paragraph(vec![InlineToken::new_img(
+                "a<b", "y"
+            )])])
+        );
+        assert_ast!(
+            "[x]\n\n[x]: /a&amp;b 'a &quot;b&quot; \\&amp;'",
+            Document::new(vec![Element::new_paragraph(vec![InlineToken::Link(
+                Link {
+                    tokens: vec![InlineToken::new_text("x")],
+                    href: "/a&b".to_string(),
+                    title: Some("a \"b\" &amp;".to_string()),
+                }
+            )])])
+        );
+    }
+
+    #[test]
+    fn parse_image_in_link_text() {
+        assert_inline!(
+            "[img ![x](y)](z)",
+            vec![InlineToken::new_link(
+                vec![
+                    InlineToken::new_text("img "),
+                    InlineToken::new_img("y", "x")
+                ],
+                "z"
+            )]
+        );
+    }
+
+    #[test]
+    fn parse_reference_links() {
+        assert_ast!(
+            "# [x][id] [id] [id][] [y][none]\n\n[id]: /url \"title\"",
+            Document::new(vec![Element::new_heading(
+                1,
+                vec![
+                    InlineToken::Link(Link {
+                        tokens: vec![InlineToken::new_text("x")],
+                        href: "/url".to_string(),
+                        title: Some("title".to_string()),
+                    }),
+                    InlineToken::new_text(" "),
+                    InlineToken::Link(Link {
+                        tokens: vec![InlineToken::new_text("id")],
+                        href: "/url".to_string(),
+                        title: Some("title".to_string()),
+                    }),
+                    InlineToken::new_text(" "),
+                    InlineToken::Link(Link {
+                        tokens: vec![InlineToken::new_text("id")],
+                        href: "/url".to_string(),
+                        title: Some("title".to_string()),
+                    }),
+                    InlineToken::new_text(" [y][none]"),
+                ]
+            )])
+        );
+
+        // the text of a reference without a definition keeps its markup
+        let text = InlineToken::new_text;
+        assert_ast!(
+            "see [*this*] now [**a**][nope]",
+            Document::new(vec![Element::new_paragraph(vec![
+                text("see ["),
+                InlineToken::Italic(vec![text("this")]),
+                text("] now ["),
+                InlineToken::Bold(vec![text("a")]),
+                text("][nope]"),
+            ])])
+        );
+        assert!(crate::parse("[a][nope]").diagnostics().is_empty());
+    }
+
+    #[test]
+    fn parse_reference_images() {
+        assert_ast!(
+            "![a][logo] ![logo][] ![logo] ![b][none]\n\n[logo]: /logo.png",
+            Document::new(vec![Element::new_paragraph(vec![
+                InlineToken::new_img("/logo.png", "a"),
+                InlineToken::new_text(" "),
+                InlineToken::new_img("/logo.png", "logo"),
+                InlineToken::new_text(" "),
+                InlineToken::new_img("/logo.png", "logo"),
+                InlineToken::new_text(" ![b][none]"),
+            ])])
+        );
+        // like links, the text keeps its markup, and what follows it may be a link
+        let text = InlineToken::new_text;
+        assert_ast!(
+            "![**a**][nope] ![a][b][c]\n\n[c]: /c",
+            Document::new(vec![Element::new_paragraph(vec![
+                text("!["),
+                InlineToken::Bold(vec![text("a")]),
+                text("][nope] ![a]"),
+                InlineToken::new_link(vec![text("b")], "/c"),
+            ])])
+        );
+
+        let image = |title: &str| {
+            InlineToken::Image(Image {
+                src: "/logo.png".to_string(),
+                alt: "a".to_string(),
+                title: Some(title.to_string()),
+            })
+        };
+        assert_ast!(
+            "![a](/logo.png \"inline\") ![a][logo]\n\n[logo]: /logo.png (defined)",
+            Document::new(vec![Element::new_paragraph(vec![
+                image("inline"),
+                InlineToken::new_text(" "),
+                image("defined"),
+            ])])
+        );
+    }
+
+    #[test]
+    fn match_reference_labels() {
+        let link =
+            |text: &str, href: &str| InlineToken::new_link(vec![InlineToken::new_text(text)], href);
+
+        assert_ast!(
+            "[FOO] [a  b] [x][ A b ] [Ω][]\n\n[foo]: /foo\n[a b]: /ab\n[ω]: /omega",
+            Document::new(vec![Element::new_paragraph(vec![
+                link("FOO", "/foo"),
+                InlineToken::new_text(" "),
+                link("a  b", "/ab"),
+                InlineToken::new_text(" "),
+                link("x", "/ab"),
+                InlineToken::new_text(" "),
+                link("Ω", "/omega"),
+            ])])
+        );
+        // the first definition of a label wins, whatever its case
+        assert_ast!(
+            "[a]\n\n[A]: /first\n[a]: /second",
+            Document::new(vec![Element::new_paragraph(vec![link("a", "/first")])])
+        );
+        // a line ending in a label separates words like a space, a non-breaking space doesn't
+        assert_ast!(
+            "[Foo\nBar] [a\u{A0}b]\n\n[foo bar]: /u\n[a b]: /ab",
+            Document::new(vec![Element::new_paragraph(vec![
+                link("Foo\nBar", "/u"),
+                InlineToken::new_text(" [a\u{A0}b]"),
+            ])])
+        );
+    }
+
+    #[test]
+    fn parse_wrapped_definitions() {
+        let heading = |title: Option<&str>| {
+            Document::new(vec![Element::new_heading(
+                1,
+                vec![InlineToken::Link(Link {
+                    tokens: vec![InlineToken::new_text("x")],
+                    href: "http://url".to_string(),
+                    title: title.map(|title| title.to_string()),
+                })],
+            )])
+        };
+
+        assert_ast!("[id]:\n  http://url\n\n# [x][id]", heading(None));
+        assert_ast!(
+            "[id]: http://url\n  \"title\"\n\n# [x][id]",
+            heading(Some("title"))
+        );
+        assert_ast!(
+            "# [x][id]\n\n[id]:\n  http://url\n  'title'\n",
+            heading(Some("title"))
+        );
+    }
+
+    #[test]
+    fn drop_definitions_from_elements() {
+        let link =
+            |text: &str, href: &str| InlineToken::new_link(vec![InlineToken::new_text(text)], href);
+
+        let document = crate::parse("[id]: http://x\n\n[link][id]");
+        assert_eq!(
+            document,
+            Document::new(vec![Element::new_paragraph(vec![link("link", "http://x")])])
+        );
+        assert_eq!(document.spans, vec![16..26]);
+
+        assert_ast!(
+            "[a]: /a\n[b]: /b\ntext [b]\n\n> [c]: /c\n> [a][c]",
+            Document::new(vec![
+                Element::new_paragraph(vec![InlineToken::new_text("text "), link("b", "/b")]),
+                Element::new_blockquote(vec![Element::new_paragraph(vec![link("a", "/c")])]),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_front_matter() {
+        let mut chars = CharIterator::new();
+        chars.read_from_str(
+            "---\ntitle: x\ntags: [a, b]\n---\n# hi",
+            Some(Encoding::UTF8),
+        );
+        let mut tokenizer = Tokenizer::new(&mut chars);
+        let options = ParserOptions {
+            front_matter: true,
+            ..ParserOptions::default()
+        };
+        let document = Parser::with_options(&mut tokenizer, options).parse();
+
+        assert_eq!(
+            document.front_matter(),
+            Some((FrontMatterKind::Yaml, "title: x\ntags: [a, b]"))
+        );
+        assert_eq!(
+            document.elements,
+            vec![Element::new_heading(1, vec![InlineToken::new_text("hi")])]
+        );
+    }
+
+    #[test]
+    fn parse_thematic_break_after_content() {
+        let mut chars = CharIterator::new();
+        chars.read_from_str("# hi\n---\n# a\n---\n", Some(Encoding::UTF8));
+        let mut tokenizer = Tokenizer::new(&mut chars);
+        let options = ParserOptions {
+            front_matter: true,
+            ..ParserOptions::default()
+        };
+        let document = Parser::with_options(&mut tokenizer, options).parse();
+
+        assert_eq!(document.front_matter(), None);
+        assert_eq!(
+            document.elements,
+            vec![
+                Element::new_heading(1, vec![InlineToken::new_text("hi")]),
+                Element::ThematicBreak,
+                Element::new_heading(1, vec![InlineToken::new_text("a")]),
+                Element::ThematicBreak,
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_front_matter_disabled() {
+        assert_ast!(
+            "---\n# a\n---",
+            Document::new(vec![
+                Element::ThematicBreak,
+                Element::new_heading(1, vec![InlineToken::new_text("a")]),
+                Element::ThematicBreak,
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_spaced_thematic_breaks() {
+        assert_ast!(
+            "* * *\n- - -\n_ _  _ \n**  *",
+            Document::new(vec![Element::ThematicBreak; 4])
+        );
+        assert_ast!(
+            "a\n - -  -\nb",
+            Document::new(vec![
+                Element::new_paragraph(vec![InlineToken::new_text("a")]),
+                Element::ThematicBreak,
+                Element::new_paragraph(vec![InlineToken::new_text("b")]),
+            ])
+        );
+        assert_ast!(
+            "- - item",
+            Document::new(vec![Element::new_list(
+                ListKind::Unordered,
+                vec![vec![Element::new_list(
+                    ListKind::Unordered,
+                    vec![vec![Element::new_paragraph(vec![InlineToken::new_text(
+                        "item"
+                    )])]]
+                )]]
+            )])
+        );
+    }
+
+    #[test]
+    fn parse_front_matter_kinds() {
+        let tests = vec![
+            (
+                "+++\ntitle = \"x\"\n+++\n# hi",
+                FrontMatterKind::Toml,
+                "title = \"x\"",
+            ),
+            (
+                "{\n  \"title\": \"}{\",\n  \"tags\": {\"a\": 1}\n}\n# hi",
+                FrontMatterKind::Json,
+                "{\n  \"title\": \"}{\",\n  \"tags\": {\"a\": 1}\n}",
+            ),
+            (
+                "{\"title\": \"x\"}  \n# hi",
+                FrontMatterKind::Json,
+                "{\"title\": \"x\"}",
+            ),
+        ];
+
+        for (raw, kind, front_matter) in tests {
+            let mut chars = CharIterator::new();
+            chars.read_from_str(raw, Some(Encoding::UTF8));
+            let mut tokenizer = Tokenizer::new(&mut chars);
+            let options = ParserOptions {
+                front_matter: true,
+                ..ParserOptions::default()
+            };
+            let document = Parser::with_options(&mut tokenizer, options).parse();
+
+            assert_eq!(document.front_matter(), Some((kind, front_matter)));
+            assert_eq!(
+                document.elements,
+                vec![Element::new_heading(1, vec![InlineToken::new_text("hi")])]
+            );
+        }
+    }
+
+    #[test]
+    fn parse_without_front_matter() {
+        // an unclosed fence is not front matter
+        for raw in ["# hi", "---\n# hi", "# hi\n---\n# a\n---"] {
+            let mut chars = CharIterator::new();
+            chars.read_from_str(raw, Some(Encoding::UTF8));
+            let mut tokenizer = Tokenizer::new(&mut chars);
+            let options = ParserOptions {
+                front_matter: true,
+                ..ParserOptions::default()
+            };
+            let document = Parser::with_options(&mut tokenizer, options).parse();
+
+            assert_eq!(document.front_matter(), None);
+        }
+    }
+
+    #[test]
+    fn parse_code_blocks() {
+        assert_ast!(
+            "```rust\nfn main() {\n\n    [a](b) **c**\n}\n```\n# after",
+            Document::new(vec![
+                Element::new_code_block(Some("rust"), "fn main() {\n\n    [a](b) **c**\n}\n"),
+                Element::new_heading(1, vec![InlineToken::new_text("after")]),
+            ])
+        );
+        assert_ast!(
+            "````\n```\n  ````\n",
+            Document::new(vec![Element::new_code_block(None, "```\n")])
+        );
+        assert_ast!(
+            "```\nunclosed\n",
+            Document::new(vec![Element::new_code_block(None, "unclosed\n")])
+        );
+    }
+
+    #[test]
+    fn parse_indented_code_blocks() {
+        let tests = vec![
+            ("\tcode", "code\n"),
+            ("\t+", "+\n"),
+            ("    a\tb\n\t\tc", "a\tb\n    c\n"),
+            (
+                "  \tfoo\n \t  bar\n\n\n      # baz\n\n",
+                "foo\n  bar\n\n\n  # baz\n",
+            ),
+        ];
+        for (source, code) in tests {
+            assert_ast!(
+                source,
+                Document::new(vec![Element::new_code_block(None, code)])
+            );
+        }
+
+        assert_ast!(
+            "a\n    b\n\n    c\nd",
+            Document::new(vec![
+                Element::new_paragraph(vec![InlineToken::new_text("a\nb")]),
+                Element::new_code_block(None, "c\n"),
+                Element::new_paragraph(vec![InlineToken::new_text("d")]),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_bytes() {
+        let heading = Document::new(vec![Element::new_heading(
+            1,
+            vec![InlineToken::new_text("hi")],
+        )]);
+
+        assert_eq!(
+            Document::try_from_bytes(b"# hi", Some(Encoding::UTF8)),
+            Ok(heading.clone())
+        );
+        assert_eq!(Document::try_from(&b"\xEF\xBB\xBF# hi"[..]), Ok(heading));
+        assert_eq!(
+            Document::try_from_bytes(b"# h\xFFi", Some(Encoding::UTF8)),
+            Err(DecodeError {
+                encoding: Encoding::UTF8,
+                offset: 3
+            })
+        );
+    }
+
+    #[test]
+    fn debug_spans() {
+        let document = crate::parse("# hi\n\n---");
+        let debug = document.debug_with_spans();
+
+        assert!(debug.starts_with("Heading@0..4(\n"), "{}", debug);
+        assert!(debug.ends_with("\nThematicBreak@6..9\n"), "{}", debug);
+    }
+
+    #[test]
+    fn trim_heading_content() {
+        let raw = "#   hi\n## *a* `b`  ";
+        let mut chars = CharIterator::new();
+        chars.read_from_str(raw, Some(Encoding::UTF8));
+        let mut tokenizer = Tokenizer::new(&mut chars);
+        let options = ParserOptions {
+            retain_source: true,
+            ..ParserOptions::default()
+        };
+        let document = Parser::with_options(&mut tokenizer, options).parse();
+
+        assert_eq!(
+            document.elements,
+            vec![
+                Element::new_heading(1, vec![InlineToken::new_text("hi")]),
+                Element::new_heading(
+                    2,
+                    vec![
+                        InlineToken::new_italic(vec![InlineToken::new_text("a")]),
+                        InlineToken::new_text(" "),
+                        InlineToken::new_code("b"),
+                    ]
+                ),
+            ]
+        );
+        assert_eq!(document.source_of(&document.elements[0]), Some("#   hi"));
+    }
+
+    #[test]
+    fn source_of_elements() {
+        let raw = "# Title\n\n```rust\nlet x = [1];\n```\n\n- a\n- b\n";
+        let mut chars = CharIterator::new();
+        chars.read_from_str(raw, Some(Encoding::UTF8));
+        let mut tokenizer = Tokenizer::new(&mut chars);
+        let options = ParserOptions {
+            retain_source: true,
+            ..ParserOptions::default()
+        };
+        let document = Parser::with_options(&mut tokenizer, options).parse();
+
+        assert_eq!(document.source_of(&document.elements[0]), Some("# Title"));
+        assert_eq!(
+            document.source_of(&document.elements[1]),
+            Some("```rust\nlet x = [1];\n```")
+        );
+        assert_eq!(document.source_of(&document.elements[2]), Some("- a\n- b"));
+        assert_eq!(document.span_of(&document.elements[0]), Some(0..7));
+
+        // elements that aren't part of the document have no source
+        let other = Element::ThematicBreak;
+        assert_eq!(document.source_of(&other), None);
+    }
+
+    #[test]
+    fn source_of_nested_elements() {
+        let raw = "> a\n> b\n>\n> - c\n>   ```\n>   d\n>   ```\n\n- e\n\n  > f *g*\n";
+        let options = ParserOptions {
+            retain_source: true,
+            ..ParserOptions::default()
+        };
+        let mut document = crate::parse_with_options(raw, options);
+
+        fn sources(document: &Document) -> Vec<Option<&str>> {
+            document
+                .find_all(|_| true)
+                .map(|element| document.source_of(element))
+                .collect()
+        }
+        let expected = vec![
+            Some("> a\n> b\n>\n> - c\n>   ```\n>   d\n>   ```"),
+            Some("a\n> b"),
+            Some("- c\n>   ```\n>   d\n>   ```"),
+            Some("c"),
+            Some("```\n>   d\n>   ```"),
+            Some("- e\n\n  > f *g*"),
+            Some("e"),
+            Some("> f *g*"),
+            Some("f *g*"),
+        ];
+        assert_eq!(sources(&document), expected);
+
+        // editing the nested elements leaves them without a source
+        document.map_elements(true, |element| element);
+        assert_eq!(sources(&document)[1], None);
+        assert_eq!(sources(&document)[0], expected[0]);
+    }
+
+    #[test]
+    fn parse_adjacent_inline_tokens() {
+        assert_inline!(
+            "**a**b",
+            vec![
+                InlineToken::new_blod(vec![InlineToken::new_text("a")]),
+                InlineToken::new_text("b")
+            ]
+        );
+        assert_inline!(
+            "`x`y",
+            vec![InlineToken::new_code("x"), InlineToken::new_text("y")]
+        );
+        assert_inline!(
+            "a**b**",
+            vec![
+                InlineToken::new_text("a"),
+                InlineToken::new_blod(vec![InlineToken::new_text("b")])
+            ]
+        );
+        assert_inline!(
+            "[x](y)z",
+            vec![
+                InlineToken::new_link(vec![InlineToken::new_text("x")], "y"),
+                InlineToken::new_text("z")
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_smart_punctuation() {
+        let source = "# \"Title\"\n\nIt's 1--2 `a--b` ... www.a--b.com";
+        let options = ParserOptions {
+            smart_punctuation: true,
+            autolink: true,
+            ..ParserOptions::default()
+        };
+        assert_eq!(
+            crate::parse_with_options(source, options),
+            Document::new(vec![
+                Element::new_heading(1, vec![InlineToken::new_text("“Title”")]),
+                Element::new_paragraph(vec![
+                    InlineToken::new_text("It’s 1–2 "),
+                    InlineToken::new_code("a--b"),
+                    InlineToken::new_text(" … "),
+                    InlineToken::new_link(
+                        vec![InlineToken::new_text("www.a--b.com")],
+                        "http://www.a--b.com"
+                    ),
+                ]),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_autolinks() {
+        let source = "(www.x.com) *a@b.com* `www.y.com` [www.z.com](z)";
+        let link = |text, href| InlineToken::new_link(vec![InlineToken::new_text(text)], href);
+
+        let options = ParserOptions {
+            autolink: true,
+            ..ParserOptions::default()
+        };
+        assert_eq!(
+            crate::parse_with_options(source, options),
+            Document::new(vec![Element::new_paragraph(vec![
+                InlineToken::new_text("("),
+                link("www.x.com", "http://www.x.com"),
+                InlineToken::new_text(") "),
+                InlineToken::new_italic(vec![link("a@b.com", "mailto:a@b.com")]),
+                InlineToken::new_text(" "),
+                InlineToken::new_code("www.y.com"),
+                InlineToken::new_text(" "),
+                link("www.z.com", "z"),
+            ])])
+        );
+
+        let document = crate::parse(source);
+        assert_eq!(document.statistics().links, 1);
+    }
+
+    #[test]
+    fn parse_heading_attributes() {
+        let options = ParserOptions {
+            heading_attributes: true,
+            ..ParserOptions::default()
+        };
+        let heading = |source| match crate::parse_with_options(source, options.clone())
+            .elements
+            .remove(0)
+        {
+            Element::Heading(heading) => heading,
+            element => panic!("expected a heading, found {:?}", element),
+        };
+
+        let foo = heading("## T {#foo}");
+        assert_eq!(foo.level(), 2);
+        assert_eq!(foo.text(), "T");
+        assert_eq!(foo.attributes().id, Some("foo".to_string()));
+
+        let classes = heading("# *a* b {.c #d .e}");
+        assert_eq!(classes.text(), "a b");
+        assert_eq!(
+            classes.attributes(),
+            &HeadingAttrs {
+                id: Some("d".to_string()),
+                classes: vec!["c".to_string(), "e".to_string()],
+            }
+        );
+
+        for source in ["# T", "# T {foo}", "# T {#}", "# {} T", "# T {#a} b"] {
+            let plain = heading(source);
+            assert_eq!(plain.attributes(), &HeadingAttrs::default(), "{:?}", source);
+            assert_eq!(plain.text(), source[2..], "{:?}", source);
+        }
+
+        assert_eq!(
+            crate::parse("# T {#foo}"),
+            Document::new(vec![Element::new_heading(
+                1,
+                vec![InlineToken::new_text("T {#foo}")]
+            )])
+        );
+    }
+
+    #[test]
+    fn parse_code_span_lang() {
+        let options = ParserOptions {
+            code_span_lang: true,
+            ..ParserOptions::default()
+        };
+        let document = crate::parse_with_options("`rust:fn()` `a b:c` `:d` `e:`", options);
+        assert_eq!(
+            document,
+            Document::new(vec![Element::new_paragraph(vec![
+                InlineToken::new_code_span(Some("rust"), "fn()"),
+                InlineToken::new_text(" "),
+                InlineToken::new_code("a b:c"),
+                InlineToken::new_text(" "),
+                InlineToken::new_code(":d"),
+                InlineToken::new_text(" "),
+                InlineToken::new_code("e:"),
+            ])])
+        );
+
+        let options = ParserOptions {
+            code_span_lang: true,
+            ..ParserOptions::default()
+        };
+        let document = crate::parse_with_options("> `sh:ls`", options);
+        assert_eq!(
+            document,
+            Document::new(vec![Element::new_blockquote(vec![Element::new_paragraph(
+                vec![InlineToken::new_code_span(Some("sh"), "ls")]
+            )])])
+        );
+
+        assert_inline!("`a:b`", vec![InlineToken::new_code("a:b")]);
+    }
+
+    #[test]
+    fn coalesce_text() {
+        assert_inline!("a*b", vec![InlineToken::new_text("a*b")]);
+        assert_inline!("a ** b ! c", vec![InlineToken::new_text("a ** b ! c")]);
+        assert_inline!(
+            "*a_b*c",
+            vec![
+                InlineToken::new_italic(vec![InlineToken::new_text("a_b")]),
+                InlineToken::new_text("c"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_escapes() {
+        assert_inline!(r"\*a\* \[b\] \\", vec![InlineToken::new_text(r"*a* [b] \")]);
+        assert_inline!(r"`\*`", vec![InlineToken::new_code(r"\*")]);
+        assert_inline!(
+            r"[a](b\)c)",
+            vec![InlineToken::new_link(
+                vec![InlineToken::new_text("a")],
+                "b)c"
+            )]
+        );
+    }
+
+    #[test]
+    fn parse_escaped_block_markers() {
+        let paragraph = |text| Element::new_paragraph(vec![InlineToken::new_text(text)]);
+
+        let tests = vec![
+            (r"\- not a list", "- not a list"),
+            (r"\# not a heading", "# not a heading"),
+            (r"\> not a quote", "> not a quote"),
+            (r"\+ a", "+ a"),
+            (r"1\. a", "1. a"),
+            (r"\---", "---"),
+            ("a\n\\- b\n\\# c\n\\> d\n\\===", "a\n- b\n# c\n> d\n==="),
+        ];
+        for (source, text) in tests {
+            assert_ast!(source, Document::new(vec![paragraph(text)]));
+        }
+
+        assert_ast!(
+            "> \\- a\n> \\# b",
+            Document::new(vec![Element::new_blockquote(vec![paragraph("- a\n# b")])])
+        );
+        assert_ast!(
+            "- \\# a\n- \\> b",
+            Document::new(vec![Element::new_list(
+                ListKind::Unordered,
+                vec![vec![paragraph("# a")], vec![paragraph("> b")]]
+            )])
+        );
+    }
+
+    #[test]
+    fn parse_inline_snippets() {
+        assert_eq!(
+            crate::parse_inline("**a** b"),
+            vec![
+                InlineToken::new_blod(vec![InlineToken::new_text("a")]),
+                InlineToken::new_text(" b"),
+            ]
+        );
+        assert_eq!(
+            crate::parse_inline("> a\n  [b](c\n"),
+            vec![InlineToken::new_text("> a\n[b](c")]
+        );
+    }
+
+    #[test]
+    fn parse_long_hash_runs() {
+        assert_ast!(
+            "####### a",
+            Document::new(vec![Element::new_paragraph(vec![InlineToken::new_text(
+                "####### a"
+            )])])
+        );
+
+        let hashes = "#".repeat(1_000_000);
+        let document = crate::parse(&hashes);
+        assert_eq!(
+            document.elements,
+            vec![Element::new_paragraph(vec![InlineToken::new_text(&hashes)])]
+        );
+    }
+
+    #[test]
+    fn parse_stray_brackets() {
+        assert_inline!(
+            "see item (1) here",
+            vec![InlineToken::new_text("see item (1) here")]
+        );
+        assert_inline!("array[0]", vec![InlineToken::new_text("array[0]")]);
+        assert_inline!("a] b) c(", vec![InlineToken::new_text("a] b) c(")]);
+        assert_ast!(
+            "(a) [b]",
+            Document::new(vec![Element::new_paragraph(vec![InlineToken::new_text(
+                "(a) [b]"
+            )])])
+        );
+    }
+
+    #[test]
+    fn parse_literal_delimiters() {
+        assert_inline!("2 * 3 = 6", vec![InlineToken::new_text("2 * 3 = 6")]);
+        assert_inline!(
+            "snake_case_name and *a * b*",
+            vec![
+                InlineToken::new_text("snake_case_name and "),
+                InlineToken::new_italic(vec![InlineToken::new_text("a * b")]),
+            ]
+        );
+        assert_inline!("a *** b", vec![InlineToken::new_text("a *** b")]);
+    }
+
+    #[test]
+    fn parse_emphasis() {
+        assert_inline!(
+            "*foo**bar**baz*",
+            vec![InlineToken::new_italic(vec![
+                InlineToken::new_text("foo"),
+                InlineToken::new_blod(vec![InlineToken::new_text("bar")]),
+                InlineToken::new_text("baz"),
+            ])]
+        );
+        assert_inline!(
+            "_a_ __b__",
+            vec![
+                InlineToken::new_italic(vec![InlineToken::new_text("a")]),
+                InlineToken::new_text(" "),
+                InlineToken::new_blod(vec![InlineToken::new_text("b")]),
+            ]
+        );
+        assert_inline!("``a`b``", vec![InlineToken::new_code("a`b")]);
+        assert_inline!("`a\\`", vec![InlineToken::new_code("a\\")]);
+        assert_inline!("` a `", vec![InlineToken::new_code("a")]);
+        assert_inline!("`  a  `", vec![InlineToken::new_code(" a ")]);
+        assert_inline!("` a`", vec![InlineToken::new_code(" a")]);
+        assert_inline!("`   `", vec![InlineToken::new_code("   ")]);
+        assert_inline!("`` ` ``", vec![InlineToken::new_code("`")]);
+        assert_inline!(
+            "`[x](y) *z*` (w)",
+            vec![
+                InlineToken::new_code("[x](y) *z*"),
+                InlineToken::new_text(" (w)"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_delimiter_runs() {
+        let italic = |tokens| InlineToken::new_italic(tokens);
+        let bold = |tokens| InlineToken::new_blod(tokens);
+        let text = |text| InlineToken::new_text(text);
+
+        assert_inline!("a **** b", vec![text("a **** b")]);
+        assert_inline!("***a***", vec![italic(vec![bold(vec![text("a")])])]);
+        assert_inline!("___a___", vec![italic(vec![bold(vec![text("a")])])]);
+        assert_inline!("**a***", vec![bold(vec![text("a")]), text("*")]);
+        assert_inline!("*a**", vec![italic(vec![text("a")]), text("*")]);
+        assert_inline!("***a**", vec![text("*"), bold(vec![text("a")])]);
+        assert_inline!(
+            "***a* b**",
+            vec![bold(vec![italic(vec![text("a")]), text(" b")])]
+        );
+        assert_inline!(
+            "foo***bar***baz",
+            vec![
+                text("foo"),
+                italic(vec![bold(vec![text("bar")])]),
+                text("baz")
+            ]
+        );
+        assert_inline!("****a****", vec![bold(vec![bold(vec![text("a")])])]);
+        assert_inline!("*foo**bar*", vec![italic(vec![text("foo**bar")])]);
+        // a run closes the nearest run it matches, the runs in between are text
+        assert_inline!(
+            "*foo *bar**",
+            vec![italic(vec![text("foo "), italic(vec![text("bar")])])]
+        );
+        assert_inline!("*a **b", vec![text("*a **b")]);
+        assert_inline!("_a *b_ c*", vec![italic(vec![text("a *b")]), text(" c*")]);
+        assert_inline!("_foo_bar_", vec![italic(vec![text("foo_bar")])]);
+
+        // the split runs keep their place in the source
+        let document = crate::parse("x ***a***");
+        assert_eq!(document.spans, vec![0..9]);
+    }
+
+    #[test]
+    fn parse_unclosed_links() {
+        let tests = vec![
+            ("# [x](y", TokenKind::ClosingParenthesis, Token::EOF, 7),
+            (
+                "# ![x](y\n",
+                TokenKind::ClosingParenthesis,
+                Token::Whitespace("\n".into()),
+                8,
+            ),
+            ("# [x", TokenKind::ClosingBracket, Token::EOF, 4),
+            ("- [x][y\n- z", TokenKind::ClosingBracket, Token::EOF, 8),
+        ];
+        for (raw, expected, found, offset) in tests {
+            let document = crate::parse(raw);
+            assert_eq!(
+                document.diagnostics()[0].error,
+                ParseError {
+                    expected,
+                    found,
+                    offset
+                }
+            );
+        }
+
+        let error = crate::parse("# [x](y").diagnostics()[0].error.clone();
+        assert_eq!(
+            error.to_string(),
+            "Expected ClosingParenthesis, found EOF at offset 7"
+        );
+    }
+
+    #[test]
+    fn report_bullet_switches() {
+        let list = |text| {
+            Element::new_list(
+                ListKind::Unordered,
+                vec![vec![Element::new_paragraph(vec![InlineToken::new_text(
+                    text,
+                )])]],
+            )
+        };
+        let options = ParserOptions {
+            strict_lists: true,
+            ..ParserOptions::default()
+        };
+
+        let document = crate::parse_with_options("- a\n+ b", options.clone());
+        assert_eq!(document, Document::new(vec![list("a"), list("b")]));
+        assert_eq!(
+            document.diagnostics(),
+            &[Diagnostic {
+                error: ParseError {
+                    expected: TokenKind::Dash,
+                    found: Token::Plus,
+                    offset: 4,
+                },
+                span: 4..5,
+            }]
+        );
+        assert_eq!(
+            document.diagnostics()[0].error.to_string(),
+            "Expected Dash, found Plus at offset 4"
+        );
+        assert!(crate::parse_with_options("- a\n- b\n\n1. c", options)
+            .diagnostics()
+            .is_empty());
+
+        let document = crate::parse("- a\n+ b");
+        assert_eq!(document, Document::new(vec![list("a"), list("b")]));
+        assert!(document.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn report_nested_bullet_switches() {
+        let list = |text| {
+            Element::new_list(
+                ListKind::Unordered,
+                vec![vec![Element::new_paragraph(vec![InlineToken::new_text(
+                    text,
+                )])]],
+            )
+        };
+        let options = ParserOptions {
+            strict_lists: true,
+            ..ParserOptions::default()
+        };
+
+        let document =
+            crate::parse_with_options("> intro\n>\n> - a\n> + b\n>\n> outro", options.clone());
+        assert_eq!(
+            document,
+            Document::new(vec![Element::new_blockquote(vec![
+                Element::new_paragraph(vec![InlineToken::new_text("intro")]),
+                list("a"),
+                list("b"),
+                Element::new_paragraph(vec![InlineToken::new_text("outro")]),
+            ])])
+        );
+        let bullet_switch = |offset| Diagnostic {
+            error: ParseError {
+                expected: TokenKind::Dash,
+                found: Token::Plus,
+                offset,
+            },
+            span: offset..offset + 1,
+        };
+        assert_eq!(document.diagnostics(), &[bullet_switch(18)]);
+
+        let document = crate::parse_with_options("- top\n  - a\n  + b\n- sibling", options);
+        assert_eq!(
+            document,
+            Document::new(vec![Element::new_list(
+                ListKind::Unordered,
+                vec![
+                    vec![
+                        Element::new_paragraph(vec![InlineToken::new_text("top")]),
+                        list("a"),
+                        list("b"),
+                    ],
+                    vec![Element::new_paragraph(vec![InlineToken::new_text(
+                        "sibling"
+                    )])],
+                ]
+            )])
+        );
+        assert_eq!(document.diagnostics(), &[bullet_switch(14)]);
+    }
+
+    #[test]
+    fn recover_from_malformed_blocks() {
+        let document =
+            crate::parse("first paragraph\n\n| a | [b](c |\n| - | - |\n\nlast\nparagraph");
+
+        assert_eq!(
+            document,
+            Document::new(vec![
+                Element::new_paragraph(vec![InlineToken::new_text("first paragraph")]),
+                Element::new_paragraph(vec![InlineToken::new_text("| a | [b](c |\n| - | - |")]),
+                Element::new_paragraph(vec![InlineToken::new_text("last\nparagraph")]),
+            ])
+        );
+        assert_eq!(
+            document.diagnostics(),
+            &[Diagnostic {
+                error: ParseError {
+                    expected: TokenKind::ClosingParenthesis,
+                    found: Token::Whitespace("\n".into()),
+                    offset: 30,
+                },
+                span: 23..30,
+            }]
+        );
+        assert_eq!(document.spans, vec![0..15, 17..40, 42..56]);
+    }
+
+    #[test]
+    fn keep_unclosed_links_as_text() {
+        let text = InlineToken::new_text;
+
+        assert_ast!(
+            "see [note here\n# [a\n# b\n\na [x](y *z*",
+            Document::new(vec![
+                Element::new_paragraph(vec![text("see [note here")]),
+                Element::new_heading(1, vec![text("[a")]),
+                Element::new_heading(1, vec![text("b")]),
+                Element::new_paragraph(vec![
+                    text("a [x](y "),
+                    InlineToken::Italic(vec![text("z")]),
+                ]),
+            ])
+        );
+        // link text and labels continue on the next line of a paragraph, but not past its end
+        assert_ast!(
+            "[a\nb](/x) [c\nd][e]\n\n[e]: /y",
+            Document::new(vec![Element::new_paragraph(vec![
+                InlineToken::Link(Link {
+                    tokens: vec![text("a\nb")],
+                    href: "/x".to_string(),
+                    title: None,
+                }),
+                text(" "),
+                InlineToken::Link(Link {
+                    tokens: vec![text("c\nd")],
+                    href: "/y".to_string(),
+                    title: None,
+                }),
+            ])])
+        );
+        assert_ast!(
+            "[a\n# b](/x)",
+            Document::new(vec![
+                Element::new_paragraph(vec![text("[a")]),
+                Element::new_heading(1, vec![text("b](/x)")]),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_links_in_unclosed_links() {
+        let text = InlineToken::new_text;
+
+        // the brackets and parentheses left open by a link that doesn't close don't close either,
+        // the ones closed in it still make links
+        assert_ast!(
+            "x [a [b](u) [c](v (w",
+            Document::new(vec![Element::new_paragraph(vec![
+                text("x [a "),
+                InlineToken::Link(Link {
+                    tokens: vec![text("b")],
+                    href: "u".to_string(),
+                    title: None,
+                }),
+                text(" [c](v (w"),
+            ])])
+        );
+        // each of them is reported
+        let document = crate::parse("x [a [b [c");
+        let offsets = document.diagnostics().iter().map(|d| d.span.start);
+        assert_eq!(offsets.collect::<Vec<_>>(), vec![2, 5, 8]);
+
+        // images nested too deep are literal text, instead of running out of stack
+        let raw = format!("{}a{}", "![".repeat(5000), "](u)".repeat(5000));
+        assert_eq!(crate::parse(&raw).elements.len(), 1);
+    }
+
+    /// Returns how long parsing `unit` repeated `count` times takes, the best of a few runs
+    fn parse_time(unit: &str, count: usize) -> Duration {
+        let raw = unit.repeat(count);
+        let elapsed = |_| {
+            let start = Instant::now();
+            crate::parse(&raw);
+            start.elapsed()
+        };
+        (0..3).map(elapsed).min().unwrap()
+    }
+
+    #[test]
+    fn parse_in_linear_time() {
+        // four times as much input takes about four times as long, it would take sixteen times as
+        // long if links, definitions or emphasis were parsed again from each bracket, parenthesis
+        // or delimiter
+        for unit in [
+            "[l](u)\n\n",
+            "[a ",
+            "[a](",
+            "![a ",
+            "[a](b (c ",
+            "*a ",
+            "_a ",
+            "[*a* ",
+        ] {
+            let ratio = parse_time(unit, 8000).as_secs_f64() / parse_time(unit, 2000).as_secs_f64();
+            assert!(ratio < 10.0, "{:?} takes {:.1} times as long", unit, ratio);
+        }
+    }
+
+    #[test]
+    fn keep_list_items_with_errors() {
+        let paragraph = |text| Element::new_paragraph(vec![InlineToken::new_text(text)]);
+
+        let document = crate::parse("- a\n- see [x\n- c");
+        assert_eq!(
+            document,
+            Document::new(vec![Element::new_list(
+                ListKind::Unordered,
+                vec![
+                    vec![paragraph("a")],
+                    vec![paragraph("see [x")],
+                    vec![paragraph("c")],
+                ]
+            )])
+        );
+        // the diagnostics inside the items are reported at their place in the source
+        assert_eq!(
+            document.diagnostics(),
+            &[Diagnostic {
+                error: ParseError {
+                    expected: TokenKind::ClosingBracket,
+                    found: Token::EOF,
+                    offset: 13,
+                },
+                span: 10..13,
+            }]
+        );
+
+        let document = crate::parse("1. x\n\n   [y\n2. z");
+        assert_eq!(
+            document.elements,
+            vec![Element::List(List {
+                kind: ListKind::Ordered,
+                start: Some(1),
+                items: vec![vec![paragraph("x"), paragraph("[y")], vec![paragraph("z")]],
+                checked: vec![],
+                loose: true,
+            })]
+        );
+        assert_eq!(document.diagnostics()[0].span, 9..12);
+    }
+
+    #[test]
+    fn parse_blockquotes() {
+        assert_ast!(
+            "> - a\n> - c\n\nafter",
+            Document::new(vec![
+                Element::new_blockquote(vec![Element::new_list(
+                    ListKind::Unordered,
+                    vec![
+                        vec![Element::new_paragraph(vec![InlineToken::new_text("a")])],
+                        vec![Element::new_paragraph(vec![InlineToken::new_text("c")])],
+                    ]
+                )]),
+                Element::new_paragraph(vec![InlineToken::new_text("after")]),
+            ])
+        );
+        assert_ast!(
+            "> > nested\n>\n> 1 > 0",
+            Document::new(vec![Element::new_blockquote(vec![
+                Element::new_blockquote(vec![Element::new_paragraph(vec![InlineToken::new_text(
+                    "nested"
+                )])]),
+                Element::new_paragraph(vec![InlineToken::new_text("1 > 0")]),
+            ])])
+        );
+    }
+
+    #[test]
+    fn strip_marker_space_by_char() {
+        assert_ast!(
+            ">\u{A0}a\n>\tb",
+            Document::new(vec![Element::new_blockquote(vec![Element::new_paragraph(
+                vec![InlineToken::new_text("a\nb")]
+            )])])
+        );
+    }
+
+    #[test]
+    fn limit_container_nesting() {
+        // the markers nested deeper than the limit are text
+        let depth = |document: &Document| {
+            let mut depth = 0;
+            let mut elements = document.elements.clone();
+            loop {
+                match elements.as_slice() {
+                    [Element::BlockQuote(nested)] => elements = nested.clone(),
+                    [Element::List(list)] => elements = list.items[0].clone(),
+                    [Element::Paragraph(paragraph)] => return (depth, paragraph.text()),
+                    _ => unreachable!(),
+                }
+                depth += 1;
+            }
+        };
+
+        for marker in ["> ", "- ", "1. "] {
+            let document = crate::parse(&format!("{}a", marker.repeat(5000)));
+            let (nesting, text) = depth(&document);
+            assert_eq!(nesting, MAX_NESTING, "{:?}", marker);
+            assert!(text.starts_with(marker.trim_end()), "{:?}", marker);
+            assert!(text.ends_with(" a"), "{:?}", marker);
+        }
+    }
+
+    #[test]
+    fn parse_lazy_blockquote_continuation() {
+        assert_ast!(
+            "> a\nlazy\n  > b\n# c",
+            Document::new(vec![
+                Element::new_blockquote(vec![Element::new_paragraph(vec![InlineToken::new_text(
+                    "a\nlazy\nb"
+                )])]),
+                Element::new_heading(1, vec![InlineToken::new_text("c")]),
+            ])
+        );
+        assert_ast!(
+            "> # a\nb\n\n> c\n\nd",
+            Document::new(vec![
+                Element::new_blockquote(vec![Element::new_heading(
+                    1,
+                    vec![InlineToken::new_text("a")]
+                )]),
+                Element::new_paragraph(vec![InlineToken::new_text("b")]),
+                Element::new_blockquote(vec![Element::new_paragraph(vec![InlineToken::new_text(
+                    "c"
+                )])]),
+                Element::new_paragraph(vec![InlineToken::new_text("d")]),
+            ])
+        );
+    }
+
+    #[test]
+    fn default_construction() {
+        assert_eq!(Document::default(), Document::new(vec![]));
+        assert!(Document::default().spans.is_empty());
+
+        let mut chars = CharIterator::default();
+        chars.read_from_str("# a\n\nb", None);
+        let mut tokenizer = Tokenizer::new(&mut chars);
+        assert_eq!(
+            Parser::with_options(&mut tokenizer, ParserOptions::default()).parse(),
+            Document::new(vec![
+                Element::new_heading(1, vec![InlineToken::new_text("a")]),
+                Element::new_paragraph(vec![InlineToken::new_text("b")]),
+            ])
+        );
+    }
+
+    #[test]
+    fn flatten_text_content() {
+        let heading = Heading {
+            level: 1,
+            tokens: vec![
+                InlineToken::new_blod(vec![InlineToken::new_text("bold")]),
+                InlineToken::new_text(" text"),
+            ],
+            attributes: HeadingAttrs::default(),
+        };
+        assert_eq!(heading.text(), "bold text");
+
+        let document = crate::parse("*a* [**b** c](d) ![e](f) `*g*`");
+        match &document.elements[0] {
+            Element::Paragraph(paragraph) => assert_eq!(paragraph.text(), "a b c e *g*"),
+            element => panic!("expected a paragraph, found {:?}", element),
+        }
+    }
+
+    #[test]
+    fn iterate_document() {
+        let mut document = crate::parse("# a\n- b\n---\n## c");
+
+        let mut headings = 0;
+        for element in &document {
+            if matches!(element, Element::Heading(_)) {
+                headings += 1;
+            }
+        }
+        assert_eq!(headings, 2);
+        assert_eq!((&document).into_iter().count(), 4);
+
+        for element in &mut document {
+            if let Element::Heading(heading) = element {
+                heading.level += 1;
+            }
+        }
+        assert_eq!(
+            document.elements[3],
+            Element::new_heading(3, vec![InlineToken::new_text("c")])
+        );
+    }
+
+    #[test]
+    fn parse_tilde_code_blocks() {
+        assert_ast!(
+            "~~~markdown\n```rust\nlet x = 1;\n```\n~~~\n# after",
+            Document::new(vec![
+                Element::new_code_block(Some("markdown"), "```rust\nlet x = 1;\n```\n"),
+                Element::new_heading(1, vec![InlineToken::new_text("after")]),
+            ])
+        );
+        assert_ast!(
+            "~~~~\n~~~\n```\n~~~~~",
+            Document::new(vec![Element::new_code_block(None, "~~~\n```\n")])
+        );
+        assert_ast!(
+            "```\n~~~\n```",
+            Document::new(vec![Element::new_code_block(None, "~~~\n")])
+        );
     }
 }
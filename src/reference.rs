@@ -0,0 +1,300 @@
+use crate::entity::decode_entities;
+
+/// The target of a reference link, defined by a `[label]: destination "title"` line
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LinkDefinition {
+    pub(crate) href: String,
+    pub(crate) title: Option<String>,
+}
+
+impl LinkDefinition {
+    pub fn new(href: &str, title: Option<&str>) -> Self {
+        Self {
+            href: href.to_string(),
+            title: title.map(|title| title.to_string()),
+        }
+    }
+}
+
+/// Returns the form of a label that references match definitions by: the words of the label, in
+/// lowercase, separated by a single space. `[Foo  Bar]` refers to `[foo bar]: /url`. Only spaces,
+/// tabs and line endings separate words, a non-breaking space is part of the word.
+pub fn normalize_label(label: &str) -> String {
+    // going through uppercase folds characters with no single lowercase form, `ẞ` matches `ss`
+    label
+        .split([' ', '\t', '\n', '\r'])
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_uppercase()
+        .to_lowercase()
+}
+
+/// Matches a link reference definition at the start of `source`. Returns the label, normalized
+/// by `normalize_label`, the definition and the number of chars the definition spans. The
+/// destination and the title may each be on a continuation line, the definition ends at the end
+/// of the line holding its last part.
+pub fn parse_definition(source: &str) -> Option<(String, LinkDefinition, usize)> {
+    let chars = source.chars().collect::<Vec<_>>();
+    let mut pos = 0;
+
+    // [label]:
+    if chars.first() != Some(&'[') {
+        return None;
+    }
+    pos += 1;
+    let label_start = pos;
+    while pos < chars.len() && chars[pos] != ']' {
+        match chars[pos] {
+            '[' => return None,
+            '\\' => pos += 2,
+            _ => pos += 1,
+        }
+    }
+    if pos >= chars.len() {
+        return None;
+    }
+    let label = normalize_label(&chars[label_start..pos].iter().collect::<String>());
+    if label.is_empty() {
+        return None;
+    }
+    pos += 1;
+    if chars.get(pos) != Some(&':') {
+        return None;
+    }
+    pos += 1;
+
+    // destination, possibly on the next line
+    skip_spaces(&chars, &mut pos, true);
+    let href = if chars.get(pos) == Some(&'<') {
+        pos += 1;
+        let start = pos;
+        while pos < chars.len() && !matches!(chars[pos], '>' | '<' | '\n') {
+            pos += 1;
+        }
+        if chars.get(pos) != Some(&'>') {
+            return None;
+        }
+        pos += 1;
+        chars[start..pos - 1].iter().collect::<String>()
+    } else {
+        let start = pos;
+        while pos < chars.len() && !chars[pos].is_whitespace() {
+            pos += 1;
+        }
+        if start == pos {
+            return None;
+        }
+        chars[start..pos].iter().collect::<String>()
+    };
+    let href = unescape(&href);
+    let destination_end = pos;
+
+    // an optional title, on the same line or the next one
+    let mut title_start = pos;
+    skip_spaces(&chars, &mut title_start, true);
+    if title_start > destination_end {
+        if let Some((title, end)) = parse_title(&chars, title_start) {
+            if at_line_end(&chars, end) {
+                return Some((
+                    label,
+                    LinkDefinition {
+                        href,
+                        title: Some(title),
+                    },
+                    end,
+                ));
+            }
+        }
+    }
+
+    // without a title, nothing but whitespace may follow the destination on its line
+    if at_line_end(&chars, destination_end) {
+        return Some((label, LinkDefinition { href, title: None }, destination_end));
+    }
+
+    None
+}
+
+/// Splits the text between the parentheses of an inline link, `/url "title"`, into its
+/// destination and its title, both unescaped. Without a title the whole text is the destination.
+pub(crate) fn split_destination(text: &str) -> (String, Option<String>) {
+    let text = text.trim();
+    let chars = text.chars().collect::<Vec<_>>();
+    for (pos, char) in chars.iter().enumerate() {
+        if !char.is_whitespace() {
+            continue;
+        }
+        let mut start = pos;
+        skip_spaces(&chars, &mut start, false);
+        if let Some((title, end)) = parse_title(&chars, start) {
+            if end == chars.len() {
+                let destination = chars[..pos].iter().collect::<String>();
+                return (unescape(&destination), Some(title));
+            }
+        }
+    }
+
+    (unescape(text), None)
+}
+
+/// Parses a `"title"`, `'title'` or `(title)` starting at `start`. Returns the title and the
+/// position after its closing delimiter. Titles may span lines but not blank lines.
+fn parse_title(chars: &[char], start: usize) -> Option<(String, usize)> {
+    let close = match chars.get(start)? {
+        '"' => '"',
+        '\'' => '\'',
+        '(' => ')',
+        _ => return None,
+    };
+
+    let mut pos = start + 1;
+    while pos < chars.len() && chars[pos] != close {
+        if chars[pos] == '\n' && chars[pos - 1] == '\n' {
+            return None;
+        }
+        // an escaped delimiter doesn't close the title
+        match chars[pos] == '\\' && pos + 1 < chars.len() {
+            true => pos += 2,
+            false => pos += 1,
+        }
+    }
+
+    if pos >= chars.len() {
+        return None;
+    }
+
+    let title = unescape(&chars[start + 1..pos].iter().collect::<String>());
+    Some((title, pos + 1))
+}
+
+/// Resolves the backslash escapes of `text` and decodes its entity references, unless their `&`
+/// is escaped
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    // the text since the last escaped character, whose entities are yet to be decoded
+    let mut raw = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(char) = chars.next() {
+        let escaped = match char {
+            '\\' => chars.next_if(char::is_ascii_punctuation),
+            _ => None,
+        };
+        match escaped {
+            Some(escaped) => {
+                unescaped.push_str(&decode_entities(&std::mem::take(&mut raw)));
+                unescaped.push(escaped);
+            }
+            None => raw.push(char),
+        }
+    }
+    unescaped.push_str(&decode_entities(&raw));
+
+    unescaped
+}
+
+/// Skips spaces and tabs, and when `newline` is set a single line ending as well
+fn skip_spaces(chars: &[char], pos: &mut usize, newline: bool) {
+    let mut newline = newline;
+    while *pos < chars.len() {
+        match chars[*pos] {
+            ' ' | '\t' => *pos += 1,
+            '\n' if newline => {
+                newline = false;
+                *pos += 1;
+            }
+            _ => break,
+        }
+    }
+}
+
+/// Returns true when only spaces and tabs follow `pos` up to the end of its line
+fn at_line_end(chars: &[char], pos: usize) -> bool {
+    let mut pos = pos;
+    skip_spaces(chars, &mut pos, false);
+
+    pos >= chars.len() || chars[pos] == '\n'
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_single_line_definition() {
+        assert_eq!(
+            parse_definition("[foo]: /url \"title\"\nrest"),
+            Some((
+                "foo".to_string(),
+                LinkDefinition::new("/url", Some("title")),
+                19
+            ))
+        );
+        assert_eq!(
+            parse_definition("[foo]: <my url>"),
+            Some(("foo".to_string(), LinkDefinition::new("my url", None), 15))
+        );
+    }
+
+    #[test]
+    fn parse_wrapped_definition() {
+        let source = "[id]:\n  http://url\n  \"title\"";
+        assert_eq!(
+            parse_definition(source),
+            Some((
+                "id".to_string(),
+                LinkDefinition::new("http://url", Some("title")),
+                source.len()
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_definition_with_invalid_title() {
+        // the title line is not part of the definition, it is an ordinary paragraph
+        assert_eq!(
+            parse_definition("[id]: /url\n\"title\" ok"),
+            Some(("id".to_string(), LinkDefinition::new("/url", None), 10))
+        );
+        assert_eq!(parse_definition("[id]: /url \"title\" ok"), None);
+    }
+
+    #[test]
+    fn reject_non_definitions() {
+        assert_eq!(parse_definition("[id] /url"), None);
+        assert_eq!(parse_definition("[]: /url"), None);
+        assert_eq!(parse_definition("[id]:"), None);
+        assert_eq!(parse_definition("[id]:\n\n/url"), None);
+    }
+
+    #[test]
+    fn unescape_destinations() {
+        let (_, definition, _) = parse_definition("[x]: /a\\&amp;b&amp;c\\*").unwrap();
+        assert_eq!(definition.href, "/a&amp;b&c*");
+
+        assert_eq!(split_destination(" /a b "), ("/a b".to_string(), None));
+        assert_eq!(
+            split_destination("/a\\&amp; 'b' (c)"),
+            ("/a&amp; 'b'".to_string(), Some("c".to_string()))
+        );
+        assert_eq!(split_destination("/a \"b"), ("/a \"b".to_string(), None));
+    }
+
+    #[test]
+    fn normalize_labels() {
+        let tests = vec![
+            ("Foo", "foo"),
+            ("  a \t b\n c ", "a b c"),
+            ("ÄÖÜ", "äöü"),
+            ("Straße", "strasse"),
+            ("a\u{A0}b", "a\u{A0}b"),
+        ];
+        for (label, normalized) in tests {
+            assert_eq!(normalize_label(label), normalized, "{:?}", label);
+        }
+        assert_eq!(
+            parse_definition("[Foo\n  Bar]: /url").map(|(label, ..)| label),
+            Some("foo bar".to_string())
+        );
+    }
+}
@@ -0,0 +1,185 @@
+//! The document traversal shared by the renderers. A renderer implements `Writer` and only decides
+//! what to emit when the traversal enters and leaves each node.
+
+use crate::parser::{Alignment, Element, InlineToken, List, Table, TableCell};
+
+/// A node of a document as visited by `walk`
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Node<'a> {
+    Element(&'a Element),
+    /// The item at the given index of a list
+    Item(&'a List, usize),
+    /// The row at the given index of the body of a table, or its header row when there is none
+    Row(&'a Table, Option<usize>),
+    /// A cell of a table, `header` telling whether it belongs to the header row
+    Cell {
+        header: bool,
+        alignment: Alignment,
+    },
+    Inline(&'a InlineToken),
+}
+
+/// Receives the nodes of a document from `walk`, in document order
+pub(crate) trait Writer {
+    /// Called when the traversal reaches a node, before its children. Returning false skips the
+    /// children, for writers that render the whole node at once.
+    fn enter(&mut self, node: Node<'_>) -> bool;
+
+    /// Called after the children of the node, or right after `enter` when they were skipped
+    fn leave(&mut self, node: Node<'_>);
+}
+
+/// Walks the elements and everything they contain depth first. Every row of a table is given as
+/// many cells as its header row: missing cells are visited empty and extra ones are left out.
+pub(crate) fn walk<W: Writer + ?Sized>(elements: &[Element], writer: &mut W) {
+    for element in elements {
+        walk_element(element, writer);
+    }
+}
+
+fn walk_element<W: Writer + ?Sized>(element: &Element, writer: &mut W) {
+    let node = Node::Element(element);
+    if writer.enter(node) {
+        match element {
+            Element::Heading(heading) => walk_inline(&heading.tokens, writer),
+            Element::Paragraph(paragraph) => walk_inline(&paragraph.0, writer),
+            Element::List(list) => {
+                for (index, item) in list.items.iter().enumerate() {
+                    let node = Node::Item(list, index);
+                    if writer.enter(node) {
+                        walk(item, writer);
+                    }
+                    writer.leave(node);
+                }
+            }
+            Element::BlockQuote(elements) => walk(elements, writer),
+            Element::Table(table) => {
+                walk_row(table, None, writer);
+                for row in 0..table.rows.len() {
+                    walk_row(table, Some(row), writer);
+                }
+            }
+            Element::CodeBlock(_)
+            | Element::ThematicBreak
+            | Element::Comment(_)
+            | Element::Html(_) => {}
+        }
+    }
+    writer.leave(node);
+}
+
+fn walk_row<W: Writer + ?Sized>(table: &Table, row: Option<usize>, writer: &mut W) {
+    let node = Node::Row(table, row);
+    if writer.enter(node) {
+        let cells: &[TableCell] = match row {
+            Some(row) => &table.rows[row],
+            None => &table.header,
+        };
+        for column in 0..table.header.len() {
+            let node = Node::Cell {
+                header: row.is_none(),
+                alignment: table
+                    .alignments
+                    .get(column)
+                    .copied()
+                    .unwrap_or(Alignment::None),
+            };
+            if writer.enter(node) {
+                walk_inline(cells.get(column).map_or(&[][..], |cell| &cell[..]), writer);
+            }
+            writer.leave(node);
+        }
+    }
+    writer.leave(node);
+}
+
+fn walk_inline<W: Writer + ?Sized>(tokens: &[InlineToken], writer: &mut W) {
+    for token in tokens {
+        let node = Node::Inline(token);
+        if writer.enter(node) {
+            match token {
+                InlineToken::Bold(tokens) | InlineToken::Italic(tokens) => {
+                    walk_inline(tokens, writer)
+                }
+                InlineToken::Link(link) => walk_inline(&link.tokens, writer),
+                InlineToken::Text(_)
+                | InlineToken::Code(_)
+                | InlineToken::Image(_)
+                | InlineToken::Comment(_)
+                | InlineToken::LineBreak => {}
+            }
+        }
+        writer.leave(node);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    /// Tallies the elements and inline tokens it is given, by kind
+    #[derive(Default)]
+    struct CountingWriter {
+        counts: Vec<(&'static str, usize)>,
+        depth: usize,
+        max_depth: usize,
+    }
+
+    impl CountingWriter {
+        fn count(&self, kind: &str) -> usize {
+            self.counts
+                .iter()
+                .find(|(counted, _)| *counted == kind)
+                .map_or(0, |(_, count)| *count)
+        }
+    }
+
+    impl Writer for CountingWriter {
+        fn enter(&mut self, node: Node<'_>) -> bool {
+            let kind = match node {
+                Node::Element(Element::Heading(_)) => "heading",
+                Node::Element(Element::Paragraph(_)) => "paragraph",
+                Node::Element(Element::List(_)) => "list",
+                Node::Element(Element::BlockQuote(_)) => "quote",
+                Node::Element(_) => "other",
+                Node::Item(..) => "item",
+                Node::Row(..) | Node::Cell { .. } => "table",
+                Node::Inline(InlineToken::Text(_)) => "text",
+                Node::Inline(_) => "inline",
+            };
+            match self.counts.iter_mut().find(|(counted, _)| *counted == kind) {
+                Some((_, count)) => *count += 1,
+                None => self.counts.push((kind, 1)),
+            }
+
+            self.depth += 1;
+            self.max_depth = self.max_depth.max(self.depth);
+            // the content of block quotes isn't counted
+            !matches!(node, Node::Element(Element::BlockQuote(_)))
+        }
+
+        fn leave(&mut self, _: Node<'_>) {
+            self.depth -= 1;
+        }
+    }
+
+    #[test]
+    fn counting_writer() {
+        let document = parse("# *a* b\n\n- c\n- d\n  - **e**\n\n> f\n>\n> g\n\n```\nh\n```");
+        let mut writer = CountingWriter::default();
+        walk(&document.elements, &mut writer);
+
+        assert_eq!(writer.count("heading"), 1);
+        assert_eq!(writer.count("list"), 2);
+        assert_eq!(writer.count("item"), 3);
+        assert_eq!(writer.count("paragraph"), 3);
+        assert_eq!(writer.count("quote"), 1);
+        assert_eq!(writer.count("other"), 1);
+        assert_eq!(writer.count("inline"), 2);
+        assert_eq!(writer.count("text"), 5);
+        // list, item, list, item, paragraph, bold, text
+        assert_eq!(writer.max_depth, 7);
+        assert_eq!(writer.depth, 0);
+    }
+}
@@ -0,0 +1,215 @@
+use crate::parse_with_options;
+use crate::parser::{Diagnostic, Document, ParseError, ParserOptions};
+use std::ops::Range;
+
+/// A change to the source of a document: the chars in `range` are replaced by `text`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TextEdit {
+    /// Source offsets in chars, like the spans of elements
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+impl TextEdit {
+    pub fn new(range: Range<usize>, text: &str) -> Self {
+        Self {
+            range,
+            text: text.to_string(),
+        }
+    }
+
+    /// Returns the source with the edit applied. Offsets past the end of the source stand for
+    /// its end.
+    pub fn apply(&self, source: &str) -> String {
+        let start = byte_offset(source, self.range.start);
+        let end = byte_offset(source, self.range.end).max(start);
+
+        format!("{}{}{}", &source[..start], self.text, &source[end..])
+    }
+}
+
+impl Document {
+    /// Parses `source` after the edit, reusing the elements of this document, which was parsed
+    /// from `source`, see `reparse_range_with_options`
+    pub fn reparse_range(&self, source: &str, edit: TextEdit) -> Document {
+        self.reparse_range_with_options(source, edit, ParserOptions::default())
+    }
+
+    /// Parses `source` after the edit like `parse_with_options`, reusing the elements of this
+    /// document, which was parsed from `source` with the same options. The elements ending before
+    /// the edit are kept but for the last of them, which the edit may continue, like a paragraph it
+    /// underlines. The source is parsed again from the line of that element to its end.
+    ///
+    /// The whole source is parsed again when the edit may change elements anywhere: when the
+    /// source holds link reference definitions, when the edit may open or close an HTML comment,
+    /// and when the edit is in the first element, which may follow front matter.
+    pub fn reparse_range_with_options(
+        &self,
+        source: &str,
+        edit: TextEdit,
+        options: ParserOptions,
+    ) -> Document {
+        let edited = edit.apply(source);
+        let Some((kept, start)) = self.reparse_start(source, &edited, &edit) else {
+            return parse_with_options(&edited, options);
+        };
+
+        let rest = parse_with_options(
+            &edited[byte_offset(&edited, start)..],
+            ParserOptions {
+                front_matter: false,
+                retain_source: false,
+                ..options.clone()
+            },
+        );
+        let shift = |span: &Range<usize>| span.start + start..span.end + start;
+
+        let mut elements = self.elements[..kept].to_vec();
+        elements.extend(rest.elements);
+        let mut spans = self.spans[..kept].to_vec();
+        spans.extend(rest.spans.iter().map(shift));
+        let mut descendant_spans = self.descendant_spans[..kept].to_vec();
+        descendant_spans.extend(
+            rest.descendant_spans
+                .iter()
+                .map(|spans| spans.iter().map(shift).collect()),
+        );
+        let mut diagnostics = self
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.span.start < start)
+            .cloned()
+            .collect::<Vec<_>>();
+        diagnostics.extend(rest.diagnostics.iter().map(|diagnostic| Diagnostic {
+            error: ParseError {
+                offset: diagnostic.error.offset + start,
+                ..diagnostic.error.clone()
+            },
+            span: shift(&diagnostic.span),
+        }));
+
+        Document {
+            elements,
+            front_matter: self.front_matter.clone(),
+            spans,
+            descendant_spans,
+            source: options.retain_source.then_some(edited),
+            diagnostics,
+        }
+    }
+
+    /// Returns the number of elements kept after the edit and the offset in chars where the
+    /// source is parsed again, at the start of a line. `None` when the whole source is.
+    fn reparse_start(&self, source: &str, edited: &str, edit: &TextEdit) -> Option<(usize, usize)> {
+        // spans are missing or out of step for documents that weren't parsed or were edited
+        if self.spans.len() != self.elements.len() {
+            return None;
+        }
+        // a definition resolves references before it as well as after it
+        if source.contains("]:") || edited.contains("]:") {
+            return None;
+        }
+        // the markers may be split by the edit, so the chars around it are checked as well
+        let inserted = edit.text.chars().count();
+        let around = |text: &str, end: usize| {
+            let from = edit.range.start.saturating_sub(3);
+            let window = text
+                .chars()
+                .skip(from)
+                .take(end + 3 - from)
+                .collect::<String>();
+            window.contains("<!--") || window.contains("-->")
+        };
+        if around(source, edit.range.end.max(edit.range.start))
+            || around(edited, edit.range.start + inserted)
+        {
+            return None;
+        }
+
+        let first = self
+            .spans
+            .iter()
+            .position(|span| span.end >= edit.range.start)
+            .unwrap_or(self.spans.len());
+        let kept = first.checked_sub(1).filter(|kept| *kept > 0)?;
+
+        let line_start = source
+            .chars()
+            .take(self.spans[kept].start)
+            .enumerate()
+            .filter(|(_, char)| *char == '\n')
+            .last()
+            .map_or(0, |(newline, _)| newline + 1);
+
+        Some((kept, line_start))
+    }
+}
+
+/// Returns the byte offset of the char at `offset`, the length of the text for offsets past its
+/// end
+fn byte_offset(text: &str, offset: usize) -> usize {
+    text.char_indices()
+        .nth(offset)
+        .map_or(text.len(), |(index, _)| index)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+    use crate::parser::{Element, InlineToken};
+
+    #[test]
+    fn reparse_edited_paragraph() {
+        let source = "# Title\n\nfirst paragraph\n\nsecond paragraph\n\n- a\n- b\n\nlast";
+        let document = parse(source);
+        // "second" -> "2nd"
+        let edit = TextEdit::new(26..32, "2nd");
+        let reparsed = document.reparse_range(source, edit.clone());
+
+        assert_eq!(reparsed, parse(&edit.apply(source)));
+        assert_eq!(reparsed.spans, parse(&edit.apply(source)).spans);
+        assert_eq!(
+            reparsed.descendant_spans,
+            parse(&edit.apply(source)).descendant_spans
+        );
+        let changed = (0..document.elements.len())
+            .filter(|index| document.elements[*index] != reparsed.elements[*index])
+            .collect::<Vec<_>>();
+        assert_eq!(changed, vec![2]);
+        assert_eq!(
+            reparsed.elements[2],
+            Element::new_paragraph(vec![InlineToken::new_text("2nd paragraph")])
+        );
+    }
+
+    #[test]
+    fn reparse_into_previous_block() {
+        let tests = vec![
+            // underlines the paragraph before
+            ("a\n\nb\n# c\n\nd", TextEdit::new(6..9, "===")),
+            // joins the paragraphs
+            ("a\n\nb\n\nc\n\nd", TextEdit::new(4..6, "\n")),
+            // opens a code block running to the end
+            ("a\n\nb\n\nc\n\nd", TextEdit::new(3..4, "```")),
+            // at the end of the source
+            ("a\n\nb\n\nc", TextEdit::new(8..8, "\n---")),
+            // a definition changes a link before it
+            ("a\n\nb\n\n[c]\n\nd", TextEdit::new(11..11, "\n\n[c]: /c")),
+            // a comment spanning blocks
+            ("<!-- a\n\nb\n\nc\n\nd", TextEdit::new(12..12, "-->")),
+        ];
+        for (source, edit) in tests {
+            let edited = edit.apply(source);
+            let reparsed = parse(source).reparse_range(source, edit);
+            assert_eq!(reparsed, parse(&edited), "{:?}", edited);
+            assert_eq!(reparsed.spans, parse(&edited).spans, "{:?}", edited);
+            assert_eq!(
+                reparsed.descendant_spans,
+                parse(&edited).descendant_spans,
+                "{:?}",
+                edited
+            );
+        }
+    }
+}
@@ -0,0 +1,333 @@
+/// Elements dropped along with their content
+const DROPPED_TAGS: &[&str] = &[
+    "script", "style", "iframe", "object", "embed", "noscript", "template",
+];
+
+/// Elements kept in sanitized HTML, any other tag is removed while its content is kept
+const ALLOWED_TAGS: &[&str] = &[
+    "a",
+    "abbr",
+    "b",
+    "blockquote",
+    "br",
+    "code",
+    "dd",
+    "del",
+    "details",
+    "div",
+    "dl",
+    "dt",
+    "em",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "i",
+    "img",
+    "kbd",
+    "li",
+    "ol",
+    "p",
+    "pre",
+    "s",
+    "span",
+    "strong",
+    "sub",
+    "summary",
+    "sup",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "tr",
+    "u",
+    "ul",
+];
+
+/// Attributes kept on allowed elements, which leaves out event handlers and inline styles
+const ALLOWED_ATTRIBUTES: &[&str] = &[
+    "align", "alt", "class", "colspan", "href", "id", "rowspan", "src", "start", "title",
+];
+
+/// Attributes holding a URL, see `sanitize_url`
+const URL_ATTRIBUTES: &[&str] = &["href", "src"];
+
+const ALLOWED_SCHEMES: &[&str] = &["http", "https", "mailto", "ftp", "tel"];
+
+/// Returns the URL when it is relative or uses a safe scheme, `#` otherwise. Whitespace and
+/// control characters are ignored when looking for the scheme, browsers skip them as well.
+pub fn sanitize_url(url: &str) -> &str {
+    let normalized = url
+        .chars()
+        .filter(|char| !char.is_ascii_whitespace() && !char.is_ascii_control())
+        .collect::<String>()
+        .to_ascii_lowercase();
+
+    let scheme = match normalized.split_once(':') {
+        Some((scheme, _)) => scheme,
+        None => return url,
+    };
+    let is_scheme = scheme.starts_with(|char: char| char.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || matches!(char, '+' | '-' | '.'));
+
+    match !is_scheme || ALLOWED_SCHEMES.contains(&scheme) {
+        true => url,
+        false => "#",
+    }
+}
+
+/// Filters raw HTML against an allowlist: scripts and similar elements are dropped with their
+/// content, unknown tags and comments are removed, attributes are limited to harmless ones and
+/// URLs go through `sanitize_url`.
+pub fn sanitize_html(html: &str) -> String {
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.split_once("-->").map_or("", |(_, after)| after);
+            continue;
+        }
+
+        let tag = match parse_tag(rest) {
+            Some(tag) => tag,
+            None => {
+                output.push_str("&lt;");
+                rest = &rest[1..];
+                continue;
+            }
+        };
+        rest = &rest[tag.length..];
+
+        if DROPPED_TAGS.contains(&tag.name.as_str()) {
+            if !tag.closing && !tag.self_closing {
+                rest = skip_element(rest, &tag.name);
+            }
+            continue;
+        }
+
+        if ALLOWED_TAGS.contains(&tag.name.as_str()) {
+            output.push_str(&render_tag(&tag));
+        }
+    }
+    output.push_str(rest);
+
+    output
+}
+
+struct Tag {
+    /// Lowercase tag name
+    name: String,
+    closing: bool,
+    self_closing: bool,
+    /// Lowercase attribute names with their unquoted values
+    attributes: Vec<(String, Option<String>)>,
+    /// Length of the tag in bytes
+    length: usize,
+}
+
+/// Returns the lowercase name of the complete tag at the start of `source`, like `<div class="a">`
+/// or `</p>`, along with its length in bytes
+pub(crate) fn tag_at(source: &str) -> Option<(String, usize)> {
+    source
+        .starts_with('<')
+        .then(|| parse_tag(source))
+        .flatten()
+        .map(|tag| (tag.name, tag.length))
+}
+
+/// Parses the tag at the start of `source`, which starts with `<`
+fn parse_tag(source: &str) -> Option<Tag> {
+    let bytes = source.as_bytes();
+    let mut i = 1;
+
+    let closing = bytes.get(i) == Some(&b'/');
+    if closing {
+        i += 1;
+    }
+
+    let name_start = i;
+    while bytes
+        .get(i)
+        .is_some_and(|byte| byte.is_ascii_alphanumeric())
+    {
+        i += 1;
+    }
+    if !bytes.get(name_start)?.is_ascii_alphabetic() {
+        return None;
+    }
+    let name = source[name_start..i].to_ascii_lowercase();
+
+    let mut attributes = Vec::new();
+    loop {
+        while bytes.get(i).is_some_and(|byte| byte.is_ascii_whitespace()) {
+            i += 1;
+        }
+
+        let self_closing = match bytes.get(i)? {
+            b'>' => false,
+            b'/' if bytes.get(i + 1) == Some(&b'>') => {
+                i += 1;
+                true
+            }
+            _ => {
+                let (attribute, length) = parse_attribute(&source[i..])?;
+                attributes.push(attribute);
+                i += length;
+                continue;
+            }
+        };
+
+        return Some(Tag {
+            name,
+            closing,
+            self_closing,
+            attributes,
+            length: i + 1,
+        });
+    }
+}
+
+/// Parses an attribute with an optional quoted or unquoted value, returning it along with its
+/// length in bytes
+fn parse_attribute(source: &str) -> Option<((String, Option<String>), usize)> {
+    let bytes = source.as_bytes();
+    let ends_name = |byte: &u8| byte.is_ascii_whitespace() || matches!(byte, b'=' | b'>' | b'/');
+
+    let mut i = 0;
+    while bytes.get(i).is_some_and(|byte| !ends_name(byte)) {
+        i += 1;
+    }
+    if i == 0 {
+        return None;
+    }
+    let name = source[..i].to_ascii_lowercase();
+
+    if bytes.get(i) != Some(&b'=') {
+        return Some(((name, None), i));
+    }
+    i += 1;
+
+    let value = match bytes.get(i)? {
+        quote @ (b'"' | b'\'') => {
+            let end = i + 1 + source[i + 1..].find(*quote as char)?;
+            let value = &source[i + 1..end];
+            i = end + 1;
+            value
+        }
+        _ => {
+            let start = i;
+            while bytes
+                .get(i)
+                .is_some_and(|byte| !byte.is_ascii_whitespace() && *byte != b'>')
+            {
+                i += 1;
+            }
+            &source[start..i]
+        }
+    };
+
+    Some(((name, Some(value.to_string())), i))
+}
+
+/// Skips everything up to and including the closing tag of the element `name`, or the rest of the
+/// input when it isn't closed
+fn skip_element<'a>(source: &'a str, name: &str) -> &'a str {
+    let closing = format!("</{}", name);
+    let start = match source.to_ascii_lowercase().find(&closing) {
+        Some(start) => start,
+        None => return "",
+    };
+
+    match source[start..].find('>') {
+        Some(end) => &source[start + end + 1..],
+        None => "",
+    }
+}
+
+fn render_tag(tag: &Tag) -> String {
+    if tag.closing {
+        return format!("</{}>", tag.name);
+    }
+
+    let mut html = format!("<{}", tag.name);
+    for (name, value) in &tag.attributes {
+        if !ALLOWED_ATTRIBUTES.contains(&name.as_str()) {
+            continue;
+        }
+
+        match value {
+            Some(value) if URL_ATTRIBUTES.contains(&name.as_str()) => html.push_str(&format!(
+                " {}=\"{}\"",
+                name,
+                sanitize_url(value).replace('"', "&quot;")
+            )),
+            Some(value) => {
+                html.push_str(&format!(" {}=\"{}\"", name, value.replace('"', "&quot;")))
+            }
+            None => html.push_str(&format!(" {}", name)),
+        }
+    }
+    html.push_str(if tag.self_closing { " />" } else { ">" });
+
+    html
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sanitize_urls() {
+        let tests = vec![
+            ("https://example.com", "https://example.com"),
+            ("mailto:me@example.com", "mailto:me@example.com"),
+            ("/relative/path?a=b:c", "/relative/path?a=b:c"),
+            ("#anchor", "#anchor"),
+            ("javascript:alert(1)", "#"),
+            ("JavaScript:alert(1)", "#"),
+            (" java\tscript:alert(1)", "#"),
+            ("vbscript:msgbox", "#"),
+            ("data:text/html;base64,PHNjcmlwdD4=", "#"),
+        ];
+        for (url, sanitized) in tests {
+            assert_eq!(sanitize_url(url), sanitized, "{}", url);
+        }
+    }
+
+    #[test]
+    fn sanitize_raw_html() {
+        let tests = vec![
+            ("<script>alert(1)</script><p>kept</p>", "<p>kept</p>"),
+            ("a<SCRIPT src=x>b</Script >c", "ac"),
+            ("<script>never closed", ""),
+            (
+                "<p onclick=\"steal()\" class='note'>hi</p>",
+                "<p class=\"note\">hi</p>",
+            ),
+            (
+                "<a href=\"javascript:alert(1)\" title=x>a</a>",
+                "<a href=\"#\" title=\"x\">a</a>",
+            ),
+            (
+                "<img src=\"/a.png\" onerror=\"x()\"/>",
+                "<img src=\"/a.png\" />",
+            ),
+            ("<font color=red>text</font><!-- note -->", "text"),
+            ("1 < 2 <3", "1 &lt; 2 &lt;3"),
+        ];
+        for (html, sanitized) in tests {
+            assert_eq!(sanitize_html(html), sanitized, "{}", html);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use crate::parser::{plain_text, Heading};
+use std::collections::HashMap;
+
+/// Turns heading text into an anchor slug: lowercase alphanumerics with words joined by `-`.
+/// Punctuation other than `-` and `_` is dropped.
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for char in text.trim().chars() {
+        if char.is_alphanumeric() || char == '_' || char == '-' {
+            slug.extend(char.to_lowercase());
+        } else if char.is_whitespace() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    slug
+}
+
+/// Generates slugs that are unique within a document by appending `-1`, `-2`, ... to repeated ones
+#[derive(Debug, Default)]
+pub struct Slugger {
+    /// The ids taken so far, along with the last suffix appended to them
+    seen: HashMap<String, usize>,
+}
+
+impl Slugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn slug(&mut self, text: &str) -> String {
+        let base = slugify(text);
+        let mut slug = base.clone();
+
+        let mut count = self.seen.get(&base).copied().unwrap_or(0);
+        while self.seen.contains_key(&slug) {
+            count += 1;
+            slug = format!("{}-{}", base, count);
+        }
+        if slug != base {
+            self.seen.insert(base, count);
+        }
+        self.seen.insert(slug.clone(), 0);
+
+        slug
+    }
+
+    /// Returns the id of `heading`: its explicit `{#id}`, or else the slug of its text. An explicit
+    /// id is taken like a slug, so that the headings slugged after it get another one.
+    pub fn heading_id(&mut self, heading: &Heading) -> String {
+        match &heading.attributes.id {
+            Some(id) => {
+                self.seen.entry(id.clone()).or_insert(0);
+                id.clone()
+            }
+            None => self.slug(&plain_text(&heading.tokens)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn slugify_text() {
+        assert_eq!(slugify("My Heading"), "my-heading");
+        assert_eq!(slugify("  What's new?  "), "whats-new");
+        assert_eq!(slugify("snake_case & kebab-case"), "snake_case-kebab-case");
+        assert_eq!(slugify("Ünïcode Tïtle"), "ünïcode-tïtle");
+    }
+
+    #[test]
+    fn deduplicate_slugs() {
+        let mut slugger = Slugger::new();
+
+        assert_eq!(slugger.slug("Intro"), "intro");
+        assert_eq!(slugger.slug("Intro"), "intro-1");
+        assert_eq!(slugger.slug("Intro"), "intro-2");
+        assert_eq!(slugger.slug("Intro 1"), "intro-1-1");
+    }
+
+    #[test]
+    fn take_explicit_heading_ids() {
+        let mut slugger = Slugger::new();
+        let mut heading = |text: &str, id: Option<&str>| {
+            let mut heading = Heading::from_text(1, text);
+            heading.attributes.id = id.map(str::to_string);
+            slugger.heading_id(&heading)
+        };
+
+        assert_eq!(heading("Foo", Some("bar")), "bar");
+        assert_eq!(heading("Bar", None), "bar-1");
+        assert_eq!(heading("Bar", None), "bar-2");
+        assert_eq!(heading("Baz", Some("bar-3")), "bar-3");
+        assert_eq!(heading("Bar", None), "bar-4");
+    }
+}
@@ -0,0 +1,126 @@
+use crate::parser::{InlineToken, Link};
+
+/// Replaces ASCII punctuation in text by its typographic form: straight quotes by curly ones,
+/// `--` by an en dash, `---` by an em dash and `...` by an ellipsis. Code spans and the text of
+/// autolinks are left alone.
+pub fn smarten(tokens: Vec<InlineToken>) -> Vec<InlineToken> {
+    smarten_tokens(tokens, &mut true)
+}
+
+/// `opens` tells whether a quote at the current position opens a quotation: at the start of the
+/// text or after whitespace or opening punctuation, but not right after a word or a code span.
+fn smarten_tokens(tokens: Vec<InlineToken>, opens: &mut bool) -> Vec<InlineToken> {
+    tokens
+        .into_iter()
+        .map(|token| match token {
+            InlineToken::Text(text) => InlineToken::Text(smarten_text(&text, opens)),
+            InlineToken::Bold(tokens) => InlineToken::Bold(smarten_tokens(tokens, opens)),
+            InlineToken::Italic(tokens) => InlineToken::Italic(smarten_tokens(tokens, opens)),
+            InlineToken::Link(mut link) if !is_autolink(&link) => {
+                link.tokens = smarten_tokens(link.tokens, opens);
+                InlineToken::Link(link)
+            }
+            // comments aren't displayed, so they don't separate the text around them
+            InlineToken::Comment(comment) => InlineToken::Comment(comment),
+            // a quote starting the next line opens, like one following a space
+            InlineToken::LineBreak => {
+                *opens = true;
+                InlineToken::LineBreak
+            }
+            token => {
+                *opens = false;
+                token
+            }
+        })
+        .collect()
+}
+
+fn smarten_text(text: &str, opens: &mut bool) -> String {
+    let mut smart = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(char) = chars.next() {
+        match char {
+            '"' => smart.push(if *opens { '“' } else { '”' }),
+            '\'' => smart.push(if *opens { '‘' } else { '’' }),
+            '-' | '.' => {
+                let mut count = 1;
+                while chars.next_if_eq(&char).is_some() {
+                    count += 1;
+                }
+                match (char, count) {
+                    ('-', 2) => smart.push('–'),
+                    ('-', 3) => smart.push('—'),
+                    ('.', 3) => smart.push('…'),
+                    _ => smart.extend(std::iter::repeat_n(char, count)),
+                }
+            }
+            _ => smart.push(char),
+        }
+
+        let last = smart.chars().next_back().unwrap_or(char);
+        *opens = last.is_whitespace() || matches!(last, '(' | '[' | '{' | '–' | '—' | '“' | '‘');
+    }
+
+    smart
+}
+
+/// Autolinks show their URL, which has to stay as written
+fn is_autolink(link: &Link) -> bool {
+    match link.tokens.as_slice() {
+        [InlineToken::Text(text)] => {
+            link.href == *text
+                || link.href == format!("http://{}", text)
+                || link.href == format!("mailto:{}", text)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn smarten_punctuation() {
+        let tests = vec![
+            ("\"quoted\"", "“quoted”"),
+            ("it's 'single'", "it’s ‘single’"),
+            ("(\"a\") [\"b\"]", "(“a”) [“b”]"),
+            ("a--b, a---b, a----b", "a–b, a—b, a----b"),
+            ("wait...", "wait…"),
+        ];
+        for (text, smart) in tests {
+            assert_eq!(
+                smarten(vec![InlineToken::new_text(text)]),
+                vec![InlineToken::new_text(smart)],
+                "{:?}",
+                text
+            );
+        }
+
+        assert_eq!(
+            smarten(vec![
+                InlineToken::new_text("\""),
+                InlineToken::new_italic(vec![InlineToken::new_text("a")]),
+                InlineToken::new_text("\" "),
+                InlineToken::new_code("\"--\""),
+                InlineToken::new_text("\" "),
+                InlineToken::new_link(
+                    vec![InlineToken::new_text("www.a--b.com")],
+                    "http://www.a--b.com"
+                ),
+            ]),
+            vec![
+                InlineToken::new_text("“"),
+                InlineToken::new_italic(vec![InlineToken::new_text("a")]),
+                InlineToken::new_text("” "),
+                InlineToken::new_code("\"--\""),
+                InlineToken::new_text("” "),
+                InlineToken::new_link(
+                    vec![InlineToken::new_text("www.a--b.com")],
+                    "http://www.a--b.com"
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use std::ops::Range;
+
+/// Source offsets (in chars) of a parsed construct, from `start` up to but not including `end`
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// Returns the smallest span covering both spans, like the span of a node from those of its
+    /// first and last children. An empty span covers nothing and is ignored.
+    pub fn merge(a: Span, b: Span) -> Span {
+        match (a.is_empty(), b.is_empty()) {
+            (_, true) => a,
+            (true, false) => b,
+            (false, false) => Span::new(a.start.min(b.start), a.end.max(b.end)),
+        }
+    }
+
+    /// Returns true when the char at `offset` is within the span
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset < self.end
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start >= self.end
+    }
+
+    pub fn len(&self) -> usize {
+        self.end.saturating_sub(self.start)
+    }
+}
+
+impl From<Range<usize>> for Span {
+    fn from(range: Range<usize>) -> Self {
+        Span::new(range.start, range.end)
+    }
+}
+
+impl From<Span> for Range<usize> {
+    fn from(span: Span) -> Self {
+        span.start..span.end
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn merge_spans() {
+        assert_eq!(Span::merge((0..4).into(), (6..9).into()), Span::new(0, 9));
+        assert_eq!(Span::merge((6..9).into(), (2..7).into()), Span::new(2, 9));
+        assert_eq!(Span::merge((3..5).into(), (0..8).into()), Span::new(0, 8));
+        assert_eq!(Span::merge((3..5).into(), Span::default()), Span::new(3, 5));
+        assert_eq!(Span::merge((7..7).into(), (3..5).into()), Span::new(3, 5));
+        assert_eq!(Range::from(Span::new(1, 2)), 1..2);
+    }
+
+    #[test]
+    fn span_contains() {
+        let span = Span::from(2..5);
+        assert!(!span.contains(1));
+        assert!(span.contains(2));
+        assert!(span.contains(4));
+        assert!(!span.contains(5));
+        assert!(!Span::from(3..3).contains(3));
+    }
+
+    #[test]
+    fn empty_spans() {
+        assert!(Span::default().is_empty());
+        assert!(Span::from(4..4).is_empty());
+        assert!(!Span::from(4..5).is_empty());
+        assert_eq!(Span::from(4..5).len(), 1);
+        assert_eq!(Span::from(4..4).len(), 0);
+    }
+}
@@ -0,0 +1,222 @@
+use crate::parser::{Document, Element, InlineToken};
+use std::collections::BTreeMap;
+
+/// Counts of every kind of element and inline token in a document
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Statistics {
+    /// Number of headings per heading level
+    pub headings: BTreeMap<usize, usize>,
+    pub paragraphs: usize,
+    pub lists: usize,
+    pub list_items: usize,
+    pub thematic_breaks: usize,
+    pub code_blocks: usize,
+    pub blockquotes: usize,
+    pub tables: usize,
+    pub links: usize,
+    pub images: usize,
+    pub code_spans: usize,
+    pub bold: usize,
+    pub italic: usize,
+}
+
+impl Statistics {
+    /// Returns the number of headings of all levels
+    pub fn heading_count(&self) -> usize {
+        self.headings.values().sum()
+    }
+
+    fn count_elements(&mut self, elements: &[Element]) {
+        for element in elements {
+            match element {
+                Element::Heading(heading) => {
+                    *self.headings.entry(heading.level).or_default() += 1;
+                    self.count_inline_tokens(&heading.tokens);
+                }
+                Element::Paragraph(paragraph) => {
+                    self.paragraphs += 1;
+                    self.count_inline_tokens(&paragraph.0);
+                }
+                Element::ThematicBreak => self.thematic_breaks += 1,
+                Element::CodeBlock(_) => self.code_blocks += 1,
+                Element::Comment(_) | Element::Html(_) => {}
+                Element::BlockQuote(elements) => {
+                    self.blockquotes += 1;
+                    self.count_elements(elements);
+                }
+                Element::Table(table) => {
+                    self.tables += 1;
+                    for cell in table.header.iter().chain(table.rows.iter().flatten()) {
+                        self.count_inline_tokens(cell);
+                    }
+                }
+                Element::List(list) => {
+                    self.lists += 1;
+                    self.list_items += list.items.len();
+                    for item in &list.items {
+                        self.count_elements(item);
+                    }
+                }
+            }
+        }
+    }
+
+    fn count_inline_tokens(&mut self, tokens: &[InlineToken]) {
+        for token in tokens {
+            match token {
+                InlineToken::Text(_) | InlineToken::Comment(_) | InlineToken::LineBreak => {}
+                InlineToken::Link(link) => {
+                    self.links += 1;
+                    self.count_inline_tokens(&link.tokens);
+                }
+                InlineToken::Image(_) => self.images += 1,
+                InlineToken::Bold(tokens) => {
+                    self.bold += 1;
+                    self.count_inline_tokens(tokens);
+                }
+                InlineToken::Italic(tokens) => {
+                    self.italic += 1;
+                    self.count_inline_tokens(tokens);
+                }
+                InlineToken::Code(_) => self.code_spans += 1,
+            }
+        }
+    }
+}
+
+impl Document {
+    /// Collects the statistics of the document in a single traversal
+    pub fn statistics(&self) -> Statistics {
+        let mut statistics = Statistics::default();
+        statistics.count_elements(&self.elements);
+
+        statistics
+    }
+
+    /// Returns the deepest nesting of lists, block quotes and emphasis in the document. Content
+    /// without any of them has a depth of 1, an empty document a depth of 0.
+    pub fn max_nesting_depth(&self) -> usize {
+        match self.elements.is_empty() {
+            true => 0,
+            false => std::cmp::max(nesting_depth(&self.elements), 1),
+        }
+    }
+}
+
+/// Returns the number of nested lists, block quotes and emphasis on the deepest path
+fn nesting_depth(elements: &[Element]) -> usize {
+    let depth = |element: &Element| match element {
+        Element::Heading(heading) => inline_nesting_depth(&heading.tokens),
+        Element::Paragraph(paragraph) => inline_nesting_depth(&paragraph.0),
+        Element::List(list) => {
+            1 + list
+                .items
+                .iter()
+                .map(|item| nesting_depth(item))
+                .max()
+                .unwrap_or(0)
+        }
+        Element::BlockQuote(elements) => 1 + nesting_depth(elements),
+        Element::Table(table) => table
+            .header
+            .iter()
+            .chain(table.rows.iter().flatten())
+            .map(|cell| inline_nesting_depth(cell))
+            .max()
+            .unwrap_or(0),
+        Element::CodeBlock(_) | Element::ThematicBreak | Element::Comment(_) | Element::Html(_) => {
+            0
+        }
+    };
+
+    elements.iter().map(depth).max().unwrap_or(0)
+}
+
+fn inline_nesting_depth(tokens: &[InlineToken]) -> usize {
+    let depth = |token: &InlineToken| match token {
+        InlineToken::Bold(tokens) | InlineToken::Italic(tokens) => 1 + inline_nesting_depth(tokens),
+        InlineToken::Link(link) => inline_nesting_depth(&link.tokens),
+        InlineToken::Text(_)
+        | InlineToken::Image(_)
+        | InlineToken::Code(_)
+        | InlineToken::Comment(_)
+        | InlineToken::LineBreak => 0,
+    };
+
+    tokens.iter().map(depth).max().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bytes::{CharIterator, Encoding};
+    use crate::parser::{ListKind, Parser};
+    use crate::tokenizer::Tokenizer;
+
+    #[test]
+    fn count_parsed_document() {
+        let mut chars = CharIterator::new();
+        chars.read_from_str(
+            "# a\n## b\n## c\n- [x](y)\n- ![i](s) [![j](t)](u)",
+            Some(Encoding::UTF8),
+        );
+        let mut tokenizer = Tokenizer::new(&mut chars);
+        let mut parser = Parser::new(&mut tokenizer);
+
+        let statistics = parser.parse().statistics();
+
+        assert_eq!(statistics.headings.get(&1), Some(&1));
+        assert_eq!(statistics.headings.get(&2), Some(&2));
+        assert_eq!(statistics.headings.get(&3), None);
+        assert_eq!(statistics.heading_count(), 3);
+        assert_eq!(statistics.lists, 1);
+        assert_eq!(statistics.list_items, 2);
+        assert_eq!(statistics.paragraphs, 2);
+        assert_eq!(statistics.links, 2);
+        assert_eq!(statistics.images, 2);
+    }
+
+    #[test]
+    fn count_nested_inline_tokens() {
+        let document = Document::new(vec![
+            Element::new_paragraph(vec![
+                InlineToken::new_blod(vec![
+                    InlineToken::new_italic(vec![InlineToken::new_text("a")]),
+                    InlineToken::new_code("b"),
+                ]),
+                InlineToken::new_code("c"),
+            ]),
+            Element::new_list(
+                ListKind::Unordered,
+                vec![vec![Element::new_paragraph(vec![InlineToken::new_italic(
+                    vec![],
+                )])]],
+            ),
+        ]);
+
+        let statistics = document.statistics();
+
+        assert_eq!(statistics.paragraphs, 2);
+        assert_eq!(statistics.bold, 1);
+        assert_eq!(statistics.italic, 2);
+        assert_eq!(statistics.code_spans, 2);
+        assert_eq!(statistics.heading_count(), 0);
+    }
+
+    #[test]
+    fn measure_nesting_depth() {
+        let item = |elements| Element::new_list(ListKind::Unordered, vec![elements]);
+        let paragraph = |text| Element::new_paragraph(vec![InlineToken::new_text(text)]);
+
+        assert_eq!(Document::new(vec![]).max_nesting_depth(), 0);
+        assert_eq!(crate::parse("# a\n\nb\n\n---").max_nesting_depth(), 1);
+        assert_eq!(crate::parse("- a\n- b").max_nesting_depth(), 1);
+        assert_eq!(crate::parse("> - **a *b* c**").max_nesting_depth(), 4);
+
+        let document = Document::new(vec![
+            paragraph("a"),
+            item(vec![paragraph("b"), item(vec![item(vec![paragraph("c")])])]),
+        ]);
+        assert_eq!(document.max_nesting_depth(), 3);
+    }
+}
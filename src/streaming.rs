@@ -0,0 +1,150 @@
+use crate::bytes::{CharIterator, Encoding};
+use crate::parser::{Document, Element, Parser, ParserOptions};
+use crate::reference::LinkDefinition;
+use crate::tokenizer::Tokenizer;
+use std::collections::HashMap;
+
+/// Parses a document that arrives in chunks, like an append-only log, returning its elements as
+/// soon as their block is finished. A block is finished once another block starts after it, or
+/// for a paragraph once a blank line follows it. Headings and thematic breaks are finished with
+/// their line.
+///
+/// References resolve to the definitions fed before them, not to the ones that follow. Front
+/// matter isn't read.
+#[derive(Debug, Default)]
+pub struct StreamingParser {
+    options: ParserOptions,
+    /// Text fed that isn't part of a finished element yet
+    pending: String,
+    /// Link reference definitions fed so far
+    definitions: HashMap<String, LinkDefinition>,
+}
+
+impl StreamingParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_options(options: ParserOptions) -> Self {
+        Self {
+            options: ParserOptions {
+                front_matter: false,
+                retain_source: false,
+                ..options
+            },
+            ..Self::default()
+        }
+    }
+
+    /// Adds text to the stream and returns the elements it finished
+    pub fn feed(&mut self, chunk: &str) -> Vec<Element> {
+        self.pending.push_str(chunk);
+
+        // the last line may still be incomplete
+        let Some(newline) = self.pending.rfind('\n') else {
+            return Vec::new();
+        };
+        let lines = self.pending[..=newline].to_string();
+        let document = self.parse(&lines);
+        let mut elements = document.elements;
+
+        // the last element may go on in the lines to come
+        let mut rest = newline + 1;
+        if let (Some(last), Some(span)) = (elements.last(), document.spans.last()) {
+            let after = lines.chars().skip(span.end).collect::<String>();
+            if !is_finished(last, &after) {
+                elements.pop();
+                rest = lines
+                    .char_indices()
+                    .nth(span.start)
+                    .map_or(lines.len(), |(i, _)| i);
+            }
+        }
+        self.pending.drain(..rest);
+
+        elements
+    }
+
+    /// Ends the stream, returning the elements of the text fed that weren't returned yet
+    pub fn finish(&mut self) -> Vec<Element> {
+        let pending = std::mem::take(&mut self.pending);
+        self.parse(&pending).elements
+    }
+
+    fn parse(&mut self, markdown: &str) -> Document {
+        let mut chars = CharIterator::new();
+        chars.read_from_str(markdown, Some(Encoding::UTF8));
+        let mut tokenizer = Tokenizer::new(&mut chars);
+        let mut parser = Parser::with_options(&mut tokenizer, self.options.clone());
+        parser.set_definitions(std::mem::take(&mut self.definitions));
+
+        let document = parser.parse();
+        self.definitions = parser.take_definitions();
+
+        document
+    }
+}
+
+/// Returns true when the lines to come can't add to an element that is followed by `after`, which
+/// holds nothing but whitespace
+fn is_finished(element: &Element, after: &str) -> bool {
+    match element {
+        Element::Heading(_) | Element::ThematicBreak | Element::Comment(_) => true,
+        Element::Paragraph(_) => after.matches('\n').count() >= 2,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::InlineToken;
+
+    fn paragraph(text: &str) -> Element {
+        Element::new_paragraph(vec![InlineToken::new_text(text)])
+    }
+
+    #[test]
+    fn feed_chunks() {
+        let mut parser = StreamingParser::new();
+
+        assert_eq!(
+            parser.feed("# Log\n\nfirst half"),
+            vec![Element::new_heading(1, vec![InlineToken::new_text("Log")])]
+        );
+        assert_eq!(parser.feed(" of a paragraph\nwrapped"), vec![]);
+        assert_eq!(
+            parser.feed("\n\n- a\n"),
+            vec![paragraph("first half of a paragraph\nwrapped")]
+        );
+        assert_eq!(
+            parser.feed("- b\n\n```\nc\n\n"),
+            vec![Element::new_list(
+                crate::parser::ListKind::Unordered,
+                vec![vec![paragraph("a")], vec![paragraph("b")]]
+            )]
+        );
+        assert_eq!(parser.feed("```\nd"), vec![]);
+        assert_eq!(
+            parser.finish(),
+            vec![Element::new_code_block(None, "c\n\n"), paragraph("d")]
+        );
+        assert_eq!(parser.finish(), vec![]);
+    }
+
+    #[test]
+    fn resolve_earlier_definitions() {
+        let mut parser = StreamingParser::new();
+
+        assert_eq!(parser.feed("[a]: /url\n\n"), vec![]);
+        assert_eq!(
+            parser.feed("[a] [b]\n\n"),
+            vec![Element::new_paragraph(vec![
+                InlineToken::new_link(vec![InlineToken::new_text("a")], "/url"),
+                InlineToken::new_text(" [b]"),
+            ])]
+        );
+        assert_eq!(parser.feed("[b]: /other\n"), vec![]);
+        assert_eq!(parser.finish(), vec![]);
+    }
+}
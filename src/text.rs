@@ -0,0 +1,144 @@
+use crate::parser::{plain_text, Document, Element};
+use std::ops::Range;
+
+/// Renders the document as plain text, without any markup. Blocks are separated by a blank line
+/// and list items are put on lines of their own.
+pub fn render_text(document: &Document) -> String {
+    let blocks = document
+        .elements
+        .iter()
+        .filter_map(element_text)
+        .collect::<Vec<_>>();
+
+    let mut text = blocks.join("\n\n");
+    if !text.is_empty() {
+        text.push('\n');
+    }
+
+    text
+}
+
+impl Document {
+    /// Counts the words in the plain text of the document, see `word_count`
+    pub fn word_count(&self) -> usize {
+        word_count(&render_text(self))
+    }
+}
+
+/// Counts the words in a text. Words are separated by whitespace, but a non-breaking space joins
+/// the words around it. Chinese and Japanese don't separate words, so each of their characters
+/// counts as a word and their punctuation separates words.
+pub fn word_count(text: &str) -> usize {
+    words(text).count()
+}
+
+/// Returns the byte ranges of the words of a text, as counted by `word_count`
+pub(crate) fn words(text: &str) -> impl Iterator<Item = Range<usize>> + '_ {
+    let is_separator =
+        |char: char| is_whitespace(char) || ('\u{3000}'..='\u{303F}').contains(&char);
+
+    let mut chars = text.char_indices().peekable();
+    std::iter::from_fn(move || {
+        let (start, first) = chars.find(|&(_, char)| !is_separator(char))?;
+        let mut end = start + first.len_utf8();
+        if !is_cjk(first) {
+            while let Some((i, char)) =
+                chars.next_if(|&(_, char)| !is_separator(char) && !is_cjk(char))
+            {
+                end = i + char.len_utf8();
+            }
+        }
+
+        Some(start..end)
+    })
+}
+
+/// Returns true for the whitespace that separates words, which leaves out non-breaking spaces
+pub(crate) fn is_whitespace(char: char) -> bool {
+    char.is_whitespace() && !is_non_breaking_space(char)
+}
+
+fn is_non_breaking_space(char: char) -> bool {
+    matches!(char, '\u{00A0}' | '\u{2007}' | '\u{202F}')
+}
+
+/// Returns true for kana and CJK ideographs
+fn is_cjk(char: char) -> bool {
+    matches!(
+        char,
+        '\u{3040}'..='\u{30FF}'
+            | '\u{3400}'..='\u{4DBF}'
+            | '\u{4E00}'..='\u{9FFF}'
+            | '\u{F900}'..='\u{FAFF}'
+            | '\u{20000}'..='\u{2FA1F}'
+    )
+}
+
+pub(crate) fn element_text(element: &Element) -> Option<String> {
+    let text = match element {
+        Element::Heading(heading) => plain_text(&heading.tokens).trim().to_string(),
+        Element::Paragraph(paragraph) => plain_text(&paragraph.0).trim().to_string(),
+        Element::List(list) => list
+            .items
+            .iter()
+            .map(|item| {
+                item.iter()
+                    .filter_map(element_text)
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Element::CodeBlock(code_block) => code_block.code.trim_end_matches('\n').to_string(),
+        Element::BlockQuote(elements) => elements
+            .iter()
+            .filter_map(element_text)
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        // cells are separated by a tab, rows put on lines of their own
+        Element::Table(table) => std::iter::once(&table.header)
+            .chain(&table.rows)
+            .map(|row| {
+                row.iter()
+                    .map(|cell| plain_text(cell).trim().to_string())
+                    .collect::<Vec<_>>()
+                    .join("\t")
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Element::ThematicBreak | Element::Comment(_) | Element::Html(_) => return None,
+    };
+
+    Some(text)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn render_plain_text() {
+        let document = parse("# **Title**\n---\n- [a](b)\n- `c`\n```\ncode\n```");
+
+        assert_eq!(render_text(&document), "Title\n\na\nc\n\ncode\n");
+    }
+
+    #[test]
+    fn count_words() {
+        let tests = vec![
+            ("", 0),
+            ("  one two\tthree\n", 3),
+            ("10\u{00A0}km away", 2),
+            ("\u{2003}wide\u{2003}space", 2),
+            ("我喜欢读书", 5),
+            ("Rust 是一种语言。", 6),
+            ("ひらがな and カタカナ", 9),
+        ];
+        for (text, count) in tests {
+            assert_eq!(word_count(text), count, "{:?}", text);
+        }
+
+        assert_eq!(parse("# A *b*\n\n- c\u{00A0}d\n- 中文").word_count(), 5);
+    }
+}
@@ -1,8 +1,9 @@
-use crate::bytes::{Bytes, CharIterator};
+use crate::bytes::{Bytes, CharIterator, CharSource, Checkpoint};
+use std::fmt;
 use std::str::FromStr;
-use std::string::ToString;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
 pub enum Token {
     String(String),
     Hash(usize),
@@ -10,17 +11,49 @@ pub enum Token {
     Backticks(usize),
     Dash(usize),
     Underscore(usize),
+    Tilde(usize),
+    Plus,
+    /// The number and delimiter opening an ordered list item, like `3.` or `10)`
+    OrderedMarker {
+        number: usize,
+        delim: Delimiter,
+    },
     Url(String),
+    /// An ASCII punctuation character escaped by a backslash, standing for itself
+    Escaped(char),
+    /// An HTML comment, holding the text between `<!--` and `-->`
+    Comment(String),
     Whitespace(String),
+    /// Whitespace at the start of a line, holding its width in columns
+    Indent(usize),
     OpeningParenthesis,
     ClosingParenthesis,
     OpeningBracket,
     ClosingBracket,
+    /// A `<` that doesn't open an HTML comment, like the start of an autolink or an HTML tag
+    OpeningAngleBracket,
     AngleBracket,
+    /// A single `!`. Runs of them aren't merged, as only the `!` right before a `[` opens an image.
     ExclamationMark,
     EOF,
 }
 
+/// The character following the number of an ordered list marker
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Delimiter {
+    Period,
+    Parenthesis,
+}
+
+impl Delimiter {
+    pub fn char(&self) -> char {
+        match self {
+            Delimiter::Period => '.',
+            Delimiter::Parenthesis => ')',
+        }
+    }
+}
+
 impl Token {
     pub fn is_string(&self) -> bool {
         matches!(self, Token::String(..))
@@ -46,6 +79,18 @@ impl Token {
         matches!(self, Token::Underscore(..))
     }
 
+    pub fn is_tilde(&self) -> bool {
+        matches!(self, Token::Tilde(..))
+    }
+
+    pub fn is_plus(&self) -> bool {
+        matches!(self, Token::Plus)
+    }
+
+    pub fn is_ordered_marker(&self) -> bool {
+        matches!(self, Token::OrderedMarker { .. })
+    }
+
     pub fn is_url(&self) -> bool {
         matches!(self, Token::Url(..))
     }
@@ -54,9 +99,81 @@ impl Token {
         matches!(self, Token::Whitespace(..))
     }
 
+    /// Returns the number of line breaks in a whitespace token
+    pub fn newline_count(&self) -> usize {
+        match self {
+            Token::Whitespace(s) => s.matches('\n').count(),
+            _ => 0,
+        }
+    }
+
+    /// Returns true when the token is whitespace spanning a blank line
+    pub fn is_blank_line(&self) -> bool {
+        self.newline_count() >= 2
+    }
+
+    pub fn is_indent(&self) -> bool {
+        matches!(self, Token::Indent(..))
+    }
+
     pub fn is_eof(&self) -> bool {
         matches!(self, Token::EOF)
     }
+
+    pub fn kind(&self) -> TokenKind {
+        match self {
+            Token::String(_) => TokenKind::String,
+            Token::Hash(_) => TokenKind::Hash,
+            Token::Asterisk(_) => TokenKind::Asterisk,
+            Token::Backticks(_) => TokenKind::Backticks,
+            Token::Dash(_) => TokenKind::Dash,
+            Token::Underscore(_) => TokenKind::Underscore,
+            Token::Tilde(_) => TokenKind::Tilde,
+            Token::Plus => TokenKind::Plus,
+            Token::OrderedMarker { .. } => TokenKind::OrderedMarker,
+            Token::Url(_) => TokenKind::Url,
+            Token::Escaped(_) => TokenKind::Escaped,
+            Token::Comment(_) => TokenKind::Comment,
+            Token::Whitespace(_) => TokenKind::Whitespace,
+            Token::Indent(_) => TokenKind::Indent,
+            Token::OpeningParenthesis => TokenKind::OpeningParenthesis,
+            Token::ClosingParenthesis => TokenKind::ClosingParenthesis,
+            Token::OpeningBracket => TokenKind::OpeningBracket,
+            Token::ClosingBracket => TokenKind::ClosingBracket,
+            Token::OpeningAngleBracket => TokenKind::OpeningAngleBracket,
+            Token::AngleBracket => TokenKind::AngleBracket,
+            Token::ExclamationMark => TokenKind::ExclamationMark,
+            Token::EOF => TokenKind::EOF,
+        }
+    }
+}
+
+/// The kind of a token, regardless of the text or length it holds
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[non_exhaustive]
+pub enum TokenKind {
+    String,
+    Hash,
+    Asterisk,
+    Backticks,
+    Dash,
+    Underscore,
+    Tilde,
+    Plus,
+    OrderedMarker,
+    Url,
+    Escaped,
+    Comment,
+    Whitespace,
+    Indent,
+    OpeningParenthesis,
+    ClosingParenthesis,
+    OpeningBracket,
+    ClosingBracket,
+    OpeningAngleBracket,
+    AngleBracket,
+    ExclamationMark,
+    EOF,
 }
 #[derive(Debug, PartialEq, Eq)]
 pub struct ParseTokenError;
@@ -65,56 +182,108 @@ impl FromStr for Token {
     type Err = ParseTokenError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.len() == 0 {
+        if s.is_empty() {
             return Ok(Token::EOF);
         }
 
+        if let Some(marker) = ordered_marker(s) {
+            return Ok(marker);
+        }
+
         let chars = s.chars().collect::<Vec<char>>();
-        let char = chars.first().unwrap().clone();
+        let char = *chars.first().unwrap();
 
         let token = match char {
             '(' => Token::OpeningParenthesis,
             ')' => Token::ClosingParenthesis,
             '[' => Token::OpeningBracket,
             ']' => Token::ClosingBracket,
+            '<' => Token::OpeningAngleBracket,
             '>' => Token::AngleBracket,
             '!' => Token::ExclamationMark,
-            _ => todo!(),
+            '+' => Token::Plus,
+            _ => return Err(ParseTokenError),
         };
 
         Ok(token)
     }
 }
 
-impl ToString for Token {
-    fn to_string(&self) -> String {
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Token::String(s) => s.clone(),
-            Token::Hash(n) => "#".repeat(*n),
-            Token::Asterisk(n) => "*".repeat(*n),
-            Token::Backticks(n) => "`".repeat(*n),
-            Token::Dash(n) => "-".repeat(*n),
-            Token::Underscore(n) => "_".repeat(*n),
-            Token::Url(s) => s.to_string(),
-            Token::Whitespace(s) => s.to_string(),
-            Token::OpeningBracket => '['.to_string(),
-            Token::ClosingBracket => ']'.to_string(),
-            Token::OpeningParenthesis => '('.to_string(),
-            Token::ClosingParenthesis => '('.to_string(),
-            Token::AngleBracket => '>'.to_string(),
-            Token::ExclamationMark => '!'.to_string(),
-            Token::EOF => String::new(),
+            Token::String(s) => write!(f, "{}", s),
+            Token::Hash(n) => write!(f, "{}", "#".repeat(*n)),
+            Token::Asterisk(n) => write!(f, "{}", "*".repeat(*n)),
+            Token::Backticks(n) => write!(f, "{}", "`".repeat(*n)),
+            Token::Dash(n) => write!(f, "{}", "-".repeat(*n)),
+            Token::Underscore(n) => write!(f, "{}", "_".repeat(*n)),
+            Token::Tilde(n) => write!(f, "{}", "~".repeat(*n)),
+            Token::Plus => write!(f, "+"),
+            Token::OrderedMarker { number, delim } => write!(f, "{}{}", number, delim.char()),
+            Token::Url(s) => write!(f, "{}", s),
+            Token::Escaped(c) => write!(f, "\\{}", c),
+            Token::Comment(s) => write!(f, "<!--{}-->", s),
+            Token::Whitespace(s) => write!(f, "{}", s),
+            Token::Indent(n) => write!(f, "{}", " ".repeat(*n)),
+            Token::OpeningBracket => write!(f, "["),
+            Token::ClosingBracket => write!(f, "]"),
+            Token::OpeningParenthesis => write!(f, "("),
+            Token::ClosingParenthesis => write!(f, ")"),
+            Token::OpeningAngleBracket => write!(f, "<"),
+            Token::AngleBracket => write!(f, ">"),
+            Token::ExclamationMark => write!(f, "!"),
+            Token::EOF => Ok(()),
         }
     }
 }
 
-pub struct Tokenizer<'a> {
-    chars: &'a mut CharIterator,
+/// Parses an ordered list marker: up to nine digits followed by a `.` or `)`. Numbers with
+/// leading zeros aren't markers, as the token couldn't be written back as it was read.
+fn ordered_marker(s: &str) -> Option<Token> {
+    let delim = match s.chars().next_back()? {
+        '.' => Delimiter::Period,
+        ')' => Delimiter::Parenthesis,
+        _ => return None,
+    };
+    let digits = &s[..s.len() - 1];
+    if !(1..=9).contains(&digits.len()) || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+
+    let number: usize = digits.parse().ok()?;
+    (number.to_string() == digits).then_some(Token::OrderedMarker { number, delim })
+}
+
+/// Column width of a tab when computing indentation
+const TAB_STOP: usize = 4;
+
+/// Returns true for the characters that can't end a string token, whatever follows them.
+/// Punctuation without a meaning of its own, like `.`, `?` or `:`, is plain, so that runs such as
+/// `...` stay in the text around them.
+fn is_plain(char: char) -> bool {
+    !(char.is_whitespace()
+        || matches!(
+            char,
+            '[' | ']' | '(' | ')' | '*' | '_' | '`' | '~' | '!' | '>' | '<' | '\\'
+        ))
+}
+
+/// Splits a stream of characters into tokens. Reads from a `CharIterator` unless another
+/// `CharSource` is given.
+pub struct Tokenizer<'a, S = CharIterator> {
+    chars: &'a mut S,
+    /// The offset the last search for the end of a comment started from, and the offset of the
+    /// first `-->` from there if any, see `comment_length`
+    comment_end: Option<(usize, Option<usize>)>,
 }
 
-impl<'a> Tokenizer<'a> {
-    pub fn new(chars: &'a mut CharIterator) -> Self {
-        Self { chars }
+impl<'a, S: CharSource> Tokenizer<'a, S> {
+    pub fn new(chars: &'a mut S) -> Self {
+        Self {
+            chars,
+            comment_end: None,
+        }
     }
 
     pub fn consume(&mut self) -> Token {
@@ -126,28 +295,267 @@ impl<'a> Tokenizer<'a> {
 
         let char = current.char();
         match char {
-            '#' | '*' | '`' | '_' | '-' => self.consume_delim(),
-            '(' | ')' | '[' | ']' => {
+            // a `#` within a line, like in `C#` or `#42`, can't open a heading and stays text
+            '#' if self.at_line_content_start() => self.consume_delim(),
+            '0'..='9' if self.at_line_content_start() => self.consume_ordered_marker(),
+            '*' | '`' | '_' | '-' | '~' => self.consume_delim(),
+            '\\' if self.chars.look_ahead(1).char().is_ascii_punctuation() => {
+                self.chars.read();
+                Token::Escaped(self.chars.read().char())
+            }
+            '<' if self.comment_length().is_some() => self.consume_comment(),
+            '(' | ')' | '[' | ']' | '+' | '!' | '<' | '>' => {
                 self.chars.read();
                 Token::from_str(&char.to_string()).unwrap()
             }
-            _ if char.is_whitespace() => self.consume_whitespace(),
+            _ if char.is_whitespace() => match self.line_indent() {
+                Some(_) if self.chars.at_line_start() => self.consume_indent(),
+                _ => self.consume_whitespace(),
+            },
             _ => self.consume_string(),
         }
     }
 
+    /// Returns the offset in the stream of the next token
+    pub fn offset(&self) -> usize {
+        self.chars.tell()
+    }
+
+    /// Consumes the characters up to the first run of exactly `count` `delim` characters on the
+    /// current line and that run, returning the characters before it verbatim. Nothing is
+    /// consumed when the line holds no such run. Used for the content of code spans, which is
+    /// taken literally rather than split into tokens.
+    pub fn read_raw_until(&mut self, delim: char, count: usize) -> Option<String> {
+        let mut length = 0;
+        loop {
+            let char = match self.chars.look_ahead(length) {
+                Bytes::Eof => return None,
+                bytes => bytes.char(),
+            };
+            if char == '\n' {
+                return None;
+            }
+            if char != delim {
+                length += 1;
+                continue;
+            }
+
+            let mut run = 1;
+            while self.chars.look_ahead(length + run) == Bytes::Ch(delim) {
+                run += 1;
+            }
+            if run == count {
+                break;
+            }
+            length += run;
+        }
+
+        let raw = (0..length).map(|_| self.chars.read().char()).collect();
+        self.chars.skip(count);
+        Some(raw)
+    }
+
+    /// Consumes the rest of the current line up to its line ending, returning it verbatim. Used for
+    /// the lines of code spans spanning several lines, see `read_raw_until`.
+    pub fn read_raw_line(&mut self) -> String {
+        let mut raw = String::new();
+        while !matches!(self.chars.current(), Bytes::Eof | Bytes::Ch('\n')) {
+            raw.push(self.chars.read().char());
+        }
+
+        raw
+    }
+
+    /// Returns the whole stream as a string
+    pub fn source(&self) -> String {
+        self.chars.slice(0, usize::MAX)
+    }
+
+    /// Consumes a block at the very start of the stream that is opened and closed by lines holding
+    /// just `fence`, returning its raw content. Nothing is consumed when there is no such block.
+    pub fn consume_front_matter(&mut self, fence: &str) -> Option<String> {
+        if self.chars.tell() != 0 {
+            return None;
+        }
+
+        let source = self.chars.remaining();
+        let mut lines = source.split_inclusive('\n');
+        let opening = lines.next()?;
+        if opening.trim_end() != fence {
+            return None;
+        }
+
+        let mut length = opening.chars().count();
+        let mut content = String::new();
+        for line in lines {
+            length += line.chars().count();
+            if line.trim_end() == fence {
+                self.chars.skip(length);
+                return Some(content.trim_end_matches('\n').to_string());
+            }
+            content.push_str(line);
+        }
+
+        None
+    }
+
+    /// Consumes a JSON object at the very start of the stream, returning it verbatim including its
+    /// braces. The object has to be balanced and nothing but whitespace may follow it on its last
+    /// line. Nothing is consumed when there is no such object.
+    pub fn consume_json_front_matter(&mut self) -> Option<String> {
+        if self.chars.tell() != 0 || self.chars.current() != Bytes::Ch('{') {
+            return None;
+        }
+
+        let source = self.chars.remaining();
+        let mut depth = 0;
+        let mut in_string = false;
+        let mut escaped = false;
+        let mut end = None;
+        for (i, char) in source.chars().enumerate() {
+            if in_string {
+                match char {
+                    _ if escaped => escaped = false,
+                    '\\' => escaped = true,
+                    '"' => in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+
+            match char {
+                '"' => in_string = true,
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                end = Some(i + 1);
+                break;
+            }
+        }
+
+        let end = end?;
+        let object = source.chars().take(end).collect::<String>();
+        let rest = source.chars().skip(end);
+        let line_rest = rest.take_while(|c| *c != '\n').collect::<String>();
+        if !line_rest.trim().is_empty() {
+            return None;
+        }
+
+        // consume the object along with the end of its line
+        self.chars.skip(end + line_rest.chars().count() + 1);
+        Some(object)
+    }
+
+    /// Consumes a run of whitespace. The run ends after a newline when the next line is indented,
+    /// so that its indentation is emitted as a separate `Token::Indent`.
     pub fn consume_whitespace(&mut self) -> Token {
         let mut whitespace = String::new();
         while self.chars.current().char().is_whitespace() {
-            whitespace.push(self.chars.read().char());
+            let char = self.chars.read().char();
+            whitespace.push(char);
+
+            if char == '\n' && self.line_indent().is_some() {
+                break;
+            }
         }
 
         Token::Whitespace(whitespace)
     }
 
+    /// Consumes the spaces and tabs at the start of a line. Tabs advance to the next multiple of
+    /// four columns.
+    pub fn consume_indent(&mut self) -> Token {
+        let mut width = 0;
+        loop {
+            match self.chars.current().char() {
+                ' ' => width += 1,
+                '\t' => width += TAB_STOP - width % TAB_STOP,
+                _ => break,
+            }
+            self.chars.read();
+        }
+
+        Token::Indent(width)
+    }
+
+    /// Returns true when nothing but spaces and tabs precede the current position on its line
+    fn at_line_content_start(&self) -> bool {
+        let mut offset = self.chars.tell();
+        while offset > 0 {
+            match self.chars.slice(offset - 1, offset).as_str() {
+                "\n" => return true,
+                " " | "\t" => offset -= 1,
+                _ => return false,
+            }
+        }
+
+        true
+    }
+
+    /// Returns the width of the spaces and tabs at the current position when they are followed by
+    /// content on the same line. Blank lines have no indentation.
+    fn line_indent(&self) -> Option<usize> {
+        let mut width = 0;
+        let mut offset = 0;
+        loop {
+            match self.chars.look_ahead(offset) {
+                Bytes::Ch(' ') => width += 1,
+                Bytes::Ch('\t') => width += TAB_STOP - width % TAB_STOP,
+                Bytes::Ch(c) if c.is_whitespace() => return None,
+                Bytes::Eof => return None,
+                _ => return Some(width),
+            }
+            offset += 1;
+        }
+    }
+
+    /// Returns a checkpoint of the position in the stream, see `CharSource::mark`
+    pub fn mark(&self) -> Checkpoint {
+        self.chars.mark()
+    }
+
+    /// Goes back (or forth) to a position returned by `mark`, to tokenize from there again
+    pub fn reset_to(&mut self, checkpoint: Checkpoint) {
+        self.chars.reset_to(checkpoint)
+    }
+
+    /// Consumes an ordered list marker, see `ordered_marker`, when the number starting a line is
+    /// one followed by whitespace, and a string otherwise
+    fn consume_ordered_marker(&mut self) -> Token {
+        // the number and its delimiter are read speculatively, and given back to be read as a
+        // string when they don't make a marker
+        let checkpoint = self.chars.mark();
+        let mut marker = String::new();
+        while marker.len() < 10 && self.chars.current().char().is_ascii_digit() {
+            marker.push(self.chars.read().char());
+        }
+        if matches!(self.chars.current(), Bytes::Ch('.' | ')')) {
+            marker.push(self.chars.read().char());
+        }
+
+        let after = self.chars.current();
+        if after == Bytes::Eof || after.char().is_whitespace() {
+            if let Some(marker) = ordered_marker(&marker) {
+                return marker;
+            }
+        }
+        self.chars.reset_to(checkpoint);
+
+        self.consume_string()
+    }
+
     pub fn consume_string(&mut self) -> Token {
         let mut string = String::new();
         loop {
+            // characters that never end a string are read in runs, which is faster on ASCII input
+            let run = self.chars.read_while(is_plain);
+            match string.is_empty() {
+                true => string = run,
+                false => string.push_str(&run),
+            }
+
             let current = self.chars.current();
             let char = current.char();
 
@@ -156,10 +564,16 @@ impl<'a> Tokenizer<'a> {
                 || char == ']'
                 || char == '('
                 || char == ')'
-                || char == '#'
                 || char == '*'
                 || char == '_'
+                || char == '`'
+                || char == '~'
                 || char == '!'
+                || char == '<'
+                || char == '>'
+                || (char == '\\'
+                    && !string.is_empty()
+                    && self.chars.look_ahead(1).char().is_ascii_punctuation())
                 || current == Bytes::Eof
             {
                 break;
@@ -171,6 +585,53 @@ impl<'a> Tokenizer<'a> {
         Token::String(string)
     }
 
+    /// Consumes an HTML comment, which may span several lines
+    pub fn consume_comment(&mut self) -> Token {
+        let length = self.comment_length().unwrap_or(7);
+        let start = self.chars.tell();
+        let comment = self.chars.slice(start + 4, start + length - 3);
+        self.chars.skip(length);
+
+        Token::Comment(comment)
+    }
+
+    /// Returns the length of the HTML comment at the current position, including its `<!--` and
+    /// `-->` markers. An unclosed comment isn't a comment.
+    ///
+    /// The first `-->` found is remembered, so that the comments opened before it don't search
+    /// for it again, and neither do the ones opened after a search that found none.
+    fn comment_length(&mut self) -> Option<usize> {
+        let opening = (0..4).map(|i| self.chars.look_ahead(i).char());
+        if !opening.eq("<!--".chars()) {
+            return None;
+        }
+
+        let start = self.chars.tell();
+        let from = start + 4;
+        let end = match self.comment_end {
+            Some((searched, end)) if searched <= from && end.is_none_or(|end| from <= end) => end,
+            _ => {
+                let mut offset = 4;
+                let end = loop {
+                    match self.chars.look_ahead(offset) {
+                        Bytes::Eof => break None,
+                        Bytes::Ch('-')
+                            if self.chars.look_ahead(offset + 1) == Bytes::Ch('-')
+                                && self.chars.look_ahead(offset + 2) == Bytes::Ch('>') =>
+                        {
+                            break Some(start + offset)
+                        }
+                        _ => offset += 1,
+                    }
+                };
+                self.comment_end = Some((from, end));
+                end
+            }
+        };
+
+        end.map(|end| end + 3 - start)
+    }
+
     pub fn consume_delim(&mut self) -> Token {
         let mut count = 1;
         let delim = self.chars.read().char();
@@ -186,6 +647,7 @@ impl<'a> Tokenizer<'a> {
             '`' => Token::Backticks(count),
             '-' => Token::Dash(count),
             '_' => Token::Underscore(count),
+            '~' => Token::Tilde(count),
             // todo: better error handling
             _ => panic!("unexpected delim: {:?}", delim),
         }
@@ -200,7 +662,7 @@ mod test {
     #[test]
     fn consume_delims() {
         let mut chars = CharIterator::new();
-        chars.read_from_str("#####**```---__", Some(Encoding::UTF8));
+        chars.read_from_str("#####**```---__~~~", Some(Encoding::UTF8));
         let mut tokenizer = Tokenizer::new(&mut chars);
 
         assert_eq!(tokenizer.consume_delim(), Token::Hash(5));
@@ -208,6 +670,19 @@ mod test {
         assert_eq!(tokenizer.consume_delim(), Token::Backticks(3));
         assert_eq!(tokenizer.consume_delim(), Token::Dash(3));
         assert_eq!(tokenizer.consume_delim(), Token::Underscore(2));
+        assert_eq!(tokenizer.consume_delim(), Token::Tilde(3));
+    }
+
+    #[test]
+    fn consume_plus() {
+        let mut chars = CharIterator::new();
+        chars.read_from_str("+ a+b", Some(Encoding::UTF8));
+        let mut tokenizer = Tokenizer::new(&mut chars);
+
+        assert_eq!(tokenizer.consume(), Token::Plus);
+        assert_eq!(tokenizer.consume(), Token::Whitespace(" ".to_string()));
+        assert_eq!(tokenizer.consume(), Token::String("a+b".to_string()));
+        assert_eq!(tokenizer.consume(), Token::EOF);
     }
 
     #[test]
@@ -225,22 +700,26 @@ mod test {
         let mut tokenizer = Tokenizer::new(&mut chars);
 
         let tokens = vec![
-            Token::Whitespace("\n        ".to_string()),
+            Token::Whitespace("\n".to_string()),
+            Token::Indent(8),
             // ### heading
             Token::Hash(3),
             Token::Whitespace(" ".to_string()),
             Token::String("heading".to_string()),
-            Token::Whitespace("\n        ".to_string()),
+            Token::Whitespace("\n".to_string()),
+            Token::Indent(8),
             // **bold**
             Token::Asterisk(2),
             Token::String("bold".to_string()),
             Token::Asterisk(2),
-            Token::Whitespace("\n        ".to_string()),
+            Token::Whitespace("\n".to_string()),
+            Token::Indent(8),
             // _italic_
             Token::Underscore(1),
             Token::String("italic".to_string()),
             Token::Underscore(1),
-            Token::Whitespace("\n        ".to_string()),
+            Token::Whitespace("\n".to_string()),
+            Token::Indent(8),
             // [text](link)
             Token::OpeningBracket,
             Token::String("text".to_string()),
@@ -254,4 +733,369 @@ mod test {
             assert_eq!(tokenizer.consume(), token);
         }
     }
+
+    #[test]
+    fn consume_indent() {
+        let mut chars = CharIterator::new();
+        chars.read_from_str("    code", Some(Encoding::UTF8));
+        let mut tokenizer = Tokenizer::new(&mut chars);
+
+        assert_eq!(tokenizer.consume(), Token::Indent(4));
+        assert_eq!(tokenizer.consume(), Token::String("code".to_string()));
+
+        let mut chars = CharIterator::new();
+        chars.read_from_str("a    b", Some(Encoding::UTF8));
+        let mut tokenizer = Tokenizer::new(&mut chars);
+
+        assert_eq!(tokenizer.consume(), Token::String("a".to_string()));
+        assert_eq!(tokenizer.consume(), Token::Whitespace("    ".to_string()));
+        assert_eq!(tokenizer.consume(), Token::String("b".to_string()));
+    }
+
+    #[test]
+    fn consume_indent_after_newline() {
+        let mut chars = CharIterator::new();
+        chars.read_from_str("a\n  \n\t b", Some(Encoding::UTF8));
+        let mut tokenizer = Tokenizer::new(&mut chars);
+
+        assert_eq!(tokenizer.consume(), Token::String("a".to_string()));
+        assert_eq!(tokenizer.consume(), Token::Whitespace("\n  \n".to_string()));
+        assert_eq!(tokenizer.consume(), Token::Indent(5));
+        assert_eq!(tokenizer.consume(), Token::String("b".to_string()));
+    }
+
+    #[test]
+    fn consume_escapes() {
+        let mut chars = CharIterator::new();
+        chars.read_from_str("\\*a\\\\b\\c\\", Some(Encoding::UTF8));
+        let mut tokenizer = Tokenizer::new(&mut chars);
+
+        assert_eq!(tokenizer.consume(), Token::Escaped('*'));
+        assert_eq!(tokenizer.consume(), Token::String("a".to_string()));
+        assert_eq!(tokenizer.consume(), Token::Escaped('\\'));
+        assert_eq!(tokenizer.consume(), Token::String("b\\c\\".to_string()));
+        assert_eq!(tokenizer.consume(), Token::EOF);
+    }
+
+    #[test]
+    fn ascii_fast_path() {
+        let tokens = |source: &str| {
+            let mut chars = CharIterator::new();
+            chars.read_from_str(source, Some(Encoding::UTF8));
+            let ascii = chars.is_ascii();
+            let mut tokenizer = Tokenizer::new(&mut chars);
+
+            let mut tokens = Vec::new();
+            while !tokens.last().is_some_and(Token::is_eof) {
+                tokens.push(tokenizer.consume());
+            }
+            (ascii, tokens)
+        };
+        let source = "# a-b\r\n\n  - c+d *e_f*\t[g](h://i.j?k=l)\n> m\\n\\*o <!-- p -->q<r\r\n\n";
+
+        let (ascii, mut ascii_tokens) = tokens(source);
+        let (unicode, mut unicode_tokens) = tokens(&format!("{}é", source));
+        assert!(ascii && !unicode);
+        assert_eq!(unicode_tokens.pop(), Some(Token::EOF));
+        assert_eq!(unicode_tokens.pop(), Some(Token::String("é".to_string())));
+        ascii_tokens.pop();
+        assert_eq!(ascii_tokens, unicode_tokens);
+    }
+
+    #[test]
+    fn read_raw_content() {
+        let mut chars = CharIterator::new();
+        chars.read_from_str("*[a](b)\\`c` d", Some(Encoding::UTF8));
+        let mut tokenizer = Tokenizer::new(&mut chars);
+
+        assert_eq!(
+            tokenizer.read_raw_until('`', 1),
+            Some("*[a](b)\\".to_string())
+        );
+        assert_eq!(tokenizer.consume(), Token::String("c".to_string()));
+
+        let mut chars = CharIterator::new();
+        chars.read_from_str("a``b`)`` c", Some(Encoding::UTF8));
+        let mut tokenizer = Tokenizer::new(&mut chars);
+
+        assert_eq!(tokenizer.read_raw_until('`', 1), Some("a``b".to_string()));
+        assert_eq!(tokenizer.read_raw_until('`', 2), Some(")".to_string()));
+        assert_eq!(tokenizer.read_raw_until('`', 1), None);
+        assert_eq!(tokenizer.consume(), Token::Whitespace(" ".to_string()));
+
+        let mut chars = CharIterator::new();
+        chars.read_from_str("a\n`", Some(Encoding::UTF8));
+        let mut tokenizer = Tokenizer::new(&mut chars);
+
+        assert_eq!(tokenizer.read_raw_until('`', 1), None);
+        assert_eq!(tokenizer.read_raw_line(), "a");
+        assert_eq!(tokenizer.consume(), Token::Whitespace("\n".to_string()));
+        assert_eq!(tokenizer.read_raw_line(), "`");
+        assert_eq!(tokenizer.consume(), Token::EOF);
+    }
+
+    #[test]
+    fn consume_hash_in_text() {
+        let tokens = |source: &str| {
+            let mut chars = CharIterator::new();
+            chars.read_from_str(source, Some(Encoding::UTF8));
+            let mut tokenizer = Tokenizer::new(&mut chars);
+
+            let mut tokens = Vec::new();
+            while !tokens.last().is_some_and(Token::is_eof) {
+                tokens.push(tokenizer.consume());
+            }
+            tokens
+        };
+        let string = |s: &str| Token::String(s.to_string());
+        let space = || Token::Whitespace(" ".to_string());
+
+        assert_eq!(
+            tokens("C# is great"),
+            vec![
+                string("C#"),
+                space(),
+                string("is"),
+                space(),
+                string("great"),
+                Token::EOF
+            ]
+        );
+        assert_eq!(
+            tokens("see #42"),
+            vec![string("see"), space(), string("#42"), Token::EOF]
+        );
+        assert_eq!(
+            tokens("# heading\n  ## b"),
+            vec![
+                Token::Hash(1),
+                space(),
+                string("heading"),
+                Token::Whitespace("\n".to_string()),
+                Token::Indent(2),
+                Token::Hash(2),
+                space(),
+                string("b"),
+                Token::EOF
+            ]
+        );
+    }
+
+    #[test]
+    fn consume_punctuation_runs() {
+        let tests = vec![
+            ("wait...", vec![Token::String("wait...".to_string())]),
+            (
+                "what?? ok",
+                vec![
+                    Token::String("what??".to_string()),
+                    Token::Whitespace(" ".to_string()),
+                    Token::String("ok".to_string()),
+                ],
+            ),
+            (
+                "wow!!",
+                vec![
+                    Token::String("wow".to_string()),
+                    Token::ExclamationMark,
+                    Token::ExclamationMark,
+                ],
+            ),
+            (
+                "?!![",
+                vec![
+                    Token::String("?".to_string()),
+                    Token::ExclamationMark,
+                    Token::ExclamationMark,
+                    Token::OpeningBracket,
+                ],
+            ),
+        ];
+        for (source, tokens) in tests {
+            let mut chars = CharIterator::new();
+            chars.read_from_str(source, Some(Encoding::UTF8));
+            let mut tokenizer = Tokenizer::new(&mut chars);
+            let consumed = std::iter::from_fn(|| match tokenizer.consume() {
+                Token::EOF => None,
+                token => Some(token),
+            })
+            .collect::<Vec<_>>();
+            assert_eq!(consumed, tokens, "{:?}", source);
+        }
+    }
+
+    #[test]
+    fn consume_comments() {
+        let mut chars = CharIterator::new();
+        chars.read_from_str("a<!-- b\n-->c<!--", Some(Encoding::UTF8));
+        let mut tokenizer = Tokenizer::new(&mut chars);
+
+        assert_eq!(tokenizer.consume(), Token::String("a".to_string()));
+        assert_eq!(tokenizer.consume(), Token::Comment(" b\n".to_string()));
+        assert_eq!(tokenizer.consume(), Token::String("c".to_string()));
+        assert_eq!(tokenizer.consume(), Token::OpeningAngleBracket);
+        assert_eq!(tokenizer.consume(), Token::ExclamationMark);
+        assert_eq!(tokenizer.consume(), Token::Dash(2));
+        assert_eq!(tokenizer.consume(), Token::EOF);
+
+        // the end of a comment found before is found again, going back or not
+        let mut chars = CharIterator::new();
+        chars.read_from_str("<!--a<!--b-->c<!--d-->e<!--", Some(Encoding::UTF8));
+        let mut tokenizer = Tokenizer::new(&mut chars);
+        let start = tokenizer.mark();
+
+        assert_eq!(tokenizer.consume(), Token::Comment("a<!--b".to_string()));
+        tokenizer.reset_to(start);
+        assert_eq!(tokenizer.consume(), Token::Comment("a<!--b".to_string()));
+        assert_eq!(tokenizer.consume(), Token::String("c".to_string()));
+        assert_eq!(tokenizer.consume(), Token::Comment("d".to_string()));
+        assert_eq!(tokenizer.consume(), Token::String("e".to_string()));
+        assert_eq!(tokenizer.consume(), Token::OpeningAngleBracket);
+    }
+
+    #[test]
+    fn consume_angle_brackets() {
+        let mut chars = CharIterator::new();
+        chars.read_from_str("<a> x<http://x>", Some(Encoding::UTF8));
+        let mut tokenizer = Tokenizer::new(&mut chars);
+
+        let tokens = std::iter::from_fn(|| match tokenizer.consume() {
+            Token::EOF => None,
+            token => Some(token),
+        })
+        .collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::OpeningAngleBracket,
+                Token::String("a".to_string()),
+                Token::AngleBracket,
+                Token::Whitespace(" ".to_string()),
+                Token::String("x".to_string()),
+                Token::OpeningAngleBracket,
+                Token::String("http://x".to_string()),
+                Token::AngleBracket,
+            ]
+        );
+        assert_eq!(Token::from_str("<"), Ok(Token::OpeningAngleBracket));
+    }
+
+    #[test]
+    fn count_newlines() {
+        let tests = vec![
+            (Token::Whitespace("\n".to_string()), 1, false),
+            (Token::Whitespace(" \n \n".to_string()), 2, true),
+            (Token::Whitespace("  ".to_string()), 0, false),
+            (Token::String("a\nb".to_string()), 0, false),
+        ];
+        for (token, count, blank) in tests {
+            assert_eq!(token.newline_count(), count, "{:?}", token);
+            assert_eq!(token.is_blank_line(), blank, "{:?}", token);
+        }
+    }
+
+    /// A source reading straight from a list of chars
+    struct VecSource {
+        chars: Vec<char>,
+        offset: usize,
+    }
+
+    impl CharSource for VecSource {
+        fn read(&mut self) -> Bytes {
+            let char = self.look_ahead(0);
+            self.offset = std::cmp::min(self.offset + 1, self.chars.len());
+            char
+        }
+
+        fn look_ahead(&self, offset: usize) -> Bytes {
+            match self.chars.get(self.offset + offset) {
+                Some(char) => Bytes::Ch(*char),
+                None => Bytes::Eof,
+            }
+        }
+
+        fn tell(&self) -> usize {
+            self.offset
+        }
+
+        fn slice(&self, start: usize, end: usize) -> String {
+            let end = std::cmp::min(end, self.chars.len());
+            self.chars[std::cmp::min(start, end)..end].iter().collect()
+        }
+
+        fn mark(&self) -> Checkpoint {
+            Checkpoint::at_offset(self.offset)
+        }
+
+        fn reset_to(&mut self, checkpoint: Checkpoint) {
+            self.offset = checkpoint.offset();
+        }
+    }
+
+    #[test]
+    fn consume_from_char_source() {
+        let mut source = VecSource {
+            chars: "## a\n  [b]".chars().collect(),
+            offset: 0,
+        };
+        let mut tokenizer = Tokenizer::new(&mut source);
+
+        let tokens = vec![
+            Token::Hash(2),
+            Token::Whitespace(" ".to_string()),
+            Token::String("a".to_string()),
+            Token::Whitespace("\n".to_string()),
+            Token::Indent(2),
+            Token::OpeningBracket,
+            Token::String("b".to_string()),
+            Token::ClosingBracket,
+            Token::EOF,
+        ];
+        for token in tokens {
+            assert_eq!(tokenizer.consume(), token);
+        }
+        assert_eq!(tokenizer.source(), "## a\n  [b]");
+    }
+
+    #[test]
+    fn ordered_marker_round_trip() {
+        let tests = vec![
+            ("3.", 3, Delimiter::Period),
+            ("10)", 10, Delimiter::Parenthesis),
+        ];
+        for (source, number, delim) in tests {
+            let marker = Token::OrderedMarker { number, delim };
+            assert_eq!(Token::from_str(source), Ok(marker.clone()));
+            assert_eq!(marker.to_string(), source);
+
+            let mut chars = CharIterator::new();
+            chars.read_from_str(&format!("{} a", source), Some(Encoding::UTF8));
+            let mut tokenizer = Tokenizer::new(&mut chars);
+            assert_eq!(tokenizer.consume(), marker);
+        }
+
+        assert_eq!(Token::from_str("03."), Err(ParseTokenError));
+        assert_eq!(Token::from_str("1234567890."), Err(ParseTokenError));
+
+        let mut chars = CharIterator::new();
+        chars.read_from_str("3.5 a 3. b", Some(Encoding::UTF8));
+        let mut tokenizer = Tokenizer::new(&mut chars);
+        assert_eq!(tokenizer.consume(), Token::String("3.5".to_string()));
+        tokenizer.consume();
+        tokenizer.consume();
+        tokenizer.consume();
+        assert_eq!(tokenizer.consume(), Token::String("3.".to_string()));
+
+        // numbers that aren't markers are read again from their start
+        let mut chars = CharIterator::new();
+        chars.read_from_str("1234567890. a\n12", Some(Encoding::UTF8));
+        let mut tokenizer = Tokenizer::new(&mut chars);
+        let string = |s: &str| Token::String(s.to_string());
+        assert_eq!(tokenizer.consume(), string("1234567890."));
+        assert_eq!(tokenizer.consume(), Token::Whitespace(" ".to_string()));
+        assert_eq!(tokenizer.consume(), string("a"));
+        assert_eq!(tokenizer.consume(), Token::Whitespace("\n".to_string()));
+        assert_eq!(tokenizer.consume(), string("12"));
+        assert_eq!(tokenizer.consume(), Token::EOF);
+    }
 }
@@ -0,0 +1,146 @@
+use crate::parser::{Document, Element, InlineToken};
+use crate::smart::smarten;
+
+/// A change made to a document as a whole, so that changes can be chained, see `Document::apply`
+pub trait Transform {
+    fn apply(&self, document: &mut Document);
+}
+
+impl Document {
+    /// Applies the transforms in order, each one to the result of the one before it
+    pub fn apply(&mut self, transforms: &[Box<dyn Transform>]) {
+        for transform in transforms {
+            transform.apply(self);
+        }
+    }
+}
+
+/// Adds to the level of every heading, up to level 6, the ones in block quotes and list items
+/// included
+#[derive(Debug, Clone, Copy)]
+pub struct ShiftHeadings(pub usize);
+
+impl Transform for ShiftHeadings {
+    fn apply(&self, document: &mut Document) {
+        document.map_elements(true, |element| match element {
+            Element::Heading(mut heading) => {
+                heading.level = std::cmp::min(heading.level + self.0, 6);
+                Element::Heading(heading)
+            }
+            element => element,
+        });
+    }
+}
+
+/// Replaces the href of every link by the result of the function, see `Document::rewrite_links`
+pub struct RewriteLinks<F>(pub F);
+
+impl<F: Fn(&str) -> String> Transform for RewriteLinks<F> {
+    fn apply(&self, document: &mut Document) {
+        document.rewrite_links(&self.0);
+    }
+}
+
+/// Turns straight quotes, dashes and ellipses into their typographic form in the text of every
+/// block, see `smart::smarten`
+#[derive(Debug, Clone, Copy)]
+pub struct SmartPunctuation;
+
+impl Transform for SmartPunctuation {
+    fn apply(&self, document: &mut Document) {
+        map_inline_runs(&mut document.elements, &mut smarten);
+    }
+}
+
+/// Inserts a table of contents at the start of the document: the list of links to its headings
+/// of `max_level` or above, see `Document::to_outline_markdown`. Nothing is inserted for a
+/// document without such headings.
+#[derive(Debug, Clone, Copy)]
+pub struct InsertOutline {
+    pub max_level: usize,
+}
+
+impl Transform for InsertOutline {
+    fn apply(&self, document: &mut Document) {
+        let outline = crate::parse(&document.to_outline_markdown(self.max_level));
+        for (index, element) in outline.elements.into_iter().enumerate() {
+            document.elements.insert(index, element);
+            // the outline isn't part of the source, its span is empty
+            if !document.spans.is_empty() {
+                document.spans.insert(index, 0..0);
+            }
+            if !document.descendant_spans.is_empty() {
+                document.descendant_spans.insert(index, Vec::new());
+            }
+        }
+    }
+}
+
+/// Replaces the inline tokens of every heading, paragraph and table cell by the result of `f`,
+/// wherever the block is nested
+fn map_inline_runs<F>(elements: &mut [Element], f: &mut F)
+where
+    F: FnMut(Vec<InlineToken>) -> Vec<InlineToken>,
+{
+    for element in elements {
+        match element {
+            Element::Heading(heading) => heading.tokens = f(std::mem::take(&mut heading.tokens)),
+            Element::Paragraph(paragraph) => paragraph.0 = f(std::mem::take(&mut paragraph.0)),
+            Element::List(list) => {
+                for item in &mut list.items {
+                    map_inline_runs(item, f);
+                }
+            }
+            Element::BlockQuote(elements) => map_inline_runs(elements, f),
+            Element::Table(table) => {
+                for cell in table
+                    .header
+                    .iter_mut()
+                    .chain(table.rows.iter_mut().flatten())
+                {
+                    *cell = f(std::mem::take(cell));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+    use crate::text::render_text;
+
+    #[test]
+    fn chain_transforms() {
+        let mut document = parse("# Intro\n\nSee [the docs](/docs).\n\n> ## Details");
+        let transforms: Vec<Box<dyn Transform>> = vec![
+            Box::new(ShiftHeadings(1)),
+            Box::new(RewriteLinks(|href: &str| {
+                format!("https://example.com{}", href)
+            })),
+        ];
+        document.apply(&transforms);
+
+        assert_eq!(
+            crate::html::render_html(&document),
+            "<h2>Intro</h2>\n<p>See <a href=\"https://example.com/docs\">the docs</a>.</p>\n<blockquote>\n<h3>Details</h3>\n</blockquote>\n"
+        );
+    }
+
+    #[test]
+    fn insert_outline() {
+        let source = "# \"A\"\n\ntext...\n\n## B";
+        let mut document = parse(source);
+        let transforms: Vec<Box<dyn Transform>> = vec![
+            Box::new(SmartPunctuation),
+            Box::new(InsertOutline { max_level: 2 }),
+        ];
+        document.apply(&transforms);
+
+        assert_eq!(render_text(&document), "“A”\nB\n\n“A”\n\ntext…\n\nB\n");
+        assert_eq!(document.span_of(&document.elements[0]), Some(0..0));
+        assert_eq!(document.span_of(&document.elements[1]), Some(0..5));
+    }
+}
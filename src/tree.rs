@@ -0,0 +1,182 @@
+use crate::parser::{Alignment, Document, Element, FrontMatterKind, InlineToken};
+use crate::render::{walk, Node, Writer};
+
+impl Document {
+    /// Prints the document as a tree of nodes, one per line and indented by two spaces under the
+    /// node holding them, as in `Heading(1)` followed by `  Text "hi"`. Text is quoted like Rust
+    /// strings. Unlike the derived `Debug`, the output only changes with the content, which makes
+    /// it suited to golden tests.
+    pub fn to_debug_tree(&self) -> String {
+        let mut writer = TreeWriter {
+            output: String::new(),
+            depth: 0,
+        };
+        if let Some((kind, raw)) = &self.front_matter {
+            let kind = match kind {
+                FrontMatterKind::Yaml => "yaml",
+                FrontMatterKind::Toml => "toml",
+                FrontMatterKind::Json => "json",
+            };
+            writer.line(&format!("FrontMatter({}) {:?}", kind, raw));
+        }
+        walk(&self.elements, &mut writer);
+
+        writer.output
+    }
+}
+
+struct TreeWriter {
+    output: String,
+    depth: usize,
+}
+
+impl TreeWriter {
+    fn line(&mut self, line: &str) {
+        self.output.push_str(&"  ".repeat(self.depth));
+        self.output.push_str(line);
+        self.output.push('\n');
+    }
+}
+
+impl Writer for TreeWriter {
+    fn enter(&mut self, node: Node<'_>) -> bool {
+        let line = match node {
+            Node::Element(element) => element_label(element),
+            Node::Item(list, index) => match list.checked(index) {
+                Some(true) => "Item [x]".to_string(),
+                Some(false) => "Item [ ]".to_string(),
+                None => "Item".to_string(),
+            },
+            Node::Row(_, None) => "Header".to_string(),
+            Node::Row(_, Some(_)) => "Row".to_string(),
+            Node::Cell { alignment, .. } => match alignment {
+                Alignment::None => "Cell".to_string(),
+                Alignment::Left => "Cell(left)".to_string(),
+                Alignment::Center => "Cell(center)".to_string(),
+                Alignment::Right => "Cell(right)".to_string(),
+            },
+            Node::Inline(token) => inline_label(token),
+        };
+        self.line(&line);
+        self.depth += 1;
+
+        true
+    }
+
+    fn leave(&mut self, _: Node<'_>) {
+        self.depth -= 1;
+    }
+}
+
+fn element_label(element: &Element) -> String {
+    match element {
+        Element::Heading(heading) => {
+            let attributes = &heading.attributes;
+            let mut label = format!("Heading({})", heading.level);
+            if let Some(id) = &attributes.id {
+                label.push_str(&format!(" #{}", id));
+            }
+            for class in &attributes.classes {
+                label.push_str(&format!(" .{}", class));
+            }
+            label
+        }
+        Element::Paragraph(_) => "Paragraph".to_string(),
+        Element::List(list) => {
+            let loose = if list.loose { ", loose" } else { "" };
+            match list.start {
+                Some(start) => format!("List(ordered, {}{})", start, loose),
+                None => format!("List(unordered{})", loose),
+            }
+        }
+        Element::BlockQuote(_) => "BlockQuote".to_string(),
+        Element::CodeBlock(code_block) => match &code_block.lang {
+            Some(lang) => format!("CodeBlock({}) {:?}", lang, code_block.code),
+            None => format!("CodeBlock {:?}", code_block.code),
+        },
+        Element::Table(_) => "Table".to_string(),
+        Element::ThematicBreak => "ThematicBreak".to_string(),
+        Element::Comment(comment) => format!("Comment {:?}", comment),
+        Element::Html(html) => format!("Html {:?}", html),
+    }
+}
+
+fn inline_label(token: &InlineToken) -> String {
+    match token {
+        InlineToken::Text(text) => format!("Text {:?}", text),
+        InlineToken::Bold(_) => "Bold".to_string(),
+        InlineToken::Italic(_) => "Italic".to_string(),
+        InlineToken::Code(code_span) => match &code_span.lang {
+            Some(lang) => format!("Code({}) {:?}", lang, code_span.code),
+            None => format!("Code {:?}", code_span.code),
+        },
+        InlineToken::Link(link) => match &link.title {
+            Some(title) => format!("Link {:?} {:?}", link.href, title),
+            None => format!("Link {:?}", link.href),
+        },
+        InlineToken::Image(image) => match &image.title {
+            Some(title) => format!("Image {:?} {:?} {:?}", image.src, image.alt, title),
+            None => format!("Image {:?} {:?}", image.src, image.alt),
+        },
+        InlineToken::Comment(comment) => format!("Comment {:?}", comment),
+        InlineToken::LineBreak => "LineBreak".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse_with_options;
+    use crate::parser::{Alignment, Element, InlineToken, ParserOptions};
+
+    #[test]
+    fn golden_tree() {
+        let options = ParserOptions {
+            front_matter: true,
+            task_lists: true,
+            ..Default::default()
+        };
+        let mut document = parse_with_options(
+            "---\ntitle: x\n---\n# Hi *there*\n\n- [x] a [b](/c)\n- d\n\n> ```rust\n> e\n> ```",
+            options,
+        );
+        document.elements.push(Element::new_table(
+            vec![Alignment::Left, Alignment::Right],
+            vec![
+                vec![InlineToken::new_text("f")],
+                vec![InlineToken::new_text("g")],
+            ],
+            vec![vec![vec![InlineToken::new_code("h")]]],
+        ));
+
+        assert_eq!(
+            document.to_debug_tree(),
+            r#"FrontMatter(yaml) "title: x"
+Heading(1)
+  Text "Hi "
+  Italic
+    Text "there"
+List(unordered)
+  Item [x]
+    Paragraph
+      Text "a "
+      Link "/c"
+        Text "b"
+  Item
+    Paragraph
+      Text "d"
+BlockQuote
+  CodeBlock(rust) "e\n"
+Table
+  Header
+    Cell(left)
+      Text "f"
+    Cell(right)
+      Text "g"
+  Row
+    Cell(left)
+      Code "h"
+    Cell(right)
+"#
+        );
+    }
+}
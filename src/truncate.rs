@@ -0,0 +1,279 @@
+use crate::parser::{plain_text, Document, Element, Heading, InlineToken, Paragraph};
+use crate::text::{element_text, is_whitespace, word_count, words};
+
+const ELLIPSIS: &str = "…";
+
+impl Document {
+    /// Returns a copy of the document holding only its first `count` words, for previews and
+    /// excerpts. The cut is made between words, links, images, code spans and code blocks are
+    /// either kept whole or left out, and `…` is appended when anything was cut.
+    pub fn truncate_words(&self, count: usize) -> Document {
+        let mut truncation = Truncation {
+            remaining: count,
+            truncated: false,
+        };
+        let mut elements = truncation.elements(&self.elements);
+        if truncation.truncated && !append_ellipsis(&mut elements) {
+            elements.push(Element::new_paragraph(vec![InlineToken::new_text(
+                ELLIPSIS,
+            )]));
+        }
+
+        Document {
+            front_matter: self.front_matter.clone(),
+            ..Document::new(elements)
+        }
+    }
+}
+
+struct Truncation {
+    /// Number of words that can still be kept
+    remaining: usize,
+    /// True once content had to be left out
+    truncated: bool,
+}
+
+impl Truncation {
+    fn elements(&mut self, elements: &[Element]) -> Vec<Element> {
+        let mut kept = Vec::new();
+        for element in elements {
+            if self.truncated {
+                break;
+            }
+            if let Some(element) = self.element(element) {
+                kept.push(element);
+            }
+        }
+
+        kept
+    }
+
+    fn element(&mut self, element: &Element) -> Option<Element> {
+        let element = match element {
+            Element::Heading(heading) => Element::Heading(Heading {
+                level: heading.level,
+                tokens: self.inline_tokens(&heading.tokens),
+                attributes: heading.attributes.clone(),
+            }),
+            Element::Paragraph(paragraph) => {
+                Element::Paragraph(Paragraph(self.inline_tokens(&paragraph.0)))
+            }
+            Element::List(list) => {
+                let mut list = list.clone();
+                list.items = list
+                    .items
+                    .iter()
+                    .map(|item| self.elements(item))
+                    .take_while(|item| !item.is_empty())
+                    .collect();
+                Element::List(list)
+            }
+            Element::BlockQuote(elements) => Element::BlockQuote(self.elements(elements)),
+            Element::CodeBlock(code_block) => {
+                if !self.fits(&code_block.code) {
+                    return None;
+                }
+                Element::CodeBlock(code_block.clone())
+            }
+            // tables are kept whole, like code blocks
+            Element::Table(_) => {
+                if !self.fits(&element_text(element).unwrap_or_default()) {
+                    return None;
+                }
+                element.clone()
+            }
+            Element::ThematicBreak | Element::Comment(_) | Element::Html(_) => element.clone(),
+        };
+
+        // a block that lost all of its content is left out
+        match self.truncated && is_empty(&element) {
+            true => None,
+            false => Some(element),
+        }
+    }
+
+    fn inline_tokens(&mut self, tokens: &[InlineToken]) -> Vec<InlineToken> {
+        let mut kept = Vec::new();
+        for token in tokens {
+            if self.truncated {
+                break;
+            }
+
+            match token {
+                InlineToken::Text(text) => {
+                    let words = word_count(text);
+                    if words <= self.remaining {
+                        self.remaining -= words;
+                        kept.push(token.clone());
+                        continue;
+                    }
+
+                    let cut = first_words(text, self.remaining);
+                    if !cut.is_empty() {
+                        kept.push(InlineToken::new_text(cut));
+                    }
+                    self.remaining = 0;
+                    self.truncated = true;
+                }
+                InlineToken::Bold(tokens) | InlineToken::Italic(tokens) => {
+                    let tokens = self.inline_tokens(tokens);
+                    if tokens.is_empty() {
+                        continue;
+                    }
+                    kept.push(match token {
+                        InlineToken::Bold(_) => InlineToken::Bold(tokens),
+                        _ => InlineToken::Italic(tokens),
+                    });
+                }
+                InlineToken::Comment(_) | InlineToken::LineBreak => kept.push(token.clone()),
+                InlineToken::Link(_) | InlineToken::Image(_) | InlineToken::Code(_) => {
+                    if self.fits(&plain_text(std::slice::from_ref(token))) {
+                        kept.push(token.clone());
+                    }
+                }
+            }
+        }
+
+        kept
+    }
+
+    /// Takes the words of content that can't be cut from the budget, returns false and marks the
+    /// document truncated when they don't fit.
+    fn fits(&mut self, text: &str) -> bool {
+        let words = word_count(text);
+        if words > self.remaining {
+            self.truncated = true;
+            return false;
+        }
+
+        self.remaining -= words;
+        true
+    }
+}
+
+/// Returns the text up to the end of its `count`th word
+fn first_words(text: &str, count: usize) -> &str {
+    match count {
+        0 => "",
+        _ => words(text)
+            .nth(count - 1)
+            .map_or(text, |word| &text[..word.end]),
+    }
+}
+
+fn is_empty(element: &Element) -> bool {
+    let is_blank = |tokens: &[InlineToken]| {
+        tokens.iter().all(
+            |token| matches!(token, InlineToken::Text(text) if text.chars().all(is_whitespace)),
+        )
+    };
+
+    match element {
+        Element::Heading(heading) => is_blank(&heading.tokens),
+        Element::Paragraph(paragraph) => is_blank(&paragraph.0),
+        Element::List(list) => list.items.is_empty(),
+        Element::BlockQuote(elements) => elements.is_empty(),
+        Element::CodeBlock(_)
+        | Element::ThematicBreak
+        | Element::Comment(_)
+        | Element::Html(_)
+        | Element::Table(_) => false,
+    }
+}
+
+/// Appends the ellipsis to the text of the last element, returns false when it holds no text
+fn append_ellipsis(elements: &mut [Element]) -> bool {
+    let tokens = match elements.last_mut() {
+        Some(Element::Heading(heading)) => &mut heading.tokens,
+        Some(Element::Paragraph(paragraph)) => &mut paragraph.0,
+        Some(Element::List(list)) => {
+            return list
+                .items
+                .last_mut()
+                .is_some_and(|item| append_ellipsis(item))
+        }
+        Some(Element::BlockQuote(elements)) => return append_ellipsis(elements),
+        _ => return false,
+    };
+
+    match tokens.last_mut() {
+        Some(InlineToken::Text(text)) => {
+            text.truncate(text.trim_end_matches(is_whitespace).len());
+            text.push_str(ELLIPSIS);
+        }
+        _ => tokens.push(InlineToken::new_text(ELLIPSIS)),
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse;
+    use crate::text::render_text;
+
+    #[test]
+    fn truncate_to_words() {
+        let document = parse("# One *two*\n\nthree **four five six**\n\nseven eight");
+
+        let tests = vec![
+            (0, "…\n"),
+            (1, "One…\n"),
+            (5, "One two\n\nthree four five…\n"),
+            (6, "One two\n\nthree four five six…\n"),
+            (8, "One two\n\nthree four five six\n\nseven eight\n"),
+            (100, "One two\n\nthree four five six\n\nseven eight\n"),
+        ];
+        for (count, text) in tests {
+            assert_eq!(
+                render_text(&document.truncate_words(count)),
+                text,
+                "{}",
+                count
+            );
+        }
+    }
+
+    #[test]
+    fn truncate_around_links() {
+        let document = parse("a [b c](d) e\n\n- f\n- `g h`\n\n---");
+
+        let tests = vec![
+            (2, "a…\n"),
+            (3, "a b c…\n"),
+            (4, "a b c e…\n"),
+            (5, "a b c e\n\nf…\n"),
+            (7, "a b c e\n\nf\ng h\n"),
+        ];
+        for (count, text) in tests {
+            assert_eq!(
+                render_text(&document.truncate_words(count)),
+                text,
+                "{}",
+                count
+            );
+        }
+    }
+
+    #[test]
+    fn truncate_words_as_counted() {
+        // a non-breaking space joins words, Chinese and Japanese characters are words of their own
+        let document = parse("10\u{A0}km away\n\nRust 是一种语言。");
+        assert_eq!(document.word_count(), 8);
+
+        let tests = vec![
+            (1, "10\u{A0}km…\n"),
+            (3, "10\u{A0}km away\n\nRust…\n"),
+            (5, "10\u{A0}km away\n\nRust 是一…\n"),
+            (8, "10\u{A0}km away\n\nRust 是一种语言。\n"),
+        ];
+        for (count, text) in tests {
+            assert_eq!(
+                render_text(&document.truncate_words(count)),
+                text,
+                "{}",
+                count
+            );
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use crate::parser::{Document, Element};
+use std::fmt;
+use std::ops::Range;
+
+/// The rules a document is checked against, see `validate`. All of them are off by default.
+#[derive(Debug, Default, Clone)]
+pub struct Rules {
+    /// Flags headings of a deeper level
+    pub max_heading_level: Option<usize>,
+    /// Flags lists nested in more list items than this, a top-level list being at depth 1
+    pub max_list_depth: Option<usize>,
+    /// Flags a heading more than one level deeper than the heading before it, like an `h3` right
+    /// after an `h1`
+    pub skipped_heading_levels: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Violation {
+    HeadingTooDeep { level: usize, max: usize },
+    ListTooDeep { depth: usize, max: usize },
+    SkippedHeadingLevel { previous: usize, level: usize },
+}
+
+/// A rule the document breaks
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ValidationError {
+    pub violation: Violation,
+    /// Source offsets (in chars) of the top-level element holding the offending one, empty for
+    /// documents that weren't parsed
+    pub span: Range<usize>,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.violation {
+            Violation::HeadingTooDeep { level, max } => {
+                write!(f, "Heading of level {} is deeper than level {}", level, max)?
+            }
+            Violation::ListTooDeep { depth, max } => write!(
+                f,
+                "List nested at depth {} is deeper than depth {}",
+                depth, max
+            )?,
+            Violation::SkippedHeadingLevel { previous, level } => write!(
+                f,
+                "Heading of level {} follows a heading of level {}",
+                level, previous
+            )?,
+        }
+        write!(f, " at offset {}", self.span.start)
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Checks the document against the rules, returning the violations in document order. Headings
+/// and lists inside block quotes and list items are checked as well. A list nested too deep is
+/// reported once, not along with the lists nested in it.
+pub fn validate(document: &Document, rules: &Rules) -> Vec<ValidationError> {
+    let mut validator = Validator {
+        rules,
+        errors: Vec::new(),
+        previous_level: None,
+        span: 0..0,
+    };
+    for (index, element) in document.elements.iter().enumerate() {
+        validator.span = document.spans.get(index).cloned().unwrap_or_default();
+        validator.check_element(element, 0);
+    }
+
+    validator.errors
+}
+
+struct Validator<'a> {
+    rules: &'a Rules,
+    errors: Vec<ValidationError>,
+    /// Level of the last heading
+    previous_level: Option<usize>,
+    /// Span of the top-level element being checked
+    span: Range<usize>,
+}
+
+impl Validator<'_> {
+    /// Checks an element nested in `depth` lists
+    fn check_element(&mut self, element: &Element, depth: usize) {
+        match element {
+            Element::Heading(heading) => {
+                let level = heading.level;
+                if let Some(max) = self.rules.max_heading_level {
+                    if level > max {
+                        self.push(Violation::HeadingTooDeep { level, max });
+                    }
+                }
+                if let Some(previous) = self.previous_level {
+                    if self.rules.skipped_heading_levels && level > previous + 1 {
+                        self.push(Violation::SkippedHeadingLevel { previous, level });
+                    }
+                }
+                self.previous_level = Some(level);
+            }
+            Element::List(list) => {
+                let depth = depth + 1;
+                if let Some(max) = self.rules.max_list_depth {
+                    if depth > max {
+                        self.push(Violation::ListTooDeep { depth, max });
+                        return;
+                    }
+                }
+                for element in list.items.iter().flatten() {
+                    self.check_element(element, depth);
+                }
+            }
+            Element::BlockQuote(elements) => {
+                for element in elements {
+                    self.check_element(element, depth);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn push(&mut self, violation: Violation) {
+        self.errors.push(ValidationError {
+            violation,
+            span: self.span.clone(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn validate_headings() {
+        let document = parse("# A\n\n### B\n\n## C\n\n#### D\n\n###### E");
+        let rules = Rules {
+            max_heading_level: Some(5),
+            skipped_heading_levels: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            validate(&document, &rules),
+            vec![
+                ValidationError {
+                    violation: Violation::SkippedHeadingLevel {
+                        previous: 1,
+                        level: 3
+                    },
+                    span: 5..10,
+                },
+                ValidationError {
+                    violation: Violation::SkippedHeadingLevel {
+                        previous: 2,
+                        level: 4
+                    },
+                    span: 18..24,
+                },
+                ValidationError {
+                    violation: Violation::HeadingTooDeep { level: 6, max: 5 },
+                    span: 26..34,
+                },
+                ValidationError {
+                    violation: Violation::SkippedHeadingLevel {
+                        previous: 4,
+                        level: 6
+                    },
+                    span: 26..34,
+                },
+            ]
+        );
+        assert_eq!(
+            validate(&document, &rules)[0].to_string(),
+            "Heading of level 3 follows a heading of level 1 at offset 5"
+        );
+        assert!(validate(&document, &Rules::default()).is_empty());
+    }
+
+    #[test]
+    fn validate_list_depth() {
+        let document = parse("- a\n  - b\n    - c\n      - d\n\n> - e\n>   - f");
+        let rules = Rules {
+            max_list_depth: Some(2),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            validate(&document, &rules),
+            vec![ValidationError {
+                violation: Violation::ListTooDeep { depth: 3, max: 2 },
+                span: 0..27,
+            }]
+        );
+    }
+}
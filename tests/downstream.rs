@@ -0,0 +1,29 @@
+//! Matches on the public enums the way a downstream crate has to, with a wildcard arm for the
+//! variants added in later versions
+
+use mdrs::parser::{Element, InlineToken};
+use mdrs::tokenizer::TokenKind;
+
+fn describe(element: &Element) -> &'static str {
+    match element {
+        Element::Heading(_) => "heading",
+        Element::Paragraph(_) => "paragraph",
+        _ => "other",
+    }
+}
+
+#[test]
+fn match_with_wildcards() {
+    let document = mdrs::parse("# a\n\nb\n\n---");
+    let kinds = document.into_iter().map(describe).collect::<Vec<_>>();
+    assert_eq!(kinds, vec!["heading", "paragraph", "other"]);
+
+    let text = match mdrs::parse_inline("a").first() {
+        Some(InlineToken::Text(text)) => text.clone(),
+        _ => String::new(),
+    };
+    assert_eq!(text, "a");
+
+    let kind = TokenKind::Hash;
+    assert!(!matches!(kind, TokenKind::Asterisk));
+}